@@ -0,0 +1,412 @@
+//! 归档文件（zip、tar、tar.gz、tar.zst）作为虚拟目录：成员以 `bundle.zip!/src/lib.rs`
+//! 这样的路径参与收集。收集时只读取目录（路径、大小、修改时间），内容在输出该成员时
+//! 才解压，之后与普通文件走同样的读取与渲染。
+//!
+//! zip 按索引直接读取成员；tar 没有索引，从头解压时顺带缓存之后要读的成员（总量有上限），
+//! 整个归档通常只需扫描一遍。
+//!
+//! 解压需要 `archives` 特性；未启用时不识别归档，它们按普通文件读取。
+
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "archives")]
+use std::fs::File;
+#[cfg(feature = "archives")]
+use std::io::BufReader;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+#[cfg(feature = "archives")]
+use std::time::Duration;
+use std::time::SystemTime;
+
+// 扫描 tar 时预先解压、留待之后读取的成员总大小上限
+const PREFETCH_BYTES: u64 = 64 << 20;
+
+/// 支持的归档格式，按文件名后缀识别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    Zip,
+    Tar,
+    TarGz,
+    TarZst,
+}
+
+impl Kind {
+    pub(crate) fn from_path(path: &Path) -> Option<Self> {
+        if !cfg!(feature = "archives") {
+            return None;
+        }
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(Kind::Zip)
+        } else if name.ends_with(".tar") {
+            Some(Kind::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Kind::TarGz)
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Some(Kind::TarZst)
+        } else {
+            None
+        }
+    }
+}
+
+/// 归档目录中的一个文件
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Member {
+    /// 归档头中记录的解压后大小
+    pub(crate) size: u64,
+    pub(crate) mtime: Option<SystemTime>,
+    /// 在归档中的序号（zip 按它直接定位）
+    #[cfg_attr(not(feature = "archives"), allow(dead_code))]
+    index: usize,
+}
+
+/// 拆分指向归档内部的条目（`bundle.zip`、`bundle.zip/src/**/*.rs` 或 `bundle.zip!/src/lib.rs`），
+/// 得到归档文件与其中的路径或模式；条目不经过归档文件时返回 None
+pub(crate) fn split_item(item: &str) -> Option<(PathBuf, String)> {
+    let mut components = Path::new(item).components();
+    let mut prefix = PathBuf::new();
+    while let Some(component) = components.next() {
+        let Component::Normal(name) = component else {
+            prefix.push(component);
+            continue;
+        };
+        let name = name.to_str()?;
+        let candidate = prefix.join(name.strip_suffix('!').unwrap_or(name));
+        if Kind::from_path(&candidate).is_some() && candidate.is_file() {
+            let rest: Vec<_> = components
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect();
+            return Some((candidate, rest.join("/")));
+        }
+        prefix.push(name);
+    }
+    None
+}
+
+/// 成员的路径：归档路径加 `!` 后接成员路径
+pub(crate) fn join(archive: &Path, member: &Path) -> PathBuf {
+    let mut path = archive.as_os_str().to_os_string();
+    path.push("!");
+    PathBuf::from(path).join(member)
+}
+
+/// 归档中的文件及其大小与修改时间；不解压内容
+pub(crate) fn list(path: &Path) -> anyhow::Result<BTreeMap<PathBuf, Member>> {
+    let mut files = BTreeMap::new();
+    for_each_file(path, |index, name, size, mtime, _| {
+        files.insert(name, Member { size, mtime, index });
+        Ok(false)
+    })?;
+    Ok(files)
+}
+
+/// 一个归档中待读取的成员，供各成员共享读取
+#[derive(Debug)]
+pub(crate) struct Batch {
+    path: PathBuf,
+    members: BTreeMap<PathBuf, Member>,
+    /// 大于它的成员不预先解压（它们按 `--max-size` 跳过，不会被读取）
+    prefetch_limit: u64,
+    state: Mutex<BatchState>,
+}
+
+#[derive(Debug, Default)]
+struct BatchState {
+    #[cfg(feature = "archives")]
+    zip: Option<zip::ZipArchive<BufReader<File>>>,
+    /// 尚未读取也未缓存的成员
+    pending: BTreeSet<PathBuf>,
+    /// 扫描 tar 时预先解压的成员
+    cached: BTreeMap<PathBuf, Vec<u8>>,
+    cached_bytes: u64,
+}
+
+impl Batch {
+    pub(crate) fn new(
+        path: PathBuf,
+        members: BTreeMap<PathBuf, Member>,
+        max_size: Option<u64>,
+    ) -> Self {
+        let pending = members.keys().cloned().collect();
+        Self {
+            path,
+            prefetch_limit: max_size.unwrap_or(u64::MAX),
+            members,
+            state: Mutex::new(BatchState {
+                pending,
+                ..BatchState::default()
+            }),
+        }
+    }
+
+    /// 解压一个成员，最多读取 `list` 给出的大小，不按归档头预分配内存
+    pub(crate) fn read(&self, name: &Path) -> anyhow::Result<Vec<u8>> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(bytes) = state.cached.remove(name) {
+            state.cached_bytes -= bytes.len() as u64;
+            return Ok(bytes);
+        }
+        state.pending.remove(name);
+        let Some(&member) = self.members.get(name) else {
+            anyhow::bail!("'{}' not found in {}", name.display(), self.path.display());
+        };
+        let result = match Kind::from_path(&self.path) {
+            #[cfg(feature = "archives")]
+            Some(Kind::Zip) => read_zip(&self.path, &mut state, member),
+            _ => self.scan_tar(&mut state, name, member),
+        };
+        result.map_err(|e| anyhow::anyhow!("cannot read archive {}: {}", self.path.display(), e))
+    }
+
+    // 从头扫描到 `name`，途中与之后遇到的待读成员在上限内一并解压缓存
+    fn scan_tar(
+        &self,
+        state: &mut BatchState,
+        name: &Path,
+        member: Member,
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut found = None;
+        for_each_file(&self.path, |_, entry_name, _, _, reader| {
+            if found.is_none() && entry_name == name {
+                found = Some(read_capped(reader, member.size)?);
+            } else if state.pending.contains(&entry_name) {
+                let other = self.members[&entry_name];
+                if other.size <= self.prefetch_limit
+                    && state.cached_bytes + other.size <= PREFETCH_BYTES
+                {
+                    let bytes = read_capped(reader, other.size)?;
+                    state.pending.remove(&entry_name);
+                    state.cached_bytes += bytes.len() as u64;
+                    state.cached.insert(entry_name, bytes);
+                }
+            }
+            let full = state.pending.is_empty() || state.cached_bytes >= PREFETCH_BYTES;
+            Ok(found.is_some() && full)
+        })?;
+        found.ok_or_else(|| anyhow::anyhow!("'{}' not found", name.display()))
+    }
+}
+
+#[cfg(feature = "archives")]
+fn read_zip(path: &Path, state: &mut BatchState, member: Member) -> anyhow::Result<Vec<u8>> {
+    let zip = match &mut state.zip {
+        Some(zip) => zip,
+        slot => slot.insert(zip::ZipArchive::new(BufReader::new(File::open(path)?))?),
+    };
+    let mut entry = zip.by_index(member.index)?;
+    read_capped(&mut entry, member.size)
+}
+
+fn read_capped(reader: &mut dyn Read, limit: u64) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(limit).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+// 访问一个文件（序号、路径、大小、修改时间、内容）；返回 true 时停止遍历
+#[cfg(feature = "archives")]
+type Visit<'a> =
+    dyn FnMut(usize, PathBuf, u64, Option<SystemTime>, &mut dyn Read) -> anyhow::Result<bool> + 'a;
+
+// 依次访问归档中的普通文件；目录、链接以及越出归档根目录的路径跳过
+#[cfg(feature = "archives")]
+fn for_each_file(
+    path: &Path,
+    mut visit: impl FnMut(
+        usize,
+        PathBuf,
+        u64,
+        Option<SystemTime>,
+        &mut dyn Read,
+    ) -> anyhow::Result<bool>,
+) -> anyhow::Result<()> {
+    let Some(kind) = Kind::from_path(path) else {
+        anyhow::bail!("not an archive: {}", path.display());
+    };
+    let file = File::open(path)?;
+    let result = match kind {
+        Kind::Zip => for_each_zip(file, &mut visit),
+        Kind::Tar => for_each_tar(BufReader::new(file), &mut visit),
+        Kind::TarGz => for_each_tar(
+            flate2::read::GzDecoder::new(BufReader::new(file)),
+            &mut visit,
+        ),
+        Kind::TarZst => for_each_tar(zstd::Decoder::new(file)?, &mut visit),
+    };
+    result.map_err(|e| anyhow::anyhow!("cannot read archive {}: {}", path.display(), e))
+}
+
+#[cfg(not(feature = "archives"))]
+fn for_each_file(
+    path: &Path,
+    _: impl FnMut(usize, PathBuf, u64, Option<SystemTime>, &mut dyn Read) -> anyhow::Result<bool>,
+) -> anyhow::Result<()> {
+    anyhow::bail!("not an archive: {}", path.display())
+}
+
+#[cfg(feature = "archives")]
+fn for_each_zip(file: File, visit: &mut Visit) -> anyhow::Result<()> {
+    let mut zip = zip::ZipArchive::new(BufReader::new(file))?;
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index)?;
+        if !entry.is_file() {
+            continue;
+        }
+        let Some(name) = entry.enclosed_name().and_then(|name| member_name(&name)) else {
+            continue;
+        };
+        // zip 的修改时间是不带时区的本地时间，不记录
+        let size = entry.size();
+        if visit(index, name, size, None, &mut entry)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "archives")]
+fn for_each_tar(reader: impl Read, visit: &mut Visit) -> anyhow::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    for (index, entry) in archive.entries()?.enumerate() {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let Some(name) = member_name(&entry.path()?) else {
+            continue;
+        };
+        let size = entry.size();
+        let mtime = entry
+            .header()
+            .mtime()
+            .ok()
+            .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        if visit(index, name, size, mtime, &mut entry)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// 去掉 `./`；含 `..` 或绝对路径的成员不接受
+#[cfg(feature = "archives")]
+fn member_name(path: &Path) -> Option<PathBuf> {
+    let mut name = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => name.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!name.as_os_str().is_empty()).then_some(name)
+}
+
+#[cfg(all(test, feature = "archives"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinds_and_member_names_are_recognised() {
+        assert_eq!(Kind::from_path(Path::new("a/Bundle.ZIP")), Some(Kind::Zip));
+        assert_eq!(Kind::from_path(Path::new("r.tgz")), Some(Kind::TarGz));
+        assert_eq!(Kind::from_path(Path::new("r.tar.zst")), Some(Kind::TarZst));
+        assert_eq!(Kind::from_path(Path::new("r.gz")), None);
+
+        assert_eq!(
+            member_name(Path::new("./src/lib.rs")),
+            Some(PathBuf::from("src/lib.rs"))
+        );
+        assert_eq!(member_name(Path::new("../etc/passwd")), None);
+        assert_eq!(member_name(Path::new("/abs")), None);
+        assert_eq!(member_name(Path::new("./")), None);
+
+        assert_eq!(
+            join(Path::new("dist/b.zip"), Path::new("src/lib.rs")),
+            PathBuf::from("dist/b.zip!/src/lib.rs")
+        );
+    }
+
+    #[test]
+    fn members_are_listed_without_reading_and_read_up_to_the_limit() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = temp.path().join("r.tar");
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, body) in [("a.txt", "0123456789"), ("dir/b.txt", "b\n")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(body.len() as u64);
+            header.set_mtime(1_700_000_000);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, body.as_bytes())
+                .unwrap();
+        }
+        std::fs::write(&path, builder.into_inner().unwrap()).unwrap();
+
+        let listed = list(&path).unwrap();
+        assert_eq!(listed.len(), 2);
+        let a = listed[Path::new("a.txt")];
+        assert_eq!(a.size, 10);
+        assert_eq!(
+            a.mtime,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+
+        let mut capped = listed.clone();
+        capped.get_mut(Path::new("a.txt")).unwrap().size = 4;
+        let batch = Batch::new(path.clone(), capped, None);
+        assert_eq!(batch.read(Path::new("a.txt")).unwrap(), b"0123");
+        assert_eq!(batch.read(Path::new("dir/b.txt")).unwrap(), b"b\n");
+        // 读过的成员可以再次读取
+        assert_eq!(batch.read(Path::new("dir/b.txt")).unwrap(), b"b\n");
+        let err = batch.read(Path::new("c.txt")).unwrap_err();
+        assert!(err.to_string().starts_with("'c.txt' not found in "));
+    }
+
+    #[test]
+    fn tar_members_are_read_in_one_pass() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = temp.path().join("r.tar.gz");
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        for i in 0..50 {
+            let body = format!("file {i}\n");
+            let mut header = tar::Header::new_gnu();
+            header.set_size(body.len() as u64);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, format!("f{i:02}.txt"), body.as_bytes())
+                .unwrap();
+        }
+        let big = vec![b'x'; 100];
+        let mut header = tar::Header::new_gnu();
+        header.set_size(big.len() as u64);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "big.txt", &big[..])
+            .unwrap();
+        std::fs::write(&path, builder.into_inner().unwrap().finish().unwrap()).unwrap();
+
+        let batch = Batch::new(path.clone(), list(&path).unwrap(), Some(10));
+        // 读取中间的一个成员时，其余待读成员已一并解压，超过大小上限的除外
+        assert_eq!(batch.read(Path::new("f25.txt")).unwrap(), b"file 25\n");
+        {
+            let state = batch.state.lock().unwrap();
+            assert_eq!(state.cached.len(), 49);
+            assert_eq!(
+                state.pending.iter().collect::<Vec<_>>(),
+                [Path::new("big.txt")]
+            );
+        }
+        for i in (0..50).filter(|&i| i != 25) {
+            let body = batch.read(Path::new(&format!("f{i:02}.txt"))).unwrap();
+            assert_eq!(body, format!("file {i}\n").as_bytes());
+        }
+        assert_eq!(batch.state.lock().unwrap().cached_bytes, 0);
+        assert_eq!(batch.read(Path::new("big.txt")).unwrap(), big);
+    }
+}
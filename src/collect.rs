@@ -0,0 +1,684 @@
+//! 文件收集：目录遍历、glob 模式、归档文件与 `--rev` 版本树的匹配，排除规则与排序。
+
+use crate::{archive, git, LineRange};
+use clap::ValueEnum;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::overrides::OverrideBuilder;
+use ignore::WalkBuilder;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// 按条目收集文件：目录递归遍历，glob 模式按工作目录匹配，归档文件按其中的文件匹配，
+/// `--rev` 时改为匹配版本树；扩展名、忽略规则与排除模式统一生效
+pub struct Collector {
+    ext: Option<String>,
+    walk: WalkOptions,
+    excludes: Option<Gitignore>,
+    rev_tree: Option<BTreeMap<PathBuf, u64>>,
+    files: BTreeSet<PathBuf>,
+    /// 归档中的文件（键为 `bundle.zip!/src/lib.rs` 形式的路径）及其所在归档与成员路径
+    members: BTreeMap<PathBuf, (PathBuf, PathBuf, archive::Member)>,
+    max_size: Option<u64>,
+    has_items: bool,
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector {
+    /// 默认跟随符号链接并应用 .gitignore 等忽略规则
+    pub fn new() -> Self {
+        Self {
+            ext: None,
+            walk: WalkOptions {
+                follow_links: true,
+                respect_ignore: true,
+            },
+            excludes: None,
+            rev_tree: None,
+            files: BTreeSet::new(),
+            members: BTreeMap::new(),
+            max_size: None,
+            has_items: false,
+        }
+    }
+
+    /// 目录只收集这些扩展名（逗号分隔，不区分大小写）的文件
+    pub fn ext(mut self, exts: Option<&str>) -> Self {
+        self.ext = exts.map(str::to_string);
+        self
+    }
+
+    pub fn follow_links(mut self, follow: bool) -> Self {
+        self.walk.follow_links = follow;
+        self
+    }
+
+    /// 是否应用 .gitignore / .ignore / git exclude 规则
+    pub fn respect_ignore(mut self, respect: bool) -> Self {
+        self.walk.respect_ignore = respect;
+        self
+    }
+
+    /// 输出时将按 `--max-size` 跳过的大小；超过它的归档成员不会预先解压
+    pub fn max_size(mut self, max_size: Option<u64>) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// 排除模式（gitignore 语法，以当前工作目录为根）
+    pub fn exclude(mut self, patterns: &[String]) -> anyhow::Result<Self> {
+        self.excludes = build_exclude_matcher(patterns)?;
+        Ok(self)
+    }
+
+    /// 条目改为按 git 版本的树匹配
+    pub fn rev(mut self, rev: &str) -> anyhow::Result<Self> {
+        git::verify_rev(rev)?;
+        self.rev_tree = Some(git::tree_files(rev)?);
+        Ok(self)
+    }
+
+    /// 添加一个目录或 glob 模式；不含通配符的路径原样加入。
+    /// 归档文件（zip/tar/tar.gz/tar.zst）作为目录展开，`bundle.zip/src/**/*.rs` 匹配其中的文件
+    pub fn add(&mut self, item: &str) -> anyhow::Result<()> {
+        self.has_items = true;
+        if let Some(tree) = &self.rev_tree {
+            let matched = match_tree(item, tree, self.ext.as_deref())?;
+            self.files.extend(
+                matched.ok_or_else(|| anyhow::anyhow!("'{}' not found in revision", item))?,
+            );
+            return Ok(());
+        }
+        let path = Path::new(item);
+        if path.is_dir() {
+            return collect_dir(path, self.ext.as_deref(), &mut self.files, self.walk);
+        }
+        if let Some((path, inner)) = archive::split_item(item) {
+            return self.add_archive(&path, &inner);
+        }
+        for path in expand_glob(item, self.walk)? {
+            if path.is_file() {
+                self.files.insert(normalize(&path));
+            }
+        }
+        Ok(())
+    }
+
+    /// 按原样添加一个路径（不做通配展开）；返回路径是否存在
+    pub fn add_path(&mut self, path: &Path) -> anyhow::Result<bool> {
+        self.has_items = true;
+        if let Some(tree) = &self.rev_tree {
+            let token = path.to_string_lossy();
+            let matched = match_tree(&token, tree, self.ext.as_deref())?;
+            self.files.extend(
+                matched.ok_or_else(|| anyhow::anyhow!("'{}' not found in revision", token))?,
+            );
+            return Ok(true);
+        }
+        if path.is_dir() {
+            collect_dir(path, self.ext.as_deref(), &mut self.files, self.walk)?;
+        } else if path.is_file() {
+            self.files.insert(normalize(path));
+        } else {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    // 列出归档中的文件并按 `inner`（空表示整个归档）匹配；内容在输出时才解压
+    fn add_archive(&mut self, path: &Path, inner: &str) -> anyhow::Result<()> {
+        let tree = archive::list(path)?;
+        let matched = match_tree(inner, &tree, self.ext.as_deref())?
+            .ok_or_else(|| anyhow::anyhow!("'{}' not found in {}", inner, path.display()))?;
+        for name in matched {
+            let listed = tree[&name];
+            let joined = archive::join(path, &name);
+            self.files.insert(joined.clone());
+            self.members
+                .insert(joined, (path.to_path_buf(), name, listed));
+        }
+        Ok(())
+    }
+
+    /// 按 git 状态选择文件；已添加过条目时只保留两者共有的文件
+    pub fn select_git(&mut self, selectors: &git::GitSelectors) -> anyhow::Result<()> {
+        let mut selected = git::select_files(selectors)?;
+        selected.retain(|path| {
+            path.is_file() && self.ext.as_deref().is_none_or(|exts| ext_match(path, exts))
+        });
+        if self.has_items {
+            let matched: BTreeSet<PathBuf> = self
+                .files
+                .iter()
+                .filter_map(|path| fs::canonicalize(path).ok())
+                .collect();
+            selected.retain(|path| {
+                fs::canonicalize(path).is_ok_and(|canonical| matched.contains(&canonical))
+            });
+        }
+        self.files = selected;
+        Ok(())
+    }
+
+    /// 应用排除规则，返回按路径排序的文件及其大小与修改时间
+    pub fn finish(self) -> Vec<FileEntry> {
+        let Self {
+            excludes,
+            rev_tree,
+            mut files,
+            members,
+            max_size,
+            ..
+        } = self;
+        // 排除规则统一作用于目录与模式两类结果
+        if let Some(matcher) = &excludes {
+            files.retain(|path| !is_excluded(matcher, path));
+        }
+        let mut members = batch_members(members, &files, max_size);
+        files
+            .into_iter()
+            .map(|path| match &rev_tree {
+                // 版本中的文件没有修改时间
+                Some(tree) => FileEntry {
+                    len: tree.get(&path).copied(),
+                    mtime: None,
+                    path,
+                    stdin: false,
+                    member: None,
+                },
+                None => match members.remove(&path) {
+                    Some((member, mtime)) => FileEntry {
+                        len: Some(member.size),
+                        mtime,
+                        path,
+                        stdin: false,
+                        member: Some(member),
+                    },
+                    None => {
+                        let len = file_len(&path).ok().flatten();
+                        let mtime = metadata_mtime(&path).ok().flatten();
+                        FileEntry {
+                            path,
+                            len,
+                            mtime,
+                            stdin: false,
+                            member: None,
+                        }
+                    }
+                },
+            })
+            .collect()
+    }
+}
+
+// 同一归档中最终要输出的成员共享一个 Batch，读取时按归档批量解压
+fn batch_members(
+    members: BTreeMap<PathBuf, (PathBuf, PathBuf, archive::Member)>,
+    files: &BTreeSet<PathBuf>,
+    max_size: Option<u64>,
+) -> BTreeMap<PathBuf, (ArchiveMember, Option<SystemTime>)> {
+    let mut by_archive: BTreeMap<PathBuf, Vec<(PathBuf, PathBuf, archive::Member)>> =
+        BTreeMap::new();
+    for (path, (archive, name, listed)) in members {
+        if files.contains(&path) {
+            by_archive
+                .entry(archive)
+                .or_default()
+                .push((path, name, listed));
+        }
+    }
+    let mut batched = BTreeMap::new();
+    for (archive, entries) in by_archive {
+        let pending = entries
+            .iter()
+            .map(|(_, name, listed)| (name.clone(), *listed))
+            .collect();
+        let batch = Arc::new(archive::Batch::new(archive, pending, max_size));
+        for (path, name, listed) in entries {
+            let member = ArchiveMember {
+                batch: Arc::clone(&batch),
+                name,
+                size: listed.size,
+            };
+            batched.insert(path, (member, listed.mtime));
+        }
+    }
+    batched
+}
+
+/// 输出顺序
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum SortKey {
+    Name,
+    Size,
+    Mtime,
+}
+
+/// 收集到的一个文件
+pub struct FileEntry {
+    /// 标准输入条目的 path 是 --stdin-name
+    pub path: PathBuf,
+    pub len: Option<u64>,
+    pub mtime: Option<SystemTime>,
+    /// 条目 `-`：内容来自标准输入
+    pub stdin: bool,
+    /// 归档中的文件：path 形如 `bundle.zip!/src/lib.rs`，内容用 [`ArchiveMember::read`] 解压
+    pub member: Option<ArchiveMember>,
+}
+
+/// 归档中的一个文件：收集时只记录所在归档与大小，读取时才解压
+#[derive(Debug, Clone)]
+pub struct ArchiveMember {
+    batch: Arc<archive::Batch>,
+    name: PathBuf,
+    size: u64,
+}
+
+impl ArchiveMember {
+    /// 解压内容；最多读取归档目录中记录的大小（即 `FileEntry::len`，`--max-size` 按它判断）。
+    /// 同一归档的成员批量解压，每个成员只能读取一次
+    pub fn read(&self) -> anyhow::Result<Vec<u8>> {
+        self.batch.read(&self.name)
+    }
+}
+
+impl FileEntry {
+    /// Header 中显示的路径；标准输入按 --stdin-name 原样显示
+    pub fn display(&self, base: Option<&Path>) -> String {
+        if self.stdin {
+            self.path.to_string_lossy().into_owned()
+        } else {
+            rel_display(&self.path, base)
+        }
+    }
+}
+
+/// 目录与模式遍历共用的选项
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct WalkOptions {
+    pub(crate) follow_links: bool,
+    /// 是否应用 .gitignore / .ignore / .git/info/exclude / core.excludesFile
+    pub(crate) respect_ignore: bool,
+}
+
+// 构建遍历器：启用忽略规则时与 git 的语义保持一致（包括嵌套 .gitignore 与 `!` 取反），
+// 并跳过 .git 目录本身。隐藏文件不做额外过滤，保持原有行为。
+fn build_walker(root: &Path, opts: WalkOptions) -> ignore::Walk {
+    let respect = opts.respect_ignore;
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .follow_links(opts.follow_links)
+        .hidden(false)
+        .parents(respect)
+        .ignore(respect)
+        .git_ignore(respect)
+        .git_global(respect)
+        .git_exclude(respect)
+        .require_git(false);
+    if respect {
+        builder.filter_entry(|entry| entry.file_name() != ".git");
+    }
+    builder.build()
+}
+
+pub(crate) fn collect_dir(
+    dir: &Path,
+    exts: Option<&str>,
+    files: &mut BTreeSet<PathBuf>,
+    opts: WalkOptions,
+) -> anyhow::Result<()> {
+    for entry in build_walker(dir, opts).filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.is_file() {
+            if let Some(exts) = exts {
+                if !ext_match(path, exts) {
+                    continue;
+                }
+            }
+            files.insert(normalize(path));
+        }
+    }
+    Ok(())
+}
+
+// 排除模式使用 gitignore 语法，以当前工作目录为根：
+// 不含 `/` 的模式匹配任意层级，命中目录时其下所有文件一并排除。
+fn build_exclude_matcher(patterns: &[String]) -> anyhow::Result<Option<Gitignore>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GitignoreBuilder::new(std::env::current_dir()?);
+    for pattern in patterns {
+        builder
+            .add_line(None, pattern)
+            .map_err(|e| anyhow::anyhow!("invalid --exclude pattern '{}': {}", pattern, e))?;
+    }
+    Ok(Some(builder.build()?))
+}
+
+fn is_excluded(matcher: &Gitignore, path: &Path) -> bool {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        matcher.path().join(path)
+    };
+    // 工作目录之外的文件不参与排除匹配
+    let Ok(relative) = absolute.strip_prefix(matcher.path()) else {
+        return false;
+    };
+    matcher
+        .matched_path_or_any_parents(relative, false)
+        .is_ignore()
+}
+
+fn normalize(p: &Path) -> PathBuf {
+    PathBuf::from(p)
+}
+
+pub(crate) fn rel_display(p: &Path, base: Option<&Path>) -> String {
+    let absolute = if p.is_absolute() {
+        p.to_path_buf()
+    } else if let Ok(cwd) = std::env::current_dir() {
+        cwd.join(p)
+    } else {
+        p.to_path_buf()
+    };
+
+    if let Some(base) = base {
+        if let Ok(stripped) = absolute.strip_prefix(base) {
+            return strip_dot_slash(stripped).to_string();
+        }
+    }
+
+    if let Ok(cwd) = std::env::current_dir() {
+        if let Ok(stripped) = absolute.strip_prefix(&cwd) {
+            return strip_dot_slash(stripped).to_string();
+        }
+    }
+
+    strip_dot_slash(p).to_string()
+}
+
+fn strip_dot_slash(p: &Path) -> String {
+    let s = p.to_string_lossy();
+    s.strip_prefix("./").unwrap_or(&s).to_string()
+}
+
+/// `--relative-from`：相对路径转换为绝对路径
+pub fn resolve_relative_base(from: Option<&PathBuf>) -> anyhow::Result<Option<PathBuf>> {
+    let Some(base) = from else {
+        return Ok(None);
+    };
+    if base.is_absolute() {
+        return Ok(Some(base.clone()));
+    }
+    let cwd = std::env::current_dir()?;
+    Ok(Some(cwd.join(base)))
+}
+
+fn file_len(path: &Path) -> anyhow::Result<Option<u64>> {
+    match path.metadata() {
+        Ok(meta) => Ok(Some(meta.len())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn metadata_mtime(path: &Path) -> anyhow::Result<Option<SystemTime>> {
+    match path.metadata() {
+        Ok(meta) => Ok(meta.modified().ok()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// 按大小或修改时间排序时，相同的再按路径排序
+pub fn sort_entries(entries: &mut [FileEntry], key: SortKey) {
+    match key {
+        SortKey::Name => entries.sort_by(|a, b| a.path.cmp(&b.path)),
+        SortKey::Size => entries.sort_by(|a, b| {
+            a.len
+                .unwrap_or_default()
+                .cmp(&b.len.unwrap_or_default())
+                .then_with(|| a.path.cmp(&b.path))
+        }),
+        SortKey::Mtime => entries.sort_by(|a, b| {
+            a.mtime
+                .unwrap_or(SystemTime::UNIX_EPOCH)
+                .cmp(&b.mtime.unwrap_or(SystemTime::UNIX_EPOCH))
+                .then_with(|| a.path.cmp(&b.path))
+        }),
+    }
+}
+
+pub(crate) fn ext_match(path: &Path, exts_csv: &str) -> bool {
+    let ext = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(|s| s.to_ascii_lowercase());
+    let Some(ext) = ext else {
+        return false;
+    };
+    for e in exts_csv.split(',') {
+        if ext == e.trim().to_ascii_lowercase() {
+            return true;
+        }
+    }
+    false
+}
+
+/// --files-from 的列表：按换行（兼容 CRLF）或 NUL 分隔，忽略空行
+pub fn parse_file_list(bytes: &[u8], nul: bool) -> anyhow::Result<Vec<String>> {
+    let separator = if nul { b'\0' } else { b'\n' };
+    bytes
+        .split(|&b| b == separator)
+        .map(|entry| match entry.strip_suffix(b"\r") {
+            Some(stripped) if !nul => stripped,
+            _ => entry,
+        })
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            String::from_utf8(entry.to_vec()).map_err(|_| {
+                anyhow::anyhow!(
+                    "--files-from entry is not valid UTF-8: {}",
+                    String::from_utf8_lossy(entry)
+                )
+            })
+        })
+        .collect()
+}
+
+fn expand_glob(pattern: &str, opts: WalkOptions) -> anyhow::Result<Vec<PathBuf>> {
+    let has_glob = pattern.contains('*') || pattern.contains('?') || pattern.contains('[');
+    if !has_glob {
+        return Ok(vec![PathBuf::from(pattern)]);
+    }
+    // 模式仅用于匹配，不挂到遍历器上：ignore 的 override 白名单会压过 .gitignore
+    let mut builder = OverrideBuilder::new(".");
+    builder.case_insensitive(false)?;
+    builder.add(pattern)?;
+    let matcher = builder.build()?;
+    // 从通配符前的目录（`target/*.txt` 中的 target）开始遍历，与直接写出的目录一样，
+    // 忽略规则只作用于它下面的路径
+    let base = glob_base(pattern);
+    let root = match base.is_dir() {
+        true => base.as_path(),
+        false => Path::new("."),
+    };
+    Ok(build_walker(root, opts)
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            let is_dir = e.file_type().is_some_and(|t| t.is_dir());
+            matcher.matched(e.path(), is_dir).is_whitelist()
+        })
+        .map(|e| e.into_path())
+        .collect())
+}
+
+// 模式中第一个含通配符的部分之前的目录；没有时为空路径
+fn glob_base(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|c| {
+            let part = c.as_os_str().to_string_lossy();
+            !part.contains(['*', '?', '['])
+        })
+        .collect()
+}
+
+// --rev 的版本树或归档中的文件列表：目录（含 `.` 与空路径）取其下的文件并应用 --ext，
+// 文件路径精确匹配，其余按 glob 匹配；不含通配符又没有匹配时返回 None
+fn match_tree<V>(
+    token: &str,
+    tree: &BTreeMap<PathBuf, V>,
+    exts: Option<&str>,
+) -> anyhow::Result<Option<Vec<PathBuf>>> {
+    let wanted = Path::new(token)
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect::<PathBuf>();
+    if tree.contains_key(&wanted) {
+        return Ok(Some(vec![wanted]));
+    }
+    // 空路径（`.`）是所有路径的前缀
+    if wanted.as_os_str().is_empty() || tree.keys().any(|path| path.starts_with(&wanted)) {
+        return Ok(Some(
+            tree.keys()
+                .filter(|path| path.starts_with(&wanted))
+                .filter(|path| exts.is_none_or(|exts| ext_match(path, exts)))
+                .cloned()
+                .collect(),
+        ));
+    }
+    let has_glob = token.contains('*') || token.contains('?') || token.contains('[');
+    if !has_glob {
+        return Ok(None);
+    }
+    let mut builder = OverrideBuilder::new(".");
+    builder.case_insensitive(false)?;
+    builder.add(token.strip_prefix("./").unwrap_or(token))?;
+    let matcher = builder.build()?;
+    Ok(Some(
+        tree.keys()
+            .filter(|path| {
+                matcher
+                    .matched(Path::new(".").join(path), false)
+                    .is_whitelist()
+            })
+            .cloned()
+            .collect(),
+    ))
+}
+
+/// 从条目末尾拆出行范围；不是合法范围时返回 None，按普通路径处理
+pub fn split_line_range(item: &str) -> Option<(&str, LineRange)> {
+    let (path, spec) = item.rsplit_once(':')?;
+    if path.is_empty() {
+        return None;
+    }
+    let spec = spec.strip_prefix("#L").unwrap_or(spec);
+    let (start, end) = match spec.split_once('-') {
+        Some((start, end)) => (start, end.strip_prefix('L').unwrap_or(end)),
+        None => (spec, spec),
+    };
+    let start: usize = start.parse().ok()?;
+    let end: usize = end.parse().ok()?;
+    if start == 0 || end < start {
+        return None;
+    }
+    Some((path, LineRange { start, end }))
+}
+
+/// 行范围按规范化路径对应到文件；--rev 下文件可能不在工作区，退回去掉 `./` 的路径。
+/// 归档中的文件按规范化的归档路径加成员路径对应
+pub fn range_key(path: &Path) -> PathBuf {
+    let member = path.to_str().and_then(archive::split_item);
+    if let Some((archive, inner)) = member.filter(|(_, inner)| !inner.is_empty()) {
+        return archive::join(&range_key(&archive), Path::new(&inner));
+    }
+    fs::canonicalize(path).unwrap_or_else(|_| {
+        path.components()
+            .filter(|c| !matches!(c, Component::CurDir))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{format_ranges, merge_ranges};
+
+    #[test]
+    fn ext_match_is_case_insensitive() {
+        assert!(ext_match(Path::new("foo.rs"), "rs,md"));
+        assert!(ext_match(Path::new("foo.RS"), "rs,md"));
+        assert!(!ext_match(Path::new("foo.txt"), "rs,md"));
+        assert!(!ext_match(Path::new("foo"), "rs"));
+    }
+
+    #[test]
+    fn rel_display_strips_current_dir_prefix() {
+        let cwd = std::env::current_dir().expect("cwd");
+        let path = cwd.join("foo").join("bar.txt");
+        assert_eq!(rel_display(&path, None), "foo/bar.txt");
+    }
+
+    #[test]
+    fn strip_dot_slash_removes_prefix() {
+        let path = Path::new("./nested/value");
+        assert_eq!(strip_dot_slash(path), "nested/value");
+    }
+
+    #[test]
+    fn rel_display_uses_custom_base() {
+        let base = std::env::temp_dir().join("rel-display-base");
+        let path = base.join("project/file.txt");
+        assert_eq!(rel_display(&path, Some(&base)), "project/file.txt");
+    }
+
+    #[test]
+    fn file_len_handles_missing_file() {
+        let path = Path::new("unlikely_missing_file");
+        assert!(file_len(path).unwrap().is_none());
+    }
+
+    #[test]
+    fn file_list_keeps_commas_and_spaces() {
+        assert_eq!(
+            parse_file_list(b"a, b.txt\r\n\nsrc/c d.rs\n", false).unwrap(),
+            ["a, b.txt", "src/c d.rs"]
+        );
+        assert_eq!(parse_file_list(b"x\ny\0z\0", true).unwrap(), ["x\ny", "z"]);
+        assert!(parse_file_list(b"\xff\n", false).is_err());
+    }
+
+    #[test]
+    fn line_range_items_are_parsed_and_merged() {
+        let range = |start, end| LineRange { start, end };
+        assert_eq!(
+            split_line_range("src/main.rs:120-180"),
+            Some(("src/main.rs", range(120, 180)))
+        );
+        assert_eq!(
+            split_line_range("src/lib.rs:#L10-L40"),
+            Some(("src/lib.rs", range(10, 40)))
+        );
+        assert_eq!(split_line_range("a.rs:#L7"), Some(("a.rs", range(7, 7))));
+        assert_eq!(split_line_range("a.rs:9-3"), None);
+        assert_eq!(split_line_range("a.rs:0"), None);
+        assert_eq!(split_line_range("src/*.rs"), None);
+
+        let mut ranges = vec![range(30, 40), range(1, 5), range(6, 8), range(35, 50)];
+        merge_ranges(&mut ranges);
+        assert_eq!(ranges, vec![range(1, 8), range(30, 50)]);
+        assert_eq!(format_ranges(&ranges), "1-8,30-50");
+    }
+}
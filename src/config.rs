@@ -0,0 +1,584 @@
+//! 配置文件：用户配置目录下的 `printfiles/config.toml` 与从当前目录向上找到的 `.printfiles.toml`。
+//!
+//! 键名与命令行长选项一致（`max-size`、`divider` …），`[profile.<name>]` 是可用 `--profile`
+//! 选择的命名配置。优先级：用户配置 < 项目配置 < 选中的 profile < 命令行。
+//! `reader-cmd` 等 `KEY=VALUE` 形式的可重复选项可以写成表。
+//!
+//! 项目配置随仓库分发，不可信：其中会运行外部命令的选项（`reader`、`reader-cmd`）、
+//! 写入或删除文件的选项（`output-dir`、`split-*`）以及决定读取哪些路径的选项
+//! （`files-from`、`null`、`relative-from`）被忽略并给出提示，除非命令行上给出 `--trust-config`。
+
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+const PROJECT_FILE: &str = ".printfiles.toml";
+
+// 只在命令行上有意义的选项；`--no-tree` 等否定开关用于关闭配置中的设置，配置里直接写 false
+const RESERVED: &[&str] = &[
+    "help",
+    "version",
+    "profile",
+    "no-config",
+    "trust-config",
+    "no-follow-links",
+    "no-git-tracked",
+    "no-git-staged",
+    "no-git-modified",
+    "ignore",
+    "no-line-numbers",
+    "no-tree",
+    "no-toc",
+    "no-verbose",
+    "no-quiet",
+];
+
+// 会运行外部命令、写入文件或读取任意路径，只接受用户配置与命令行的选项
+const TRUSTED_ONLY: &[&str] = &[
+    "reader",
+    "reader-cmd",
+    "output-dir",
+    "split-bytes",
+    "split-tokens",
+    "files-from",
+    "null",
+    "relative-from",
+];
+
+// 依赖其他选项才有意义的选项（与 clap 的 `requires` 一致）；所依赖的选项在命令行与配置中
+// 都没有给出时忽略该设置，而不是让整条命令报错
+const REQUIRES: &[(&str, &str)] = &[
+    ("context", "grep"),
+    ("line-number-width", "line-numbers"),
+    ("null", "files-from"),
+    ("split-bytes", "output-dir"),
+    ("split-tokens", "output-dir"),
+];
+
+/// 一个配置值及其来源（文件，profile 中的值附带名称）
+struct Setting {
+    value: Value,
+    source: String,
+}
+
+pub(crate) struct Config {
+    /// 按优先级从低到高读取的配置文件
+    files: Vec<PathBuf>,
+    profile: Option<String>,
+    settings: BTreeMap<String, Setting>,
+    /// 因来自不可信的项目配置而忽略的设置（`键 (来源)`）
+    ignored: Vec<String>,
+}
+
+/// 一个已解析的配置文件；`trusted` 为假时忽略 [`TRUSTED_ONLY`] 中的选项
+struct ConfigFile {
+    path: PathBuf,
+    table: Table,
+    trusted: bool,
+}
+
+impl Config {
+    /// 查找并合并配置文件，应用选中的 profile；`trust_project` 对应 `--trust-config`
+    pub(crate) fn load(profile: Option<&str>, trust_project: bool) -> anyhow::Result<Self> {
+        let mut paths = Vec::new();
+        if let Some(dir) = dirs::config_dir() {
+            let path = dir.join("printfiles").join("config.toml");
+            if path.is_file() {
+                paths.push((path, true));
+            }
+        }
+        if let Some(path) = find_project_file(&std::env::current_dir()?) {
+            if !paths.iter().any(|(user, _)| *user == path) {
+                paths.push((path, trust_project));
+            }
+        }
+        let mut files = Vec::with_capacity(paths.len());
+        for (path, trusted) in paths {
+            let text = fs::read_to_string(&path)
+                .map_err(|e| anyhow::anyhow!("cannot read {}: {}", path.display(), e))?;
+            let table: Table = text
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid config {}: {}", path.display(), e))?;
+            files.push(ConfigFile {
+                path,
+                table,
+                trusted,
+            });
+        }
+        Self::merge(files, profile)
+    }
+
+    fn merge(files: Vec<ConfigFile>, profile: Option<&str>) -> anyhow::Result<Self> {
+        let mut settings = BTreeMap::new();
+        let mut ignored = Vec::new();
+        let mut profiles: Vec<(String, String, bool, Table)> = Vec::new();
+        for ConfigFile {
+            path,
+            table,
+            trusted,
+        } in &files
+        {
+            for (key, value) in table {
+                if key != "profile" {
+                    let source = path.display().to_string();
+                    let key = normalize_key(key);
+                    if !trusted && TRUSTED_ONLY.contains(&key.as_str()) {
+                        ignored.push(format!("{} ({})", key, source));
+                        continue;
+                    }
+                    settings.insert(
+                        key,
+                        Setting {
+                            value: value.clone(),
+                            source,
+                        },
+                    );
+                    continue;
+                }
+                let Value::Table(named) = value else {
+                    anyhow::bail!("{}: 'profile' must be a table of profiles", path.display());
+                };
+                for (name, body) in named {
+                    let Value::Table(body) = body else {
+                        anyhow::bail!("{}: profile '{}' must be a table", path.display(), name);
+                    };
+                    profiles.push((
+                        name.clone(),
+                        path.display().to_string(),
+                        *trusted,
+                        body.clone(),
+                    ));
+                }
+            }
+        }
+
+        if let Some(wanted) = profile {
+            let mut found = false;
+            for (name, path, trusted, body) in &profiles {
+                if name != wanted {
+                    continue;
+                }
+                found = true;
+                for (key, value) in body {
+                    let source = format!("{} [profile.{}]", path, name);
+                    let key = normalize_key(key);
+                    if !trusted && TRUSTED_ONLY.contains(&key.as_str()) {
+                        ignored.push(format!("{} ({})", key, source));
+                        continue;
+                    }
+                    settings.insert(
+                        key,
+                        Setting {
+                            value: value.clone(),
+                            source,
+                        },
+                    );
+                }
+            }
+            if !found {
+                let mut names: Vec<&str> = profiles
+                    .iter()
+                    .map(|(name, _, _, _)| name.as_str())
+                    .collect();
+                names.sort_unstable();
+                names.dedup();
+                anyhow::bail!(
+                    "unknown profile '{}' (available: {})",
+                    wanted,
+                    if names.is_empty() {
+                        "none".to_string()
+                    } else {
+                        names.join(", ")
+                    }
+                );
+            }
+        }
+
+        Ok(Self {
+            files: files.into_iter().map(|file| file.path).collect(),
+            profile: profile.map(str::to_string),
+            settings,
+            ignored,
+        })
+    }
+
+    /// 被忽略的项目配置设置对应的提示
+    pub(crate) fn warnings(&self) -> Vec<String> {
+        self.ignored
+            .iter()
+            .map(|setting| {
+                format!(
+                    "提示: 忽略项目配置中不受信任的 {}，需要时请加 --trust-config",
+                    setting
+                )
+            })
+            .collect()
+    }
+
+    /// 转换为命令行参数；命令行上已给出的选项、与命令行选项冲突的选项，以及所依赖的选项
+    /// 没有给出的选项不再从配置文件取值
+    pub(crate) fn to_args(&self, cmd: &Command, cli: &ArgMatches) -> anyhow::Result<Vec<String>> {
+        let given: Vec<&Arg> = cmd
+            .get_arguments()
+            .filter(|arg| cli.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine))
+            .collect();
+        let mut settings = Vec::new();
+        for (key, setting) in &self.settings {
+            let arg = find_arg(cmd, key, setting)?;
+            let conflicts = cmd.get_arg_conflicts_with(arg);
+            if given.iter().any(|other| {
+                other.get_id() == arg.get_id()
+                    || conflicts.iter().any(|c| c.get_id() == other.get_id())
+                    || cmd
+                        .get_arg_conflicts_with(other)
+                        .iter()
+                        .any(|c| c.get_id() == arg.get_id())
+            }) {
+                continue;
+            }
+            let tokens = arg_tokens(arg, &setting.value)
+                .map_err(|e| anyhow::anyhow!("{}: {}", setting.source, e))?;
+            if !tokens.is_empty() {
+                settings.push((key.as_str(), tokens));
+            }
+        }
+        let present = |long: &str| {
+            given.iter().any(|arg| arg.get_long() == Some(long))
+                || settings.iter().any(|(key, _)| *key == long)
+        };
+        let missing: Vec<&str> = settings
+            .iter()
+            .map(|(key, _)| *key)
+            .filter(|key| {
+                REQUIRES
+                    .iter()
+                    .any(|(arg, required)| arg == key && !present(required))
+            })
+            .collect();
+        Ok(settings
+            .into_iter()
+            .filter(|(key, _)| !missing.contains(key))
+            .flat_map(|(_, tokens)| tokens)
+            .collect())
+    }
+
+    /// `printfiles config show`：读取的文件、profile 以及每个选项生效的值和来源
+    pub(crate) fn show(&self, cmd: &Command) -> anyhow::Result<String> {
+        // 构建后开关选项才有默认值 false
+        let mut cmd = cmd.clone();
+        cmd.build();
+        let cmd = &cmd;
+        for (key, setting) in &self.settings {
+            find_arg(cmd, key, setting)?;
+        }
+        let mut out = String::from("# config files:\n");
+        if self.files.is_empty() {
+            out.push_str("#   (none)\n");
+        }
+        for path in &self.files {
+            out.push_str(&format!("#   {}\n", path.display()));
+        }
+        if let Some(profile) = &self.profile {
+            out.push_str(&format!("# profile: {}\n", profile));
+        }
+        for setting in &self.ignored {
+            out.push_str(&format!("# ignored without --trust-config: {}\n", setting));
+        }
+        for arg in cmd.get_arguments() {
+            let Some(long) = arg.get_long().filter(|long| !RESERVED.contains(long)) else {
+                continue;
+            };
+            if let Some(setting) = self.settings.get(long) {
+                out.push_str(&format!(
+                    "{} = {}  # {}\n",
+                    long, setting.value, setting.source
+                ));
+                continue;
+            }
+            let defaults = arg.get_default_values();
+            if let [default] = defaults {
+                let default = default.to_string_lossy();
+                out.push_str(&format!(
+                    "{} = {}  # default\n",
+                    long,
+                    default_value(arg, &default)
+                ));
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn find_project_file(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_FILE))
+        .find(|path| path.is_file())
+}
+
+// 键名也接受下划线写法（max_size）
+fn normalize_key(key: &str) -> String {
+    key.replace('_', "-")
+}
+
+fn find_arg<'a>(cmd: &'a Command, key: &str, setting: &Setting) -> anyhow::Result<&'a Arg> {
+    cmd.get_arguments()
+        .find(|arg| arg.get_long() == Some(key) && !RESERVED.contains(&key))
+        .ok_or_else(|| anyhow::anyhow!("unknown option '{}' in {}", key, setting.source))
+}
+
+// 一个配置值对应的命令行参数；统一用 `--key=value` 形式，避免与可省略值的选项混淆
+fn arg_tokens(arg: &Arg, value: &Value) -> anyhow::Result<Vec<String>> {
+    let long = arg.get_long().unwrap_or_default();
+    match (arg.get_action(), value) {
+        (ArgAction::SetTrue, Value::Boolean(true)) => Ok(vec![format!("--{}", long)]),
+        (ArgAction::SetTrue, Value::Boolean(false)) => Ok(Vec::new()),
+        (ArgAction::SetTrue, _) => anyhow::bail!("'{}' expects true or false", long),
+        (ArgAction::Append, Value::Array(items)) => items
+            .iter()
+            .map(|item| Ok(format!("--{}={}", long, scalar(long, item)?)))
+            .collect(),
+        // `[reader-cmd]` 这类 `KEY=VALUE` 形式的可重复选项也可以写成表
+        (ArgAction::Append, Value::Table(entries)) => entries
+            .iter()
+            .map(|(key, item)| Ok(format!("--{}={}={}", long, key, scalar(long, item)?)))
+            .collect(),
+        (_, Value::Array(_)) => anyhow::bail!("'{}' takes a single value", long),
+        (_, value) => Ok(vec![format!("--{}={}", long, scalar(long, value)?)]),
+    }
+}
+
+fn scalar(long: &str, value: &Value) -> anyhow::Result<String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Integer(n) => Ok(n.to_string()),
+        Value::Float(n) => Ok(n.to_string()),
+        Value::Boolean(b) => Ok(b.to_string()),
+        _ => anyhow::bail!("unsupported value for '{}'", long),
+    }
+}
+
+// 默认值按 TOML 写法输出：开关与数字不加引号
+fn default_value(arg: &Arg, default: &str) -> Value {
+    if matches!(arg.get_action(), ArgAction::SetTrue) || default == "true" || default == "false" {
+        return Value::Boolean(default == "true");
+    }
+    match default.parse::<i64>() {
+        Ok(n) => Value::Integer(n),
+        Err(_) => Value::String(default.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Args;
+    use clap::CommandFactory;
+
+    #[test]
+    fn profiles_override_files_and_cli_overrides_both() {
+        let user: Table =
+            "divider = \"xml-tag\"\nmax_size = 100\n[reader_cmd]\npdf = \"pdftotext {} -\"\n"
+                .parse()
+                .unwrap();
+        let project: Table = "binary = \"skip\"\nexclude = [\"*.lock\", \"dist/**\"]\n\
+                              [profile.llm]\nmax-tokens = 5000\nline-numbers = true\ndivider = \"equals\"\n"
+            .parse()
+            .unwrap();
+        let files = || {
+            vec![
+                ConfigFile {
+                    path: PathBuf::from("user.toml"),
+                    table: user.clone(),
+                    trusted: true,
+                },
+                ConfigFile {
+                    path: PathBuf::from("project.toml"),
+                    table: project.clone(),
+                    trusted: false,
+                },
+            ]
+        };
+        let config = Config::merge(files(), Some("llm")).unwrap();
+
+        let cmd = Args::command();
+        let cli = cmd
+            .clone()
+            .get_matches_from(["printfiles", "src", "--binary", "hex"]);
+        assert_eq!(
+            config.to_args(&cmd, &cli).unwrap(),
+            [
+                "--divider=equals",
+                "--exclude=*.lock",
+                "--exclude=dist/**",
+                "--line-numbers",
+                "--max-size=100",
+                "--max-tokens=5000",
+                "--reader-cmd=pdf=pdftotext {} -",
+            ]
+        );
+
+        let err = Config::merge(files(), Some("review")).err().unwrap();
+        assert_eq!(err.to_string(), "unknown profile 'review' (available: llm)");
+    }
+
+    #[test]
+    fn project_config_cannot_enable_external_commands() {
+        let project: Table =
+            "reader = \"auto\"\nmax-size = 100\n[reader-cmd]\nrs = \"sh -c 'id'\"\n\
+                              [profile.x]\nreader_cmd = [\"md=sh -c 'id'\"]\n"
+                .parse()
+                .unwrap();
+        let files = |trusted| {
+            vec![ConfigFile {
+                path: PathBuf::from(".printfiles.toml"),
+                table: project.clone(),
+                trusted,
+            }]
+        };
+        let cmd = Args::command();
+        let cli = cmd.clone().get_matches_from(["printfiles", "src"]);
+
+        let config = Config::merge(files(false), Some("x")).unwrap();
+        assert_eq!(config.to_args(&cmd, &cli).unwrap(), ["--max-size=100"]);
+        assert_eq!(
+            config.ignored,
+            [
+                "reader (.printfiles.toml)",
+                "reader-cmd (.printfiles.toml)",
+                "reader-cmd (.printfiles.toml [profile.x])",
+            ]
+        );
+
+        // --trust-config
+        let config = Config::merge(files(true), Some("x")).unwrap();
+        assert_eq!(
+            config.to_args(&cmd, &cli).unwrap(),
+            [
+                "--max-size=100",
+                "--reader=auto",
+                "--reader-cmd=md=sh -c 'id'",
+            ]
+        );
+        assert!(config.ignored.is_empty());
+    }
+
+    #[test]
+    fn settings_conflicting_with_the_cli_are_dropped() {
+        let user: Table = "tree = true
+toc = true
+line-numbers = true
+line-number-width = 4
+"
+        .parse()
+        .unwrap();
+        let config = Config::merge(
+            vec![ConfigFile {
+                path: PathBuf::from("user.toml"),
+                table: user,
+                trusted: true,
+            }],
+            None,
+        )
+        .unwrap();
+        let cmd = Args::command();
+        let cli = cmd.clone().get_matches_from([
+            "printfiles",
+            "src",
+            "--split-bytes=100",
+            "--output-dir=out",
+            "--no-line-numbers",
+        ]);
+        // --line-numbers 保留，由之后命令行上的 --no-line-numbers 覆盖
+        assert_eq!(config.to_args(&cmd, &cli).unwrap(), ["--line-numbers"]);
+
+        // 否定开关只能在命令行上使用
+        let table: Table = "no-tree = true\n".parse().unwrap();
+        let config = Config::merge(
+            vec![ConfigFile {
+                path: PathBuf::from("user.toml"),
+                table,
+                trusted: true,
+            }],
+            None,
+        )
+        .unwrap();
+        let err = config.to_args(&cmd, &cli).unwrap_err();
+        assert_eq!(err.to_string(), "unknown option 'no-tree' in user.toml");
+    }
+
+    #[test]
+    fn settings_whose_required_options_are_missing_are_dropped() {
+        let user: Table = "context = 2\nline-number-width = 4\n".parse().unwrap();
+        let config = Config::merge(
+            vec![ConfigFile {
+                path: PathBuf::from("user.toml"),
+                table: user,
+                trusted: true,
+            }],
+            None,
+        )
+        .unwrap();
+        let cmd = Args::command();
+        let cli = cmd.clone().get_matches_from(["printfiles", "src/a.rs"]);
+        assert!(config.to_args(&cmd, &cli).unwrap().is_empty());
+
+        // 所依赖的选项来自命令行或配置都可以
+        let cli = cmd
+            .clone()
+            .get_matches_from(["printfiles", "src", "--grep=fn", "-n"]);
+        assert_eq!(
+            config.to_args(&cmd, &cli).unwrap(),
+            ["--context=2", "--line-number-width=4"]
+        );
+        let table: Table =
+            "grep = \"fn\"\ncontext = 2\nline-numbers = false\nline-number-width = 4\n"
+                .parse()
+                .unwrap();
+        let config = Config::merge(
+            vec![ConfigFile {
+                path: PathBuf::from("user.toml"),
+                table,
+                trusted: true,
+            }],
+            None,
+        )
+        .unwrap();
+        let cli = cmd.clone().get_matches_from(["printfiles", "src"]);
+        assert_eq!(
+            config.to_args(&cmd, &cli).unwrap(),
+            ["--context=2", "--grep=fn"]
+        );
+    }
+
+    #[test]
+    fn requires_table_matches_the_cli() {
+        let cmd = Args::command();
+        for (arg, required) in REQUIRES {
+            let arg = cmd
+                .get_arguments()
+                .find(|a| a.get_long() == Some(arg))
+                .unwrap();
+            let flag = format!("--{}", arg.get_long().unwrap());
+            let value = format!("{}=1", flag);
+            let token = if matches!(arg.get_action(), ArgAction::SetTrue) {
+                &flag
+            } else {
+                &value
+            };
+            let err = cmd
+                .clone()
+                .try_get_matches_from(["printfiles", token.as_str()])
+                .unwrap_err();
+            assert_eq!(err.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+            assert!(
+                err.to_string().contains(&format!("--{}", required)),
+                "{}",
+                err
+            );
+        }
+    }
+}
@@ -0,0 +1,392 @@
+//! `printfiles diff <left> <right>`：按相对路径配对两侧文件，在文件块中输出统一差异格式。
+//!
+//! 两侧可以是目录或 git 版本（提交、分支、标签等）；内容相同的新增/删除文件视为重命名。
+
+use crate::collect::{collect_dir, ext_match, rel_display, WalkOptions};
+use crate::read::{decode_content, is_probably_binary, BinaryStrategy};
+use crate::render::{Divider, HeaderMeta, Renderer};
+use crate::{git, Logger};
+use base64::engine::general_purpose::STANDARD as Base64;
+use base64::Engine;
+use similar::TextDiff;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Header 中标记的变更类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change<'a> {
+    Added,
+    Deleted,
+    Modified,
+    Renamed { from: &'a str },
+}
+
+impl Change<'_> {
+    pub fn name(self) -> &'static str {
+        match self {
+            Change::Added => "added",
+            Change::Deleted => "deleted",
+            Change::Modified => "modified",
+            Change::Renamed { .. } => "renamed",
+        }
+    }
+}
+
+/// `printfiles diff` 的设置
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    /// 左侧（旧）：目录或 git 版本
+    pub left: String,
+    /// 右侧（新）：目录或 git 版本
+    pub right: String,
+    /// 仅比较这些扩展的文件
+    pub ext: Option<String>,
+    /// 任一侧超过该大小（字节）则跳过
+    pub max_size: Option<u64>,
+    pub binary: BinaryStrategy,
+    pub divider: Divider,
+    /// 差异上下文行数
+    pub context: usize,
+    /// 不读取 .gitignore / .ignore 等忽略规则（仅对目录生效）
+    pub no_ignore: bool,
+}
+
+/// 差异的一侧
+enum Side {
+    Dir(PathBuf),
+    Rev(String),
+}
+
+impl Side {
+    fn parse(spec: &str) -> anyhow::Result<Self> {
+        if Path::new(spec).is_dir() {
+            return Ok(Side::Dir(PathBuf::from(spec)));
+        }
+        git::verify_rev(spec)
+            .map_err(|_| anyhow::anyhow!("'{}' is neither a directory nor a git revision", spec))?;
+        Ok(Side::Rev(spec.to_string()))
+    }
+
+    /// 相对路径（与 rel_display 一致）到文件大小
+    fn files(
+        &self,
+        exts: Option<&str>,
+        opts: WalkOptions,
+    ) -> anyhow::Result<BTreeMap<String, u64>> {
+        match self {
+            Side::Dir(dir) => {
+                let mut found = BTreeSet::new();
+                collect_dir(dir, exts, &mut found, opts)?;
+                let base = std::env::current_dir()?.join(dir);
+                Ok(found
+                    .into_iter()
+                    .map(|path| {
+                        let len = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                        (rel_display(&path, Some(&base)), len)
+                    })
+                    .collect())
+            }
+            Side::Rev(rev) => Ok(git::tree_files(rev)?
+                .into_iter()
+                .filter(|(path, _)| exts.is_none_or(|exts| ext_match(path, exts)))
+                .map(|(path, len)| (path.to_string_lossy().into_owned(), len))
+                .collect()),
+        }
+    }
+
+    fn read(&self, rel: &str) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Side::Dir(dir) => Ok(fs::read(dir.join(rel))?),
+            Side::Rev(rev) => git::read_blob(rev, Path::new(rel)),
+        }
+    }
+}
+
+/// 一对待比较的文件；新增时没有 old，删除时没有 new
+struct Pair {
+    old: Option<(String, u64)>,
+    new: Option<(String, u64)>,
+}
+
+impl Pair {
+    fn change(&self) -> Change<'_> {
+        match (&self.old, &self.new) {
+            (None, _) => Change::Added,
+            (_, None) => Change::Deleted,
+            (Some((old, _)), Some((new, _))) if old != new => Change::Renamed { from: old },
+            _ => Change::Modified,
+        }
+    }
+
+    fn too_large(&self, max_size: Option<u64>) -> bool {
+        let Some(limit) = max_size else {
+            return false;
+        };
+        [&self.old, &self.new]
+            .into_iter()
+            .flatten()
+            .any(|(_, len)| *len > limit)
+    }
+}
+
+/// 执行 diff 并把文件块写到 `out`，返回是否有文件读取失败
+pub fn run<W: Write>(options: &DiffOptions, mut out: W, logger: &Logger) -> anyhow::Result<bool> {
+    let left = Side::parse(&options.left)?;
+    let right = Side::parse(&options.right)?;
+    let opts = WalkOptions {
+        follow_links: true,
+        respect_ignore: !options.no_ignore,
+    };
+    let exts = options.ext.as_deref();
+    let left_files = left.files(exts, opts)?;
+    let right_files = right.files(exts, opts)?;
+
+    let pairs = pair_files(&left, &right, left_files, right_files, options.max_size);
+
+    let mut had_error = false;
+    let mut printed = 0usize;
+    for (path, pair) in &pairs {
+        let change = pair.change();
+        let mut meta = HeaderMeta {
+            change: Some(change),
+            ..HeaderMeta::default()
+        };
+        let body = if pair.too_large(options.max_size) {
+            logger.warn(&format!(
+                "提示: 跳过 {} (超过 max_size={})",
+                path,
+                options.max_size.unwrap_or_default()
+            ));
+            Some("(skipped: file exceeds max size)\n".to_string())
+        } else {
+            let read = |side: &Side, file: &Option<(String, u64)>| match file {
+                Some((rel, _)) => side.read(rel).map(Some),
+                None => Ok(None),
+            };
+            match (read(&left, &pair.old), read(&right, &pair.new)) {
+                (Ok(old), Ok(new)) => {
+                    if old == new && change == Change::Modified {
+                        continue;
+                    }
+                    let old_label = pair.old.as_ref().map(|(rel, _)| format!("a/{}", rel));
+                    let new_label = pair.new.as_ref().map(|(rel, _)| format!("b/{}", rel));
+                    let labels = (
+                        old_label.as_deref().unwrap_or("/dev/null"),
+                        new_label.as_deref().unwrap_or("/dev/null"),
+                    );
+                    let (body, binary) = diff_body(
+                        old.as_deref().unwrap_or_default(),
+                        new.as_deref().unwrap_or_default(),
+                        labels,
+                        options,
+                    );
+                    meta.binary = binary;
+                    Some(body)
+                }
+                (Err(err), _) | (_, Err(err)) => {
+                    logger.error(&format!("错误: 读取失败 {}: {err}", path));
+                    had_error = true;
+                    None
+                }
+            }
+        };
+
+        writeln!(out, "{}", options.divider.header(path, meta))?;
+        match body {
+            Some(body) => write!(out, "{}", body)?,
+            // 读取失败时只输出空的文件块
+            None => writeln!(out)?,
+        }
+        writeln!(out, "{}", options.divider.footer(path))?;
+        printed += 1;
+    }
+    out.flush()?;
+
+    if printed == 0 {
+        logger.warn("（没有差异）");
+    }
+    Ok(had_error)
+}
+
+// 按相对路径配对；只在一侧出现且内容完全相同的文件配成重命名
+fn pair_files(
+    left: &Side,
+    right: &Side,
+    mut left_files: BTreeMap<String, u64>,
+    right_files: BTreeMap<String, u64>,
+    max_size: Option<u64>,
+) -> BTreeMap<String, Pair> {
+    let mut pairs = BTreeMap::new();
+    let mut added = Vec::new();
+    for (path, len) in right_files {
+        match left_files.remove(&path) {
+            Some(old_len) => {
+                let pair = Pair {
+                    old: Some((path.clone(), old_len)),
+                    new: Some((path.clone(), len)),
+                };
+                pairs.insert(path, pair);
+            }
+            None => added.push((path, len)),
+        }
+    }
+
+    // 两侧都有且不超过 --max-size 的大小才可能是重命名；每个候选文件只读一次，
+    // 删除的文件按 (大小, 内容哈希) 索引，哈希相同时再比较一次内容
+    let within_limit = |len: u64| max_size.is_none_or(|limit| len <= limit);
+    let added_lens: BTreeSet<u64> = added.iter().map(|(_, len)| *len).collect();
+    let deleted_lens: BTreeSet<u64> = left_files.values().copied().collect();
+    let candidate = |len: u64| within_limit(len) && added_lens.contains(&len);
+    let mut deleted: HashMap<(u64, u64), Vec<String>> = HashMap::new();
+    for (old, &len) in &left_files {
+        if !candidate(len) {
+            continue;
+        }
+        if let Ok(bytes) = left.read(old) {
+            deleted
+                .entry((len, content_hash(&bytes)))
+                .or_default()
+                .push(old.clone());
+        }
+    }
+    for (path, len) in added {
+        let new_bytes = match candidate(len) && deleted_lens.contains(&len) {
+            true => right.read(&path).ok(),
+            false => None,
+        };
+        let renamed_from = new_bytes.and_then(|bytes| {
+            let candidates = deleted.get_mut(&(len, content_hash(&bytes)))?;
+            let index = candidates
+                .iter()
+                .position(|old| left.read(old).is_ok_and(|old| old == bytes))?;
+            Some(candidates.remove(index))
+        });
+        let old = renamed_from.map(|old| {
+            left_files.remove(&old);
+            (old, len)
+        });
+        let pair = Pair {
+            old,
+            new: Some((path.clone(), len)),
+        };
+        pairs.insert(path, pair);
+    }
+
+    for (path, len) in left_files {
+        let pair = Pair {
+            old: Some((path.clone(), len)),
+            new: None,
+        };
+        pairs.insert(path, pair);
+    }
+    pairs
+}
+
+fn content_hash(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+// 统一差异格式的正文；二进制内容按 --binary 策略编码后再比较
+fn diff_body(
+    old: &[u8],
+    new: &[u8],
+    labels: (&str, &str),
+    options: &DiffOptions,
+) -> (String, Option<BinaryStrategy>) {
+    let binary = (is_probably_binary(old) || is_probably_binary(new))
+        && !matches!(options.binary, BinaryStrategy::Print);
+    let (old_text, new_text, strategy) = if binary {
+        match options.binary {
+            BinaryStrategy::Hex => (
+                wrap(&hex::encode(old), 64),
+                wrap(&hex::encode(new), 64),
+                Some(options.binary),
+            ),
+            BinaryStrategy::Base64 => (
+                wrap(&Base64.encode(old), 76),
+                wrap(&Base64.encode(new), 76),
+                Some(options.binary),
+            ),
+            _ => return ("(binary files differ)\n".to_string(), None),
+        }
+    } else {
+        (
+            decode_content(old).0.into_owned(),
+            decode_content(new).0.into_owned(),
+            None,
+        )
+    };
+
+    if old_text == new_text {
+        return ("(no content changes)\n".to_string(), strategy);
+    }
+    let diff = TextDiff::from_lines(&old_text, &new_text);
+    let body = diff
+        .unified_diff()
+        .context_radius(options.context)
+        .header(labels.0, labels.1)
+        .to_string();
+    (body, strategy)
+}
+
+// 编码后的二进制内容按固定宽度换行，差异才能落到具体位置
+fn wrap(encoded: &str, width: usize) -> String {
+    let mut s = String::with_capacity(encoded.len() + encoded.len() / width + 1);
+    for chunk in encoded.as_bytes().chunks(width) {
+        s.push_str(std::str::from_utf8(chunk).unwrap_or_default());
+        s.push('\n');
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_splits_encoded_text_into_lines() {
+        assert_eq!(wrap("abcdefg", 3), "abc\ndef\ng\n");
+        assert_eq!(wrap("", 3), "");
+    }
+
+    #[test]
+    fn same_size_files_pair_as_renames_by_content() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let files = |side: &str, entries: &[(&str, &str)]| {
+            let dir = temp.path().join(side);
+            fs::create_dir_all(&dir).unwrap();
+            for (name, body) in entries {
+                fs::write(dir.join(name), body).unwrap();
+            }
+            let map = entries
+                .iter()
+                .map(|(name, body)| (name.to_string(), body.len() as u64))
+                .collect();
+            (Side::Dir(dir), map)
+        };
+        let (left, left_files) = files("old", &[("a", "xx"), ("b", "yy"), ("c", "zz")]);
+        let (right, right_files) = files("new", &[("d", "yy"), ("e", "xx"), ("f", "ww")]);
+
+        let pairs = pair_files(&left, &right, left_files, right_files, None);
+        let change = |path: &str| match pairs[path].change() {
+            Change::Renamed { from } => format!("{} <- {}", path, from),
+            change => format!("{} {:?}", path, change),
+        };
+        assert_eq!(
+            ["c", "d", "e", "f"].map(change),
+            ["c Deleted", "d <- b", "e <- a", "f Added"]
+        );
+
+        // 超过 --max-size 的文件不读取，不配成重命名
+        let (_, left_files) = files("old", &[("a", "xx")]);
+        let (_, right_files) = files("new", &[("e", "xx")]);
+        let pairs = pair_files(&left, &right, left_files, right_files, Some(1));
+        assert_eq!(pairs["e"].change(), Change::Added);
+    }
+}
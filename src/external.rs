@@ -0,0 +1,194 @@
+//! `--reader-cmd`：按扩展名调用外部命令转换文件（如 `jupyter nbconvert`、`pdftotext`）。
+//!
+//! 命令按 shell 规则拆分但不经过 shell 执行；`{}` 替换为文件路径，命令中没有 `{}` 时
+//! 文件内容从标准输入传入。命令无法启动、退出码非零或超时时回退到所包装的读取后端。
+
+use crate::read::{decode_content, LoadedFile, ReadOptions, Reader};
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+use wait_timeout::ChildExt;
+
+/// 按扩展名配置的外部命令；其余文件与命令失败的文件交给 `fallback`
+pub struct ExternalReader {
+    /// 小写、不带点的扩展名到拆分好的命令
+    commands: HashMap<String, Vec<String>>,
+    timeout: Duration,
+    fallback: Box<dyn Reader>,
+}
+
+impl ExternalReader {
+    pub fn new(fallback: Box<dyn Reader>, timeout: Duration) -> Self {
+        Self {
+            commands: HashMap::new(),
+            timeout,
+            fallback,
+        }
+    }
+
+    /// 为扩展名配置命令，如 `add("ipynb", "jupyter nbconvert --to script --stdout {}")`
+    pub fn add(&mut self, ext: &str, command: &str) -> anyhow::Result<()> {
+        let words = shell_words::split(command)
+            .map_err(|e| anyhow::anyhow!("invalid reader command '{}': {}", command, e))?;
+        if words.is_empty() {
+            anyhow::bail!("empty reader command for '{}'", ext);
+        }
+        let ext = ext.trim().trim_start_matches('.').to_ascii_lowercase();
+        self.commands.insert(ext, words);
+        Ok(())
+    }
+
+    fn command_for(&self, path: &Path) -> Option<&[String]> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        self.commands.get(&ext).map(Vec::as_slice)
+    }
+}
+
+/// 拆分 `--reader-cmd` 的 `EXT=COMMAND`
+pub fn parse_spec(spec: &str) -> anyhow::Result<(&str, &str)> {
+    match spec.split_once('=') {
+        Some((ext, command)) if !ext.trim().is_empty() && !command.trim().is_empty() => {
+            Ok((ext.trim(), command.trim()))
+        }
+        _ => anyhow::bail!("invalid --reader-cmd '{}': expected EXT=COMMAND", spec),
+    }
+}
+
+impl Reader for ExternalReader {
+    fn read(&self, path: &Path, bytes: &[u8], opts: ReadOptions) -> anyhow::Result<LoadedFile> {
+        let Some(words) = self.command_for(path) else {
+            return self.fallback.read(path, bytes, opts);
+        };
+        let takes_path = words.iter().any(|word| word.contains("{}"));
+        // --rev 与归档中的内容不一定在工作区中，只能交给从标准输入读取的命令
+        if takes_path && (opts.rev.is_some() || !path.is_file()) {
+            opts.logger.warn(&format!(
+                "提示: 外部命令需要工作区中的文件，回退到内置读取: {}",
+                path.display()
+            ));
+            return self.fallback.read(path, bytes, opts);
+        }
+        let input = (!takes_path).then_some(bytes);
+        match run(words, path, input, self.timeout) {
+            Ok(stdout) => {
+                let (text, encoding) = decode_content(&stdout);
+                Ok(LoadedFile::extracted(text.into_owned(), encoding))
+            }
+            Err(reason) => {
+                opts.logger.warn(&format!(
+                    "警告: 外部命令 {} 处理失败 ({}), 回退到内置读取: {}",
+                    words[0],
+                    reason,
+                    path.display()
+                ));
+                self.fallback.read(path, bytes, opts)
+            }
+        }
+    }
+
+    fn is_plain_text(&self, path: &Path) -> bool {
+        self.command_for(path).is_none() && self.fallback.is_plain_text(path)
+    }
+}
+
+// 运行命令并返回标准输出；无法启动、退出码非零或超时时返回原因
+fn run(
+    words: &[String],
+    path: &Path,
+    input: Option<&[u8]>,
+    timeout: Duration,
+) -> Result<Vec<u8>, String> {
+    let args: Vec<OsString> = words.iter().map(|word| substitute(word, path)).collect();
+    let mut child = Command::new(&args[0])
+        .args(&args[1..])
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    // 输入输出都在线程中读写，避免管道写满时互相等待
+    if let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) {
+        let input = input.to_vec();
+        thread::spawn(move || {
+            // 命令可能不读完输入就退出
+            let _ = stdin.write_all(&input);
+        });
+    }
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let status = match child.wait_timeout(timeout).map_err(|e| e.to_string())? {
+        Some(status) => status,
+        None => {
+            let _ = child.kill();
+            let _ = child.wait();
+            // 子进程派生的进程可能仍占着管道，不等待读取线程
+            return Err(format!("超时 {}s", timeout.as_secs_f64()));
+        }
+    };
+    let stdout = stdout.join().unwrap_or_default();
+    if !status.success() {
+        let stderr = stderr.join().unwrap_or_default();
+        let message = String::from_utf8_lossy(&stderr);
+        return Err(match message.lines().find(|line| !line.trim().is_empty()) {
+            Some(line) => format!("{}: {}", status, line.trim()),
+            None => status.to_string(),
+        });
+    }
+    Ok(stdout)
+}
+
+fn drain<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+// `{}` 替换为文件路径；整个参数就是 `{}` 时保留非 UTF-8 路径
+fn substitute(word: &str, path: &Path) -> OsString {
+    if word == "{}" {
+        return path.as_os_str().to_os_string();
+    }
+    OsStr::new(&word.replace("{}", &path.to_string_lossy())).to_os_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn specs_and_commands_are_split() {
+        assert_eq!(
+            parse_spec("ipynb = jupyter nbconvert --stdout {}").unwrap(),
+            ("ipynb", "jupyter nbconvert --stdout {}")
+        );
+        assert!(parse_spec("pdf").is_err());
+        assert!(parse_spec("=cat").is_err());
+
+        let mut reader = ExternalReader::new(Box::new(crate::TextReader), Duration::from_secs(1));
+        reader.add(".PDF", "pdftotext -layout '{}' -").unwrap();
+        assert_eq!(
+            reader.command_for(Path::new("a/b.pdf")).unwrap(),
+            ["pdftotext", "-layout", "{}", "-"]
+        );
+        assert!(reader.command_for(Path::new("a.txt")).is_none());
+        assert!(reader.add("x", "cmd 'unterminated").is_err());
+        assert_eq!(
+            substitute("--input={}", Path::new("x y.pdf")),
+            OsString::from("--input=x y.pdf")
+        );
+    }
+}
@@ -0,0 +1,740 @@
+//! 进程内的富文本提取：docx / odt / rtf / html / pdf。
+//!
+//! 不依赖 macOS `textutil`，在所有平台上产出一致的纯文本。
+//! pdf 需要 `pdf` 特性，docx / odt 需要 `office` 特性；未启用时提取返回错误，由调用方回退。
+
+use encoding_rs::{Encoding, WINDOWS_1252};
+use quick_xml::events::{BytesStart, Event};
+use std::ffi::OsStr;
+#[cfg(feature = "office")]
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+/// 支持原生提取的文档格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DocFormat {
+    Docx,
+    Odt,
+    Rtf,
+    Html,
+}
+
+impl DocFormat {
+    pub(crate) fn from_path(path: &Path) -> Option<Self> {
+        let ext = path
+            .extension()
+            .and_then(OsStr::to_str)
+            .map(|s| s.to_ascii_lowercase())?;
+        match ext.as_str() {
+            "docx" => Some(DocFormat::Docx),
+            "odt" => Some(DocFormat::Odt),
+            "rtf" => Some(DocFormat::Rtf),
+            "html" | "htm" | "xhtml" => Some(DocFormat::Html),
+            _ => None,
+        }
+    }
+}
+
+/// 按格式把原始字节转换为纯文本
+pub(crate) fn extract_text(format: DocFormat, bytes: &[u8]) -> anyhow::Result<String> {
+    let raw = match format {
+        DocFormat::Docx => docx_text(&zip_entry(bytes, "word/document.xml")?)?,
+        DocFormat::Odt => odt_text(&zip_entry(bytes, "content.xml")?)?,
+        DocFormat::Rtf => rtf_text(bytes),
+        DocFormat::Html => {
+            let (s, _) = crate::read::decode_content(bytes);
+            html_text(&s)
+        }
+    };
+    Ok(tidy(&raw))
+}
+
+pub(crate) fn is_pdf(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
+}
+
+/// 逐页提取 PDF 文本；加密或无法解析时返回错误
+#[cfg(feature = "pdf")]
+pub(crate) fn pdf_pages(bytes: &[u8]) -> anyhow::Result<Vec<String>> {
+    // pdf-extract 遇到畸形文件时可能 panic，这里兜底转换为错误
+    let result = std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem_by_pages(bytes))
+        .map_err(|_| anyhow::anyhow!("pdf parser panicked"))?;
+    match result {
+        Ok(pages) => Ok(pages.iter().map(|p| tidy(p)).collect()),
+        Err(_) if is_encrypted_pdf(bytes) => anyhow::bail!("encrypted pdf"),
+        Err(e) => Err(anyhow::anyhow!("{}", e)),
+    }
+}
+
+#[cfg(not(feature = "pdf"))]
+pub(crate) fn pdf_pages(_: &[u8]) -> anyhow::Result<Vec<String>> {
+    anyhow::bail!("built without the pdf feature")
+}
+
+#[cfg(feature = "pdf")]
+fn is_encrypted_pdf(bytes: &[u8]) -> bool {
+    bytes.windows(b"/Encrypt".len()).any(|w| w == b"/Encrypt")
+}
+
+#[cfg(feature = "office")]
+fn zip_entry(bytes: &[u8], name: &str) -> anyhow::Result<String> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+    let mut entry = archive
+        .by_name(name)
+        .map_err(|e| anyhow::anyhow!("missing {} in archive: {}", name, e))?;
+    let mut xml = String::new();
+    entry.read_to_string(&mut xml)?;
+    Ok(xml)
+}
+
+#[cfg(not(feature = "office"))]
+fn zip_entry(_: &[u8], _: &str) -> anyhow::Result<String> {
+    anyhow::bail!("built without the office feature")
+}
+
+// 段落、表格单元格与行的拼接状态，docx 与 odt 共用
+#[derive(Default)]
+struct TextBuilder {
+    out: String,
+    cell_depth: usize,
+}
+
+impl TextBuilder {
+    fn push_str(&mut self, s: &str) {
+        self.out.push_str(s);
+    }
+
+    fn paragraph_end(&mut self) {
+        // 单元格内的段落用空格相连，保持一行一行的表格
+        if self.cell_depth > 0 {
+            if !self.out.ends_with([' ', '\t', '\n']) {
+                self.out.push(' ');
+            }
+        } else {
+            self.out.push('\n');
+        }
+    }
+
+    fn cell_start(&mut self) {
+        self.cell_depth += 1;
+    }
+
+    fn cell_end(&mut self) {
+        self.cell_depth = self.cell_depth.saturating_sub(1);
+        let trimmed = self.out.trim_end_matches(' ').len();
+        self.out.truncate(trimmed);
+        self.out.push('\t');
+    }
+
+    fn row_end(&mut self) {
+        if self.out.ends_with('\t') {
+            self.out.pop();
+        }
+        self.out.push('\n');
+    }
+}
+
+fn docx_text(xml: &str) -> anyhow::Result<String> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut text = TextBuilder::default();
+    // 只有 <w:t> 中的字符属于正文，其余（如域代码 <w:instrText>）忽略
+    let mut in_text = false;
+    // <w:tab/> 只在 run（<w:r>）中表示制表符，<w:pPr><w:tabs> 中的是制表位定义
+    let mut run_depth = 0usize;
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"t" => in_text = true,
+                b"r" => run_depth += 1,
+                b"tc" => text.cell_start(),
+                _ => {}
+            },
+            Event::Empty(e) => match e.local_name().as_ref() {
+                b"tab" if run_depth > 0 => text.push_str("\t"),
+                b"br" | b"cr" => text.push_str("\n"),
+                b"p" => text.paragraph_end(),
+                _ => {}
+            },
+            Event::End(e) => match e.local_name().as_ref() {
+                b"t" => in_text = false,
+                b"r" => run_depth = run_depth.saturating_sub(1),
+                b"p" => text.paragraph_end(),
+                b"tc" => text.cell_end(),
+                b"tr" => text.row_end(),
+                _ => {}
+            },
+            Event::Text(e) if in_text => text.push_str(&e.unescape()?),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(text.out)
+}
+
+fn odt_text(xml: &str) -> anyhow::Result<String> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut text = TextBuilder::default();
+    // 段落嵌套深度：odt 的文本直接出现在 <text:p>/<text:h> 及其 span 中
+    let mut para_depth = 0usize;
+    // 批注等非正文内容
+    let mut skip_depth = 0usize;
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"annotation" | b"note-citation" => skip_depth += 1,
+                b"p" | b"h" => para_depth += 1,
+                b"table-cell" => text.cell_start(),
+                _ => {}
+            },
+            Event::Empty(e) if skip_depth == 0 => match e.local_name().as_ref() {
+                b"s" => text.push_str(&" ".repeat(odt_space_count(&e))),
+                b"tab" => text.push_str("\t"),
+                b"line-break" => text.push_str("\n"),
+                b"p" | b"h" => text.paragraph_end(),
+                b"table-cell" => {
+                    text.cell_start();
+                    text.cell_end();
+                }
+                _ => {}
+            },
+            Event::End(e) => match e.local_name().as_ref() {
+                b"annotation" | b"note-citation" => skip_depth = skip_depth.saturating_sub(1),
+                b"p" | b"h" => {
+                    para_depth = para_depth.saturating_sub(1);
+                    if skip_depth == 0 {
+                        text.paragraph_end();
+                    }
+                }
+                b"table-cell" => text.cell_end(),
+                b"table-row" => text.row_end(),
+                _ => {}
+            },
+            Event::Text(e) if para_depth > 0 && skip_depth == 0 => text.push_str(&e.unescape()?),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(text.out)
+}
+
+fn odt_space_count(e: &BytesStart<'_>) -> usize {
+    e.try_get_attribute("text:c")
+        .ok()
+        .flatten()
+        .and_then(|a| std::str::from_utf8(&a.value).ok()?.parse().ok())
+        .unwrap_or(1)
+}
+
+// 这些目的地组（destination group）不包含正文
+const RTF_SKIP_DESTINATIONS: &[&str] = &[
+    "fonttbl",
+    "colortbl",
+    "stylesheet",
+    "info",
+    "pict",
+    "object",
+    "header",
+    "footer",
+    "listtable",
+    "listoverridetable",
+    "rsidtbl",
+    "generator",
+    "xmlnstbl",
+    "themedata",
+    "colorschememapping",
+    "latentstyles",
+    "datastore",
+    "filetbl",
+    "revtbl",
+];
+
+#[derive(Clone, Copy)]
+struct RtfGroup {
+    skip: bool,
+    // \ucN：每个 \u 之后需要忽略的后备字符数
+    uc: usize,
+}
+
+fn rtf_text(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    // \'hh 产生的原始字节，遇到其它内容时按代码页解码
+    let mut pending: Vec<u8> = Vec::new();
+    let mut codepage: &'static Encoding = WINDOWS_1252;
+    let mut stack: Vec<RtfGroup> = Vec::new();
+    let mut group = RtfGroup { skip: false, uc: 1 };
+    // 组起始位置：用于识别 {\* 以及 {\fonttbl 这类目的地
+    let mut at_group_start = false;
+    let mut skip_fallback = 0usize;
+    let mut i = 0;
+
+    let flush = |pending: &mut Vec<u8>, out: &mut String, enc: &'static Encoding| {
+        if !pending.is_empty() {
+            let (s, _, _) = enc.decode(pending);
+            out.push_str(&s);
+            pending.clear();
+        }
+    };
+
+    while i < bytes.len() {
+        let b = bytes[i];
+        match b {
+            b'{' => {
+                stack.push(group);
+                at_group_start = true;
+                i += 1;
+                continue;
+            }
+            b'}' => {
+                flush(&mut pending, &mut out, codepage);
+                group = stack.pop().unwrap_or(group);
+                at_group_start = false;
+                i += 1;
+                continue;
+            }
+            b'\r' | b'\n' => {
+                i += 1;
+                continue;
+            }
+            b'\\' => {
+                i += 1;
+                let Some(&next) = bytes.get(i) else { break };
+                if next.is_ascii_alphabetic() {
+                    let start = i;
+                    while i < bytes.len() && bytes[i].is_ascii_alphabetic() {
+                        i += 1;
+                    }
+                    let word = std::str::from_utf8(&bytes[start..i]).unwrap_or_default();
+                    let num_start = i;
+                    if i < bytes.len() && bytes[i] == b'-' {
+                        i += 1;
+                    }
+                    while i < bytes.len() && bytes[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                    let param: Option<i32> = std::str::from_utf8(&bytes[num_start..i])
+                        .ok()
+                        .and_then(|s| s.parse().ok());
+                    if i < bytes.len() && bytes[i] == b' ' {
+                        i += 1;
+                    }
+
+                    if at_group_start && RTF_SKIP_DESTINATIONS.contains(&word) {
+                        group.skip = true;
+                    }
+                    at_group_start = false;
+                    if group.skip {
+                        continue;
+                    }
+                    if word != "u" {
+                        flush(&mut pending, &mut out, codepage);
+                    }
+                    if skip_fallback > 0 && word != "u" {
+                        skip_fallback -= 1;
+                        continue;
+                    }
+                    match word {
+                        "par" | "line" | "row" | "sect" | "page" => out.push('\n'),
+                        "tab" | "cell" => out.push('\t'),
+                        "emdash" => out.push('\u{2014}'),
+                        "endash" => out.push('\u{2013}'),
+                        "bullet" => out.push('\u{2022}'),
+                        "lquote" => out.push('\u{2018}'),
+                        "rquote" => out.push('\u{2019}'),
+                        "ldblquote" => out.push('\u{201C}'),
+                        "rdblquote" => out.push('\u{201D}'),
+                        "uc" => group.uc = param.unwrap_or(1).max(0) as usize,
+                        "ansicpg" => {
+                            if let Some(enc) = param.and_then(codepage_encoding) {
+                                codepage = enc;
+                            }
+                        }
+                        "u" => {
+                            flush(&mut pending, &mut out, codepage);
+                            if let Some(code) = param {
+                                // 负值表示按 16 位有符号数存储的码点
+                                let code = if code < 0 { code + 65536 } else { code } as u32;
+                                out.push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                            }
+                            skip_fallback = group.uc;
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                i += 1;
+                match next {
+                    b'*' if at_group_start => group.skip = true,
+                    b'*' => {}
+                    b'\'' => {
+                        let hex = bytes
+                            .get(i..i + 2)
+                            .and_then(|h| std::str::from_utf8(h).ok());
+                        if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                            i += 2;
+                            if !group.skip {
+                                if skip_fallback > 0 {
+                                    skip_fallback -= 1;
+                                } else {
+                                    pending.push(byte);
+                                }
+                            }
+                        }
+                    }
+                    _ if group.skip => {}
+                    b'\\' | b'{' | b'}' => {
+                        flush(&mut pending, &mut out, codepage);
+                        out.push(next as char);
+                    }
+                    b'~' => {
+                        flush(&mut pending, &mut out, codepage);
+                        out.push('\u{00A0}');
+                    }
+                    b'_' => {
+                        flush(&mut pending, &mut out, codepage);
+                        out.push('-');
+                    }
+                    b'\r' | b'\n' => {
+                        flush(&mut pending, &mut out, codepage);
+                        out.push('\n');
+                    }
+                    _ => {}
+                }
+                at_group_start = false;
+                continue;
+            }
+            _ => {}
+        }
+
+        at_group_start = false;
+        i += 1;
+        if group.skip {
+            continue;
+        }
+        if skip_fallback > 0 {
+            skip_fallback -= 1;
+            continue;
+        }
+        if b.is_ascii() {
+            flush(&mut pending, &mut out, codepage);
+            out.push(b as char);
+        } else {
+            pending.push(b);
+        }
+    }
+    flush(&mut pending, &mut out, codepage);
+    out
+}
+
+fn codepage_encoding(cp: i32) -> Option<&'static Encoding> {
+    let label = match cp {
+        874 => "windows-874".to_string(),
+        932 => "shift_jis".to_string(),
+        936 => "gbk".to_string(),
+        949 => "euc-kr".to_string(),
+        950 => "big5".to_string(),
+        1250..=1258 => format!("windows-{}", cp),
+        65001 => "utf-8".to_string(),
+        _ => return None,
+    };
+    Encoding::for_label(label.as_bytes())
+}
+
+// 块级元素：前后换行
+const HTML_BLOCK_TAGS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "title",
+    "tr",
+    "ul",
+];
+
+// 内容整体丢弃的元素
+const HTML_SKIP_TAGS: &[&str] = &["script", "style", "noscript", "template", "head"];
+
+fn html_text(html: &str) -> String {
+    let mut out = String::new();
+    let mut skip_until: Option<String> = None;
+    let mut pre_depth = 0usize;
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        let Some(lt) = rest.find('<') else {
+            if skip_until.is_none() {
+                push_html_text(&mut out, rest, pre_depth > 0);
+            }
+            break;
+        };
+        if skip_until.is_none() {
+            push_html_text(&mut out, &rest[..lt], pre_depth > 0);
+        }
+        rest = &rest[lt..];
+
+        if let Some(after) = rest.strip_prefix("<!--") {
+            rest = after.find("-->").map_or("", |end| &after[end + 3..]);
+            continue;
+        }
+        let Some(gt) = rest.find('>') else {
+            if skip_until.is_none() {
+                push_html_text(&mut out, rest, pre_depth > 0);
+            }
+            break;
+        };
+        let tag = &rest[1..gt];
+        rest = &rest[gt + 1..];
+
+        let closing = tag.starts_with('/');
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if name.is_empty() {
+            continue;
+        }
+
+        if let Some(target) = &skip_until {
+            if closing && &name == target {
+                skip_until = None;
+            }
+            continue;
+        }
+        if !closing && !tag.ends_with('/') && HTML_SKIP_TAGS.contains(&name.as_str()) {
+            // <head> 中只保留 <title>
+            if name == "head" {
+                if let Some(title) = html_title(rest) {
+                    push_html_text(&mut out, &title, false);
+                    out.push('\n');
+                }
+            }
+            skip_until = Some(name);
+            continue;
+        }
+
+        if name == "pre" {
+            if closing {
+                pre_depth = pre_depth.saturating_sub(1);
+            } else {
+                pre_depth += 1;
+            }
+        }
+        if matches!(name.as_str(), "td" | "th") {
+            if closing {
+                out.push('\t');
+            }
+            continue;
+        }
+        if HTML_BLOCK_TAGS.contains(&name.as_str()) {
+            if name == "tr" && closing && out.ends_with('\t') {
+                out.pop();
+            }
+            if !out.is_empty() && !out.ends_with('\n') {
+                out.push('\n');
+            }
+            if name == "li" && !closing {
+                out.push_str("- ");
+            }
+        }
+    }
+    out
+}
+
+fn html_title(head: &str) -> Option<String> {
+    let lower = head.to_ascii_lowercase();
+    let end_head = lower.find("</head").unwrap_or(lower.len());
+    let start = lower[..end_head].find("<title")?;
+    let open_end = start + lower[start..].find('>')? + 1;
+    let close = open_end + lower[open_end..].find("</title")?;
+    Some(head[open_end..close].trim().to_string())
+}
+
+fn push_html_text(out: &mut String, raw: &str, preformatted: bool) {
+    let decoded = decode_html_entities(raw);
+    if preformatted {
+        out.push_str(&decoded);
+        return;
+    }
+    for c in decoded.chars() {
+        if c.is_whitespace() && c != '\u{00A0}' {
+            if !out.is_empty() && !out.ends_with([' ', '\n', '\t']) {
+                out.push(' ');
+            }
+        } else {
+            out.push(c);
+        }
+    }
+}
+
+fn decode_html_entities(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest.find(';').filter(|&end| end <= 10).and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{00A0}'),
+                "copy" => Some('\u{00A9}'),
+                "reg" => Some('\u{00AE}'),
+                "mdash" => Some('\u{2014}'),
+                "ndash" => Some('\u{2013}'),
+                "hellip" => Some('\u{2026}'),
+                _ => {
+                    let num = entity.strip_prefix('#')?;
+                    let code = match num.strip_prefix(['x', 'X']) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => num.parse().ok()?,
+                    };
+                    char::from_u32(code)
+                }
+            }?;
+            Some((c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// 去掉行尾空白，把连续空行压缩为一行，并保证以换行结尾
+fn tidy(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut blank_run = 0usize;
+    for line in raw.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank_run += 1;
+            if blank_run > 1 || out.is_empty() {
+                continue;
+            }
+        } else {
+            blank_run = 0;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    while out.ends_with("\n\n") {
+        out.pop();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn docx_paragraphs_and_tables() {
+        let xml = r#"<w:document xmlns:w="w"><w:body>
+            <w:p><w:r><w:t>Hello</w:t></w:r><w:r><w:tab/><w:t xml:space="preserve">world &amp; co</w:t></w:r></w:p>
+            <w:tbl><w:tr><w:tc><w:p><w:r><w:t>a</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>b</w:t></w:r></w:p></w:tc></w:tr></w:tbl>
+            <w:p><w:r><w:instrText>PAGE</w:instrText><w:t>end</w:t></w:r></w:p>
+        </w:body></w:document>"#;
+        let text = tidy(&docx_text(xml).unwrap());
+        assert_eq!(text, "Hello\tworld & co\na\tb\nend\n");
+    }
+
+    #[test]
+    fn docx_tab_stop_definitions_are_not_tabs() {
+        let xml = r#"<w:document xmlns:w="w"><w:body>
+            <w:p><w:pPr><w:tabs><w:tab w:val="left" w:pos="720"/><w:tab w:val="right" w:pos="9360"/></w:tabs></w:pPr>
+            <w:r><w:t>Name</w:t></w:r><w:r><w:tab/><w:t>Value</w:t></w:r></w:p>
+        </w:body></w:document>"#;
+        let text = tidy(&docx_text(xml).unwrap());
+        assert_eq!(text, "Name\tValue\n");
+    }
+
+    #[test]
+    fn odt_spaces_and_headings() {
+        let xml = r#"<office:document-content><office:body><office:text>
+            <text:h>Title</text:h>
+            <text:p>a<text:s text:c="3"/>b<text:span>c</text:span></text:p>
+        </office:text></office:body></office:document-content>"#;
+        let text = tidy(&odt_text(xml).unwrap());
+        assert_eq!(text, "Title\na   bc\n");
+    }
+
+    #[test]
+    fn rtf_strips_control_words_and_tables() {
+        let rtf = br"{\rtf1\ansi\ansicpg1252{\fonttbl{\f0 Helvetica;}}{\*\generator x;}
+\f0 Hello \b bold\b0 , \'e9t\'e9\par
+caf\u233?\par
+\{braces\}}";
+        assert_eq!(
+            tidy(&rtf_text(rtf)),
+            "Hello bold, \u{e9}t\u{e9}\ncaf\u{e9}\n{braces}\n"
+        );
+    }
+
+    #[test]
+    fn html_block_elements_and_entities() {
+        let html = "<html><head><title>T</title><style>p{}</style></head>\
+            <body><h1>Head</h1><p>a&nbsp;&amp;   b</p><ul><li>one</li><li>two</li></ul>\
+            <table><tr><td>x</td><td>y</td></tr></table><script>var a;</script></body></html>";
+        assert_eq!(
+            tidy(&html_text(html)),
+            "T\nHead\na\u{a0}& b\n- one\n- two\nx\ty\n"
+        );
+    }
+
+    #[test]
+    fn doc_format_from_extension() {
+        assert_eq!(
+            DocFormat::from_path(Path::new("a.DOCX")),
+            Some(DocFormat::Docx)
+        );
+        assert_eq!(
+            DocFormat::from_path(Path::new("a.htm")),
+            Some(DocFormat::Html)
+        );
+        assert_eq!(DocFormat::from_path(Path::new("a.doc")), None);
+    }
+}
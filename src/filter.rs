@@ -0,0 +1,130 @@
+//! `--contains` / `--not-contains` / `--grep`：按正文内容筛选文件，或只输出匹配的区块。
+//!
+//! 正则逐行匹配（行尾换行不参与），只作用于文本正文。
+
+use crate::read::{FileBody, FileOutcome, LoadedFile};
+use crate::render::{merge_ranges, LineRange};
+use crate::stream;
+use regex::Regex;
+use std::io;
+
+pub struct ContentFilter {
+    pub contains: Option<Regex>,
+    pub not_contains: Option<Regex>,
+    /// `--grep` 的正则与上下文行数
+    pub grep: Option<(Regex, usize)>,
+}
+
+/// 筛选结果；保留时可能附带 `--grep` 得到的行范围
+pub enum Verdict {
+    Drop,
+    Keep(Option<Vec<LineRange>>),
+}
+
+impl ContentFilter {
+    pub fn check(&self, outcome: &FileOutcome) -> io::Result<Verdict> {
+        let loaded = match outcome {
+            FileOutcome::Loaded(loaded) if loaded.is_text() => loaded,
+            // 读取失败仍然输出以便看到错误；其余无法确认内容，要求匹配时不保留
+            FileOutcome::Failed(_) => return Ok(Verdict::Keep(None)),
+            _ if self.contains.is_some() || self.grep.is_some() => return Ok(Verdict::Drop),
+            _ => return Ok(Verdict::Keep(None)),
+        };
+        if let Some(re) = &self.contains {
+            if matching_lines(loaded, re, true)?.0.is_empty() {
+                return Ok(Verdict::Drop);
+            }
+        }
+        if let Some(re) = &self.not_contains {
+            if !matching_lines(loaded, re, true)?.0.is_empty() {
+                return Ok(Verdict::Drop);
+            }
+        }
+        let Some((re, context)) = &self.grep else {
+            return Ok(Verdict::Keep(None));
+        };
+        let (found, total) = matching_lines(loaded, re, false)?;
+        if found.is_empty() {
+            return Ok(Verdict::Drop);
+        }
+        Ok(Verdict::Keep(Some(context_ranges(&found, *context, total))))
+    }
+}
+
+// 匹配的行号（从 1 开始）与总行数；`first_only` 时找到一处即停止，总行数不完整
+fn matching_lines(
+    loaded: &LoadedFile,
+    re: &Regex,
+    first_only: bool,
+) -> io::Result<(Vec<usize>, usize)> {
+    let mut found = Vec::new();
+    let mut total = 0;
+    let mut line_start = true;
+    // 逐段检查，返回是否继续读取；流式正文的长行分成多段，跨段的匹配不会被发现
+    let mut check = |piece: &str| {
+        if line_start {
+            total += 1;
+        }
+        line_start = piece.ends_with('\n');
+        let piece = piece.strip_suffix('\n').unwrap_or(piece);
+        let piece = piece.strip_suffix('\r').unwrap_or(piece);
+        if found.last() != Some(&total) && re.is_match(piece) {
+            found.push(total);
+        }
+        !first_only || found.is_empty()
+    };
+    match &loaded.body {
+        FileBody::Text(s) => {
+            for line in s.split_inclusive('\n') {
+                if !check(line) {
+                    break;
+                }
+            }
+        }
+        FileBody::Stream { path, encoding, .. } => {
+            for piece in stream::DecodedLines::open(path, *encoding)? {
+                if !check(&piece?) {
+                    break;
+                }
+            }
+        }
+        FileBody::Binary { .. } => {}
+    }
+    Ok((found, total))
+}
+
+// 每个匹配行前后各扩展 context 行，合并重叠或相邻的区块
+fn context_ranges(found: &[usize], context: usize, total: usize) -> Vec<LineRange> {
+    let mut ranges: Vec<LineRange> = found
+        .iter()
+        .map(|&line| LineRange {
+            start: line.saturating_sub(context).max(1),
+            end: (line + context).min(total),
+        })
+        .collect();
+    merge_ranges(&mut ranges);
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grep_hunks_include_context_and_merge() {
+        let range = |start, end| LineRange { start, end };
+        assert_eq!(
+            context_ranges(&[2, 5, 20], 2, 21),
+            vec![range(1, 7), range(18, 21)]
+        );
+        assert_eq!(context_ranges(&[3, 4], 0, 10), vec![range(3, 4)]);
+
+        let loaded = LoadedFile::text("fn a()\r\n// TODO\nfn b()\n".to_string(), None);
+        let re = Regex::new("^fn .*\\)$").unwrap();
+        assert_eq!(
+            matching_lines(&loaded, &re, false).unwrap(),
+            (vec![1, 3], 3)
+        );
+        assert_eq!(matching_lines(&loaded, &re, true).unwrap(), (vec![1], 1));
+    }
+}
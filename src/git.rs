@@ -0,0 +1,129 @@
+//! `--git-tracked` / `--git-staged` / `--git-modified` / `--git-changed-since`：
+//! 调用本地 `git` 命令得到文件列表（不访问网络）；`--rev` 从对象库读取内容。
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// 命令行中启用的 git 选择器；多个选择器取并集
+#[derive(Debug, Default)]
+pub struct GitSelectors<'a> {
+    pub tracked: bool,
+    pub staged: bool,
+    pub modified: bool,
+    pub changed_since: Option<&'a str>,
+}
+
+impl GitSelectors<'_> {
+    pub fn is_empty(&self) -> bool {
+        !self.tracked && !self.staged && !self.modified && self.changed_since.is_none()
+    }
+}
+
+/// 返回选中的文件，路径相对当前目录且限于当前目录之下；已删除的文件不包含在内
+pub fn select_files(selectors: &GitSelectors) -> anyhow::Result<BTreeSet<PathBuf>> {
+    let mut files = BTreeSet::new();
+    if selectors.tracked {
+        files.extend(git_paths(&["ls-files", "-z"])?);
+    }
+    if selectors.staged {
+        files.extend(git_paths(&[
+            "diff",
+            "--cached",
+            "--name-only",
+            "--relative",
+            "--diff-filter=d",
+            "-z",
+        ])?);
+    }
+    if selectors.modified {
+        files.extend(git_paths(&[
+            "diff",
+            "--name-only",
+            "--relative",
+            "--diff-filter=d",
+            "-z",
+        ])?);
+    }
+    if let Some(rev) = selectors.changed_since {
+        // 与分支分叉点比较，得到分支上（含未提交改动）碰过的文件
+        if rev.starts_with('-') {
+            anyhow::bail!("invalid git revision '{}'", rev);
+        }
+        let base = git_output(&["merge-base", rev, "HEAD"])?;
+        let base = String::from_utf8_lossy(&base).trim().to_string();
+        files.extend(git_paths(&[
+            "diff",
+            "--name-only",
+            "--relative",
+            "--diff-filter=d",
+            "-z",
+            &base,
+        ])?);
+    }
+    Ok(files)
+}
+
+fn git_paths(args: &[&str]) -> anyhow::Result<Vec<PathBuf>> {
+    let output = git_output(args)?;
+    Ok(output
+        .split(|&b| b == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| PathBuf::from(String::from_utf8_lossy(name).into_owned()))
+        .collect())
+}
+
+fn git_output(args: &[&str]) -> anyhow::Result<Vec<u8>> {
+    if which::which("git").is_err() {
+        anyhow::bail!("git command not found");
+    }
+    let output = Command::new("git").args(args).output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("git {} failed: {}", args.join(" "), stderr.trim());
+    }
+    Ok(output.stdout)
+}
+
+/// 确认 `rev` 能解析为提交
+pub fn verify_rev(rev: &str) -> anyhow::Result<()> {
+    if rev.starts_with('-') {
+        anyhow::bail!("invalid git revision '{}'", rev);
+    }
+    git_output(&[
+        "rev-parse",
+        "--verify",
+        "--quiet",
+        &format!("{}^{{commit}}", rev),
+    ])
+    .map_err(|_| anyhow::anyhow!("unknown git revision '{}'", rev))?;
+    Ok(())
+}
+
+/// 列出 `rev` 中当前目录之下的所有文件及其大小（路径相对当前目录）
+pub fn tree_files(rev: &str) -> anyhow::Result<BTreeMap<PathBuf, u64>> {
+    let output = git_output(&["ls-tree", "-r", "-l", "-z", rev])?;
+    let mut files = BTreeMap::new();
+    for record in output.split(|&b| b == 0).filter(|r| !r.is_empty()) {
+        let record = String::from_utf8_lossy(record);
+        // <mode> SP <type> SP <object> SP <size> TAB <path>
+        let Some((meta, path)) = record.split_once('\t') else {
+            continue;
+        };
+        let mut fields = meta.split_whitespace();
+        if fields.nth(1) != Some("blob") {
+            // 子模块等非文件条目
+            continue;
+        }
+        let size = fields.nth(1).and_then(|s| s.parse().ok()).unwrap_or(0);
+        files.insert(PathBuf::from(path), size);
+    }
+    Ok(files)
+}
+
+/// 从对象库读取 `rev` 中某个文件的内容（路径相对当前目录）
+pub fn read_blob(rev: &str, path: &Path) -> anyhow::Result<Vec<u8>> {
+    let rel = path.strip_prefix(".").unwrap_or(path);
+    let spec = format!("{}:./{}", rev, rel.to_string_lossy());
+    git_output(&["cat-file", "blob", &spec])
+}
@@ -0,0 +1,158 @@
+//! `--format json|jsonl` 的结构化输出。
+
+use crate::read::{FileBody, FileOutcome};
+use crate::render::{rendered_body, snipped_lines, BodyStyle};
+use crate::FileEntry;
+use serde::Serialize;
+use std::io::Write;
+use std::time::SystemTime;
+
+/// 每个文件对应的一条记录
+#[derive(Debug, Serialize)]
+pub struct JsonRecord<'a> {
+    /// 与分隔符 Header 中一致的显示路径
+    pub path: &'a str,
+    /// `--rev` 指定的版本
+    pub rev: Option<&'a str>,
+    pub size: Option<u64>,
+    /// 修改时间（Unix 秒）
+    pub mtime: Option<u64>,
+    /// 文本编码；二进制或未读取时为 null
+    pub encoding: Option<&'a str>,
+    pub pages: Option<usize>,
+    /// 正文是从文档中提取（或由外部命令转换）的文本，不是文件的原始内容
+    pub extracted: bool,
+    /// 实际应用的二进制策略（skip / hex / base64）；文本文件为 null
+    pub binary: Option<&'static str>,
+    /// 只输出了部分行时的范围列表 `[[start, end], ...]`
+    pub lines: Option<Vec<[usize; 2]>>,
+    pub clip: Option<JsonClip>,
+    /// 正文带有 `--line-numbers` 的行号
+    pub numbered: bool,
+    /// `--budget-mode clip` 按 token 预算截断了正文
+    pub budget_clip: Option<JsonBudgetClip>,
+    pub content: Option<String>,
+    /// 未读取的原因（如 max-size）
+    pub skipped: Option<&'static str>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonClip {
+    pub head: usize,
+    pub tail: usize,
+    pub snipped_lines: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonBudgetClip {
+    pub snipped_lines: usize,
+}
+
+/// 把记录编码为单行 JSON；与分隔无关，可以在工作线程中完成
+pub fn encode(record: &JsonRecord<'_>) -> anyhow::Result<Vec<u8>> {
+    Ok(serde_json::to_vec(record)?)
+}
+
+/// json 输出为数组，jsonl 每行一个对象；两者都按文件逐条写出
+pub struct JsonWriter {
+    lines: bool,
+    written: usize,
+}
+
+impl JsonWriter {
+    pub fn new(lines: bool) -> Self {
+        Self { lines, written: 0 }
+    }
+
+    /// 写出一条由 `encode` 编码好的记录，补上数组或行的分隔
+    pub fn write<W: Write>(&mut self, out: &mut W, encoded: &[u8]) -> anyhow::Result<()> {
+        if !self.lines {
+            out.write_all(if self.written == 0 { b"[\n" } else { b",\n" })?;
+        }
+        out.write_all(encoded)?;
+        if self.lines {
+            writeln!(out)?;
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    pub fn finish<W: Write>(&mut self, out: &mut W) -> anyhow::Result<()> {
+        if !self.lines {
+            out.write_all(if self.written == 0 { b"[]\n" } else { b"\n]\n" })?;
+        }
+        Ok(())
+    }
+}
+
+/// 构造一个文件的记录；content 与文本输出一致（含裁剪）
+pub fn record<'a>(
+    rel_path: &'a str,
+    entry: &FileEntry,
+    outcome: &'a FileOutcome,
+    style: BodyStyle,
+    rev: Option<&'a str>,
+) -> anyhow::Result<JsonRecord<'a>> {
+    let mtime = entry
+        .mtime
+        .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs());
+    let mut record = JsonRecord {
+        path: rel_path,
+        rev,
+        size: entry.len,
+        mtime,
+        encoding: None,
+        pages: None,
+        extracted: false,
+        binary: None,
+        lines: None,
+        clip: None,
+        numbered: false,
+        budget_clip: None,
+        content: None,
+        skipped: None,
+        error: None,
+    };
+    match outcome {
+        FileOutcome::Loaded(loaded) => {
+            record.pages = loaded.pages;
+            record.extracted = loaded.extracted;
+            match &loaded.body {
+                FileBody::Text(_) | FileBody::Stream { .. } => {
+                    record.encoding = Some(loaded.encoding.unwrap_or("UTF-8"));
+                    record.content = Some(rendered_body(loaded, style)?);
+                    record.lines = loaded
+                        .lines
+                        .as_ref()
+                        .map(|ranges| ranges.iter().map(|r| [r.start, r.end]).collect());
+                    // 被预算截断时正文已替换，--clip 的省略数按截断前的行数计算
+                    let total_lines = match style.budget {
+                        Some(budget) => budget.source_lines,
+                        None => loaded.total_lines()?,
+                    };
+                    record.clip = style
+                        .clip
+                        .filter(|_| style.ranges.is_none())
+                        .map(|c| JsonClip {
+                            head: c.head,
+                            tail: c.tail,
+                            snipped_lines: snipped_lines(total_lines, c),
+                        });
+                    record.numbered = style.numbers.is_some();
+                    record.budget_clip = style.budget.map(|budget| JsonBudgetClip {
+                        snipped_lines: budget.snipped_lines,
+                    });
+                }
+                FileBody::Binary { strategy, encoded } => {
+                    record.binary = Some(strategy.name());
+                    record.content = encoded.clone();
+                }
+            }
+        }
+        FileOutcome::TooLarge => record.skipped = Some("max-size"),
+        FileOutcome::Failed(err) => record.error = Some(err.to_string()),
+    }
+    Ok(record)
+}
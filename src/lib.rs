@@ -0,0 +1,96 @@
+//! printfiles 的库接口：按条目收集文件（[`Collector`]），用可替换的读取后端（[`Reader`]）
+//! 得到正文，再以分隔符风格（[`Renderer`]）输出文件块。完整的打印流程是 [`print::run`]，
+//! 命令行工具是其上的一层薄封装。
+//!
+//! ```no_run
+//! use printfiles::{
+//!     load_file, write_block, BinaryStrategy, BodyStyle, Collector, Divider, FileOutcome,
+//!     Logger, ReadOptions, ReaderKind,
+//! };
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let mut collector = Collector::new().ext(Some("rs"));
+//! collector.add("src")?;
+//! let reader = ReaderKind::Auto.reader();
+//! let logger = Logger::new(false, true);
+//! let opts = ReadOptions {
+//!     binary: BinaryStrategy::Skip,
+//!     rev: None,
+//!     notebook_outputs: None,
+//!     logger: &logger,
+//! };
+//! let mut out = std::io::stdout();
+//! for entry in collector.finish() {
+//!     let outcome = FileOutcome::Loaded(load_file(reader.as_ref(), &entry.path, opts)?);
+//!     let rel = entry.display(None);
+//!     write_block(&mut out, &Divider::XmlTag, &rel, &outcome, BodyStyle::default(), None)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod archive;
+pub mod collect;
+pub mod diff;
+pub mod external;
+mod extract;
+pub mod filter;
+pub mod git;
+pub mod json;
+mod notebook;
+pub mod outline;
+pub mod parallel;
+pub mod print;
+pub mod read;
+pub mod render;
+pub mod split;
+mod stream;
+pub mod summary;
+pub mod tokens;
+pub mod unpack;
+
+pub use collect::{
+    parse_file_list, range_key, resolve_relative_base, sort_entries, split_line_range,
+    ArchiveMember, Collector, FileEntry, SortKey,
+};
+pub use external::ExternalReader;
+pub use read::{
+    load_file, AutoReader, BinaryStrategy, FileBody, FileOutcome, LoadedFile, NativeReader,
+    ReadOptions, Reader, ReaderKind, TextReader, TextutilReader,
+};
+pub use render::{
+    clamp_ranges, format_ranges, merge_ranges, parse_clip_spec, rendered_body, write_block,
+    BodyStyle, BudgetClip, ClipSpec, Divider, HeaderMeta, LineNumbers, LineRange, Renderer,
+};
+
+/// 输出到标准错误的日志：info 只在 verbose 时输出，quiet 时只保留 error
+#[derive(Clone)]
+pub struct Logger {
+    verbose: bool,
+    quiet: bool,
+}
+
+impl Logger {
+    pub fn new(verbose: bool, quiet: bool) -> Self {
+        Self { verbose, quiet }
+    }
+    /// info 日志是否会输出
+    pub fn is_verbose(&self) -> bool {
+        self.verbose && !self.quiet
+    }
+    pub fn info(&self, msg: &str) {
+        if self.quiet || !self.verbose {
+            return;
+        }
+        eprintln!("{}", msg);
+    }
+    pub fn warn(&self, msg: &str) {
+        if self.quiet {
+            return;
+        }
+        eprintln!("{}", msg);
+    }
+    pub fn error(&self, msg: &str) {
+        eprintln!("{}", msg);
+    }
+}
@@ -1,93 +1,78 @@
-use base64::engine::general_purpose::STANDARD as Base64;
-use base64::Engine;
-use chardetng::EncodingDetector;
-use clap::{Parser, ValueEnum};
-use globwalk::GlobWalkerBuilder;
-use std::borrow::Cow;
-use std::collections::BTreeSet;
-use std::ffi::OsStr;
-use std::fs;
-use std::io::{self, Write};
+use clap::{CommandFactory, Parser};
+use printfiles::print::{self, BudgetMode, OutputFormat, PrintOptions, Status, SummaryTarget};
+use printfiles::tokens::Tokenizer;
+use printfiles::{
+    diff, parse_clip_spec, unpack, BinaryStrategy, Divider, Logger, ReaderKind, SortKey,
+};
+use regex::Regex;
+use std::ffi::OsString;
+use std::io;
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use std::time::SystemTime;
-
-#[derive(Debug, Clone, Copy, ValueEnum)]
-enum Reader {
-    /// 直接按文本读取（默认）
-    Text,
-    /// 调用 macOS `textutil` 读取（适合 doc/docx/rtf/html 等）
-    Textutil,
-    /// 自动：对部分扩展名用 textutil，其它走 Text
-    Auto,
-}
-
-#[derive(Debug, Clone, Copy, ValueEnum)]
-enum BinaryStrategy {
-    /// 跳过二进制文件
-    Skip,
-    /// 按十六进制输出
-    Hex,
-    /// 按 Base64 输出
-    Base64,
-    /// 强制按文本处理
-    Print,
-}
-
-#[derive(Debug, Clone, Copy, ValueEnum)]
-enum Divider {
-    /// 形如 ===path=== / ===end of 'path'===
-    Equals,
-    /// 形如 ``` path/to/file
-    TripleBacktick,
-    /// 形如 <file path="path/to/file">
-    XmlTag,
-}
-
-impl Divider {
-    // 修改：增加 encoding 参数
-    fn header(self, rel: &str, encoding: Option<&str>) -> String {
-        match self {
-            Divider::Equals => {
-                let enc_info = encoding.map(|e| format!(" [{}]", e)).unwrap_or_default();
-                format!("==={}{}===", rel, enc_info)
-            }
-            Divider::TripleBacktick => {
-                let enc_info = encoding.map(|e| format!(" [{}]", e)).unwrap_or_default();
-                format!("``` {}{}", rel, enc_info)
-            }
-            Divider::XmlTag => {
-                let enc_attr = encoding
-                    .map(|e| format!(" encoding=\"{}\"", e))
-                    .unwrap_or_default();
-                format!("<file path=\"{}\"{}>", escape_xml_attr(rel), enc_attr)
-            }
-        }
-    }
+use std::time::Duration;
 
-    fn footer(self, rel: &str) -> String {
-        match self {
-            Divider::Equals => format!("===end of '{}'===", rel),
-            Divider::TripleBacktick => "```".to_string(),
-            Divider::XmlTag => "</file>".to_string(),
-        }
-    }
-}
+mod config;
 
 #[derive(Debug, Parser)]
 #[command(
     name = "printfiles",
     version,
-    about = "Print files matched by globs/dirs with ===header=== and ===end of 'file'==="
+    about = "Print files matched by globs/dirs with ===header=== and ===end of 'file'===",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
 )]
 struct Args {
+    #[command(subcommand)]
+    command: Option<SubCommand>,
+
     /// 一组以空格或逗号分隔的模式或目录
-    #[arg(required = true)]
+    #[arg(required_unless_present_any = [
+        "files_from",
+        "git_tracked",
+        "git_staged",
+        "git_modified",
+        "git_changed_since",
+    ])]
     items: Vec<String>,
 
-    /// 读取后端：text(默认) / textutil / auto
-    #[arg(long, value_enum, default_value_t = Reader::Text)]
-    reader: Reader,
+    /// 从文件读取路径列表（每行一个，`-` 为标准输入）；路径按原样使用，不按逗号拆分
+    #[arg(
+        long,
+        short = '@',
+        value_name = "PATH",
+        num_args = 0..=1,
+        default_missing_value = "-"
+    )]
+    files_from: Option<String>,
+
+    /// --files-from 的列表以 NUL 分隔（配合 `find -print0`、`git ls-files -z`）
+    #[arg(long = "null", short = '0', requires = "files_from")]
+    null_separated: bool,
+
+    /// 条目 `-` 读取标准输入时 Header 中显示的名称
+    #[arg(long, value_name = "NAME", default_value = "stdin")]
+    stdin_name: String,
+
+    /// 读取后端：text(默认) / textutil / native / auto
+    #[arg(long, value_enum, default_value_t = ReaderKind::Text)]
+    reader: ReaderKind,
+
+    /// --reader auto 时用外部命令读取该扩展名的文件（可重复）；`{}` 替换为文件路径，
+    /// 命令中没有 `{}` 时内容从标准输入传入，失败或超时时回退到内置读取
+    #[arg(long, value_name = "EXT=COMMAND")]
+    reader_cmd: Vec<String>,
+
+    /// 外部读取命令的超时时间（秒）
+    #[arg(long, value_name = "SECS", default_value_t = 30)]
+    reader_timeout: u64,
+
+    /// 读取 Jupyter 笔记本时附带文本输出，每个输出最多保留 N 行（默认不输出）
+    #[arg(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        default_missing_value = "20"
+    )]
+    notebook_outputs: Option<usize>,
 
     /// 若传入目录，是否仅限这些扩展
     #[arg(long)]
@@ -113,669 +98,452 @@ struct Args {
     #[arg(long, default_value_t = true)]
     follow_links: bool,
 
-    /// 仅输出文件内容的前/后若干行
+    /// 不跟随符号链接
+    #[arg(long, overrides_with = "follow_links")]
+    no_follow_links: bool,
+
+    /// 并行读取、解码与渲染的线程数（0 表示按 CPU 核数）；输出与串行时逐字节一致
+    #[arg(long, short = 'j', value_name = "N", default_value_t = 1)]
+    jobs: usize,
+
+    /// 选择 git 跟踪的文件；与 items 同时使用时取交集
+    #[arg(long)]
+    git_tracked: bool,
+
+    /// 取消 --git-tracked（如配置文件中设置的）
+    #[arg(long, overrides_with = "git_tracked")]
+    no_git_tracked: bool,
+
+    /// 选择已暂存（git add）的文件
+    #[arg(long)]
+    git_staged: bool,
+
+    /// 取消 --git-staged（如配置文件中设置的）
+    #[arg(long, overrides_with = "git_staged")]
+    no_git_staged: bool,
+
+    /// 选择工作区中已修改、尚未暂存的文件
+    #[arg(long)]
+    git_modified: bool,
+
+    /// 取消 --git-modified（如配置文件中设置的）
+    #[arg(long, overrides_with = "git_modified")]
+    no_git_modified: bool,
+
+    /// 选择自 REF 分叉以来改动过的文件（含未提交的改动）
+    #[arg(long, value_name = "REF")]
+    git_changed_since: Option<String>,
+
+    /// 从指定的 git 版本（提交、分支、标签等）读取文件：模式按该版本的树匹配
     #[arg(
         long,
-        short = 'c',
-        value_name = "N[:M]",
-        num_args = 0..=1,
-        default_missing_value = "5:3"
+        value_name = "COMMIT",
+        conflicts_with_all = ["git_tracked", "git_staged", "git_modified", "git_changed_since"]
     )]
-    clip: Option<String>,
+    rev: Option<String>,
 
-    /// 输出分隔符风格
-    #[arg(long, value_enum, default_value_t = Divider::Equals)]
-    divider: Divider,
+    /// 排除匹配该模式的文件（可重复；也可在 items 中写 `!pattern`）
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
 
-    /// 输出详细日志
+    /// 不应用 .gitignore / .ignore / git exclude 规则
     #[arg(long, action = clap::ArgAction::SetTrue)]
-    verbose: bool,
+    no_ignore: bool,
 
-    /// 安静模式
-    #[arg(long, action = clap::ArgAction::SetTrue)]
-    quiet: bool,
-}
+    /// 应用忽略规则，取消 --no-ignore（如配置文件中设置的）
+    #[arg(long, overrides_with = "no_ignore")]
+    ignore: bool,
 
-#[derive(Debug, Clone, Copy, ValueEnum)]
-enum SortKey {
-    Name,
-    Size,
-    Mtime,
-}
+    /// 只保留内容匹配该正则的文本文件（逐行匹配）
+    #[arg(long, value_name = "REGEX")]
+    contains: Option<String>,
 
-#[derive(Debug, Clone, Copy)]
-struct ClipSpec {
-    head: usize,
-    tail: usize,
-}
+    /// 排除内容匹配该正则的文本文件
+    #[arg(long, value_name = "REGEX")]
+    not_contains: Option<String>,
 
-fn parse_clip_spec(raw: &str) -> anyhow::Result<ClipSpec> {
-    let s = raw.trim();
-    if s.is_empty() {
-        return Ok(ClipSpec { head: 5, tail: 3 });
-    }
-
-    let (head_str, tail_str_opt) = match s.split_once(':') {
-        Some((h, t)) => (h.trim(), Some(t.trim())),
-        None => (s, None),
-    };
+    /// 只输出匹配该正则的行（及上下文），区块之间以 snipped 标记隔开；没有匹配的文件不输出
+    #[arg(long, value_name = "REGEX")]
+    grep: Option<String>,
 
-    let head = if head_str.is_empty() {
-        0
-    } else {
-        head_str.parse::<usize>().map_err(|e| {
-            anyhow::anyhow!("invalid --clip value (head part '{}'): {}", head_str, e)
-        })?
-    };
+    /// --grep 匹配行前后各保留的行数
+    #[arg(
+        long,
+        short = 'C',
+        value_name = "N",
+        default_value_t = 0,
+        requires = "grep"
+    )]
+    context: usize,
 
-    let tail = match tail_str_opt {
-        Some(t) if !t.is_empty() => t.parse::<usize>().map_err(|e| {
-            anyhow::anyhow!("invalid --clip value (tail part '{}'): {}", t, e)
-        })?,
-        _ => 0,
-    };
+    /// 仅输出文件内容的前/后若干行
+    #[arg(
+        long,
+        short = 'c',
+        value_name = "N[:M]",
+        num_args = 0..=1,
+        default_missing_value = "5:3"
+    )]
+    clip: Option<String>,
 
-    if head == 0 && tail == 0 {
-        anyhow::bail!("invalid --clip value '{}': head and tail cannot both be 0", raw);
-    }
+    /// 在每行内容前加上原始行号（裁剪后仍保持原行号）
+    #[arg(long, short = 'n')]
+    line_numbers: bool,
 
-    Ok(ClipSpec { head, tail })
-}
+    /// 取消 --line-numbers（如配置文件中设置的）
+    #[arg(
+        long,
+        overrides_with = "line_numbers",
+        conflicts_with = "line_number_width"
+    )]
+    no_line_numbers: bool,
 
-fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
+    /// 行号宽度，默认按文件总行数的位数自动对齐
+    #[arg(long, value_name = "N", requires = "line_numbers")]
+    line_number_width: Option<usize>,
 
-    let logger = Logger::new(args.verbose, args.quiet);
+    /// 行号与内容之间的分隔符
+    #[arg(long, value_name = "SEP", default_value = ": ")]
+    line_number_separator: String,
 
-    let clip_spec = match args.clip.as_deref() {
-        Some(raw) => Some(parse_clip_spec(raw)?),
-        None => None,
-    };
+    /// 输出分隔符风格
+    #[arg(long, value_enum, default_value_t = Divider::Equals)]
+    divider: Divider,
 
-    let relative_base = resolve_relative_base(args.relative_from.as_ref())?;
+    /// 估算 token 时使用的分词器（--verbose 与 --max-tokens 使用）
+    #[arg(long, value_enum, default_value_t = Tokenizer::Cl100k)]
+    tokenizer: Tokenizer,
 
-    let mut tokens: Vec<String> = Vec::new();
-    for it in args.items.iter() {
-        for piece in it.split(',') {
-            let s = piece.trim();
-            if !s.is_empty() {
-                tokens.push(s.to_string());
-            }
-        }
-    }
+    /// token 预算：按排序顺序累计输出的 token 数（文件块的 Header、正文、Footer 与 --tree/--toc），超出后停止或裁剪
+    #[arg(long, value_name = "N")]
+    max_tokens: Option<usize>,
 
-    if tokens.is_empty() {
-        logger.warn("（未匹配到任何文件）");
-        std::process::exit(2);
-    }
+    /// 超出 --max-tokens 时的处理：stop(默认) 省略其余文件 / clip 先裁剪当前文件
+    #[arg(long, value_enum, default_value_t = BudgetMode::Stop)]
+    budget_mode: BudgetMode,
 
-    let mut files: BTreeSet<PathBuf> = BTreeSet::new();
+    /// 按字节数把输出拆分为多个分卷（part-001.txt …），需配合 --output-dir
+    #[arg(
+        long,
+        value_name = "N",
+        requires = "output_dir",
+        conflicts_with = "split_tokens"
+    )]
+    split_bytes: Option<usize>,
 
-    for token in tokens {
-        let path = Path::new(&token);
-        if path.is_dir() {
-            if let Err(err) = collect_dir(path, args.ext.as_deref(), &mut files, args.follow_links)
-            {
-                logger.warn(&format!("目录遍历失败 {token}: {err}"));
-            }
-            continue;
-        }
+    /// 按 token 数（--tokenizer）拆分输出，需配合 --output-dir
+    #[arg(long, value_name = "N", requires = "output_dir")]
+    split_tokens: Option<usize>,
 
-        match expand_glob(&token, args.follow_links) {
-            Ok(paths) => {
-                for path in paths {
-                    if path.is_file() {
-                        files.insert(normalize(&path));
-                    }
-                }
-            }
-            Err(err) => {
-                logger.warn(&format!("模式无效或没有匹配: {err}"));
-            }
-        }
-    }
+    /// 分卷与 index.txt 的输出目录
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
 
-    if files.is_empty() {
-        logger.warn("（未匹配到任何文件）");
-        std::process::exit(2);
-    }
+    /// 在文件块之前输出所选文件的目录树（含大小与行数）
+    #[arg(long, conflicts_with_all = ["split_bytes", "split_tokens"])]
+    tree: bool,
 
-    let mut entries: Vec<FileEntry> = files
-        .into_iter()
-        .map(|path| {
-            let len = file_len(&path).ok().flatten();
-            let mtime = metadata_mtime(&path).ok().flatten();
-            FileEntry { path, len, mtime }
-        })
-        .collect();
-
-    sort_entries(&mut entries, args.sort);
-
-    let mut out = io::BufWriter::new(io::stdout());
-    let mut had_error = false;
-
-    for entry in entries {
-        let path = entry.path;
-        let rel = rel_display(&path, relative_base.as_deref());
-        logger.info(&format!("处理文件: {}", rel));
-
-        // 逻辑修改：在这里处理文件大小限制
-        // 如果超过限制，直接打印默认 Header 并跳过
-        if let Some(limit) = args.max_size {
-            if let Some(size) = entry.len {
-                if size > limit {
-                    logger.warn(&format!(
-                        "提示: 跳过 {} (size={} > max_size={})",
-                        path.display(),
-                        size,
-                        limit
-                    ));
-                    // 因为没有读取，不知道编码，传入 None
-                    writeln!(out, "{}", args.divider.header(&rel, None))?;
-                    writeln!(out, "(skipped: file exceeds max size)")?;
-                    writeln!(out, "{}", args.divider.footer(&rel))?;
-                    continue;
-                }
-            }
-        }
+    /// 取消 --tree（如配置文件中设置的）
+    #[arg(long, overrides_with = "tree")]
+    no_tree: bool,
 
-        // 逻辑修改：将 divider 和 rel 传入 read_and_write，
-        // 由内部函数在读取并探测编码后，负责打印 Header。
-        match read_and_write(
-            &path,
-            &rel, // 新增参数
-            args.divider, // 新增参数
-            args.reader,
-            args.binary,
-            clip_spec,
-            &logger,
-            &mut out,
-        ) {
-            Ok(ended_with_newline) => {
-                if !ended_with_newline {
-                    writeln!(out)?;
-                }
-            }
-            Err(err) => {
-                logger.error(&format!("错误: 读取失败 {}: {err}", path.display()));
-                had_error = true;
-                // 只有在报错时（意味着内部可能没来得及打印 Header），
-                // 这里不需要补 Header，因为 read_and_write 内部不同阶段报错的处理比较复杂。
-                // 简单起见，如果 read_and_write 彻底失败，我们至少换行
-                writeln!(out)?;
-            }
-        }
+    /// 在文件块之前输出目录：各文件块在输出中的字节偏移与行号
+    #[arg(long, conflicts_with_all = ["split_bytes", "split_tokens"])]
+    toc: bool,
 
-        let footer = args.divider.footer(&rel);
-        writeln!(out, "{}", footer)?;
-    }
+    /// 取消 --toc（如配置文件中设置的）
+    #[arg(long, overrides_with = "toc")]
+    no_toc: bool,
 
-    out.flush()?;
-    if had_error {
-        std::process::exit(1);
-    }
+    /// 结束时汇总打印/裁剪/跳过/失败的文件数与总量；默认追加到标准输出
+    #[arg(
+        long,
+        value_enum,
+        value_name = "TARGET",
+        num_args = 0..=1,
+        default_missing_value = "stdout"
+    )]
+    summary: Option<SummaryTarget>,
 
-    Ok(())
-}
+    /// 输出格式：text(默认) / json / jsonl
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
 
-// ... (collect_dir, normalize, rel_display, strip_dot_slash 等辅助函数保持不变) ...
-// 为了节省篇幅，这里省略了未修改的辅助函数，请保留原有的 ...
+    /// 输出详细日志
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    verbose: bool,
 
-fn collect_dir(
-    dir: &Path,
-    exts: Option<&str>,
-    files: &mut BTreeSet<PathBuf>,
-    follow_links: bool,
-) -> anyhow::Result<()> {
-    let walker = GlobWalkerBuilder::from_patterns(dir, &["**/*"])
-        .follow_links(follow_links)
-        .case_insensitive(false)
-        .build()?;
-    for entry in walker.filter_map(|e| e.ok()) {
-        let path = entry.path();
-        if path.is_file() {
-            if let Some(exts) = exts {
-                if !ext_match(path, exts) {
-                    continue;
-                }
-            }
-            files.insert(normalize(path));
-        }
-    }
-    Ok(())
-}
+    /// 取消 --verbose（如配置文件中设置的）
+    #[arg(long, overrides_with = "verbose")]
+    no_verbose: bool,
 
-fn normalize(p: &Path) -> PathBuf {
-    PathBuf::from(p)
-}
+    /// 安静模式
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    quiet: bool,
 
-fn rel_display(p: &Path, base: Option<&Path>) -> String {
-    let absolute = if p.is_absolute() {
-        p.to_path_buf()
-    } else if let Ok(cwd) = std::env::current_dir() {
-        cwd.join(p)
-    } else {
-        p.to_path_buf()
-    };
+    /// 取消 --quiet（如配置文件中设置的）
+    #[arg(long, overrides_with = "quiet")]
+    no_quiet: bool,
 
-    if let Some(base) = base {
-        if let Ok(stripped) = absolute.strip_prefix(base) {
-            return strip_dot_slash(stripped).to_string();
-        }
-    }
+    /// 使用配置文件中的命名配置 [profile.<NAME>]
+    #[arg(long, value_name = "NAME", conflicts_with = "no_config")]
+    profile: Option<String>,
 
-    if let Ok(cwd) = std::env::current_dir() {
-        if let Ok(stripped) = absolute.strip_prefix(&cwd) {
-            return strip_dot_slash(stripped).to_string();
-        }
-    }
+    /// 不读取 .printfiles.toml 与用户配置文件
+    #[arg(long)]
+    no_config: bool,
 
-    strip_dot_slash(p).to_string()
+    /// 允许 .printfiles.toml 设置会运行外部命令、写入文件或读取任意路径的选项（reader、output-dir 等）
+    #[arg(long, conflicts_with = "no_config")]
+    trust_config: bool,
 }
 
-fn strip_dot_slash(p: &Path) -> String {
-    let s = p.to_string_lossy();
-    s.strip_prefix("./").unwrap_or(&s).to_string()
+#[derive(Debug, clap::Subcommand)]
+enum SubCommand {
+    /// 把 printfiles 的输出还原为目录树
+    ///
+    /// 输出中的文本块总是以换行结尾，因此末尾没有换行的文件还原后会多出一个换行（`\n`）；
+    /// 其余内容（包括 CRLF 换行）按原样写回。
+    #[command(long_flag = "unpack")]
+    Unpack(UnpackArgs),
+    /// 比较两个目录或 git 版本，在文件块中输出统一差异格式
+    #[command(long_flag = "diff")]
+    Diff(DiffArgs),
+    /// 查看配置文件
+    #[command(long_flag = "config")]
+    Config(ConfigArgs),
 }
 
-fn resolve_relative_base(from: Option<&PathBuf>) -> anyhow::Result<Option<PathBuf>> {
-    let Some(base) = from else {
-        return Ok(None);
-    };
-    if base.is_absolute() {
-        return Ok(Some(base.clone()));
-    }
-    let cwd = std::env::current_dir()?;
-    Ok(Some(cwd.join(base)))
+#[derive(Debug, clap::Args)]
+struct ConfigArgs {
+    #[command(subcommand)]
+    action: ConfigAction,
 }
 
-fn file_len(path: &Path) -> anyhow::Result<Option<u64>> {
-    match path.metadata() {
-        Ok(meta) => Ok(Some(meta.len())),
-        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
-        Err(err) => Err(err.into()),
-    }
+#[derive(Debug, clap::Subcommand)]
+enum ConfigAction {
+    /// 输出合并后生效的设置及其来源（默认值、配置文件或 profile）
+    Show {
+        /// 同时应用的命名配置
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+
+        /// 同 `--trust-config`：采用项目配置中不受信任的选项
+        #[arg(long)]
+        trust_config: bool,
+    },
 }
 
-fn metadata_mtime(path: &Path) -> anyhow::Result<Option<SystemTime>> {
-    match path.metadata() {
-        Ok(meta) => Ok(meta.modified().ok()),
-        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
-        Err(err) => Err(err.into()),
-    }
-}
+#[derive(Debug, clap::Args)]
+struct UnpackArgs {
+    /// printfiles 的输出文件；省略或为 `-` 时读取标准输入
+    input: Option<PathBuf>,
 
-fn sort_entries(entries: &mut [FileEntry], key: SortKey) {
-    match key {
-        SortKey::Name => entries.sort_by(|a, b| a.path.cmp(&b.path)),
-        SortKey::Size => entries.sort_by(|a, b| {
-            a.len
-                .unwrap_or_default()
-                .cmp(&b.len.unwrap_or_default())
-                .then_with(|| a.path.cmp(&b.path))
-        }),
-        SortKey::Mtime => entries.sort_by(|a, b| {
-            a.mtime
-                .unwrap_or(SystemTime::UNIX_EPOCH)
-                .cmp(&b.mtime.unwrap_or(SystemTime::UNIX_EPOCH))
-                .then_with(|| a.path.cmp(&b.path))
-        }),
-    }
-}
+    /// 写入的目标根目录
+    #[arg(long, default_value = ".")]
+    to: PathBuf,
 
-fn ext_match(path: &Path, exts_csv: &str) -> bool {
-    let ext = path
-        .extension()
-        .and_then(OsStr::to_str)
-        .map(|s| s.to_ascii_lowercase());
-    let Some(ext) = ext else {
-        return false;
-    };
-    for e in exts_csv.split(',') {
-        if ext == e.trim().to_ascii_lowercase() {
-            return true;
-        }
-    }
-    false
-}
+    /// 只列出将要创建/更新的文件，不写入
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    dry_run: bool,
 
-struct FileEntry {
-    path: PathBuf,
-    len: Option<u64>,
-    mtime: Option<SystemTime>,
-}
+    /// 输出详细日志
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    verbose: bool,
 
-fn expand_glob(pattern: &str, follow_links: bool) -> anyhow::Result<Vec<PathBuf>> {
-    let has_glob = pattern.contains('*') || pattern.contains('?') || pattern.contains('[');
-    if !has_glob {
-        return Ok(vec![PathBuf::from(pattern)]);
-    }
-    let walker = GlobWalkerBuilder::from_patterns(".", &[pattern])
-        .follow_links(follow_links)
-        .case_insensitive(false)
-        .build()?;
-    Ok(walker
-        .filter_map(|e| e.ok())
-        .map(|e| e.path().to_path_buf())
-        .collect())
+    /// 安静模式
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    quiet: bool,
 }
 
-// 修改：返回 (解码内容, 编码名称)
-// 如果是 UTF-8，编码名称为 None
-fn decode_content(bytes: &[u8]) -> (Cow<'_, str>, Option<&'static str>) {
-    if let Ok(s) = std::str::from_utf8(bytes) {
-        return (Cow::Borrowed(s), None);
-    }
+#[derive(Debug, clap::Args)]
+struct DiffArgs {
+    /// 左侧（旧）：目录或 git 版本
+    left: String,
 
-    let mut detector = EncodingDetector::new();
-    detector.feed(bytes, true);
-    let encoding = detector.guess(None, true);
-    let (cow, _, _) = encoding.decode(bytes);
-    // 返回检测到的编码名称（如 GBK, EUC-JP 等）
-    (cow, Some(encoding.name()))
-}
+    /// 右侧（新）：目录或 git 版本
+    right: String,
 
-// 修改：read_and_write 签名增加 rel_path 和 divider
-fn read_and_write<W: Write>(
-    path: &Path,
-    rel_path: &str,
-    divider: Divider,
-    reader: Reader,
-    binary: BinaryStrategy,
-    clip: Option<ClipSpec>,
-    logger: &Logger,
-    mut out: W,
-) -> anyhow::Result<bool> {
-    match reader {
-        Reader::Text => write_text(path, rel_path, divider, binary, clip, logger, &mut out),
-        Reader::Textutil => {
-            write_textutil_then_fallback(path, rel_path, divider, binary, clip, logger, &mut out)
-        }
-        Reader::Auto => {
-            if should_use_textutil(path) {
-                write_textutil_then_fallback(
-                    path, rel_path, divider, binary, clip, logger, &mut out,
-                )
-            } else {
-                write_text(path, rel_path, divider, binary, clip, logger, &mut out)
-            }
-        }
-    }
-}
+    /// 仅比较这些扩展的文件
+    #[arg(long)]
+    ext: Option<String>,
 
-// 修改：write_text 负责打印 Header
-fn write_text<W: Write>(
-    path: &Path,
-    rel_path: &str,
-    divider: Divider,
+    /// 最大文件大小（字节），任一侧超过则跳过
+    #[arg(long)]
+    max_size: Option<u64>,
+
+    /// 当检测到可能是二进制文件时的处理策略
+    #[arg(long, value_enum, default_value_t = BinaryStrategy::Skip)]
     binary: BinaryStrategy,
-    clip: Option<ClipSpec>,
-    logger: &Logger,
-    out: &mut W,
-) -> anyhow::Result<bool> {
-    match fs::read(path) {
-        Ok(bytes) => {
-            // 如果判定为二进制，先打印默认 Header（不带编码信息），再处理二进制内容
-            if is_probably_binary(&bytes) && !matches!(binary, BinaryStrategy::Print) {
-                writeln!(out, "{}", divider.header(rel_path, None))?;
-                if handle_binary_content(path, &bytes, binary, logger, out)? {
-                    return Ok(true);
-                }
-            }
 
-            // 文本处理：先探测编码
-            let (s, encoding_name) = decode_content(&bytes);
+    /// 输出分隔符风格
+    #[arg(long, value_enum, default_value_t = Divider::Equals)]
+    divider: Divider,
 
-            // 打印带有编码信息的 Header
-            writeln!(out, "{}", divider.header(rel_path, encoding_name))?;
+    /// 差异上下文行数
+    #[arg(long, short = 'U', value_name = "N", default_value_t = 3)]
+    context: usize,
 
-            if let Some(clip) = clip {
-                write_clipped(&s, clip, out)
-            } else {
-                write!(out, "{}", s)?;
-                Ok(s.ends_with('\n'))
-            }
-        }
-        Err(e) => {
-            // 如果读取都失败了，打印一个默认 Header 然后抛出错误
-            writeln!(out, "{}", divider.header(rel_path, None))?;
-            anyhow::bail!("{}", e);
-        }
-    }
-}
+    /// 不读取 .gitignore / .ignore 等忽略规则（仅对目录生效）
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    no_ignore: bool,
 
-fn write_textutil_then_fallback<W: Write>(
-    path: &Path,
-    rel_path: &str,
-    divider: Divider,
-    binary: BinaryStrategy,
-    clip: Option<ClipSpec>,
-    logger: &Logger,
-    out: &mut W,
-) -> anyhow::Result<bool> {
-    if which::which("textutil").is_ok() {
-        let output = Command::new("textutil")
-            .arg("-convert")
-            .arg("txt")
-            .arg("-stdout")
-            .arg(path)
-            .output();
-        match output {
-            Ok(outp) if outp.status.success() => {
-                // textutil 转换后一定是 UTF-8，所以 Header 不显示特殊编码
-                writeln!(out, "{}", divider.header(rel_path, None))?;
-                
-                if let Some(clip) = clip {
-                    // 依然做一个 decode 以防万一
-                    let (s, _) = decode_content(&outp.stdout);
-                    return write_clipped(&s, clip, out);
-                } else {
-                    out.write_all(&outp.stdout)?;
-                    return Ok(outp.stdout.ends_with(b"\n"));
-                }
-            }
-            Ok(outp) => {
-                logger.warn(&format!(
-                    "警告: textutil 处理失败 ({}), 回退到文本读取: {}",
-                    outp.status,
-                    path.display()
-                ));
-            }
-            Err(e) => {
-                logger.warn(&format!(
-                    "警告: textutil 调用异常 ({}), 回退到文本读取: {}",
-                    e,
-                    path.display()
-                ));
-            }
-        }
-    } else {
-        logger.warn(&format!(
-            "提示: 未检测到 textutil，回退到文本读取。文件: {}",
-            path.display()
-        ));
-    }
-    // 回退
-    write_text(path, rel_path, divider, binary, clip, logger, out)
-}
+    /// 输出详细日志
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    verbose: bool,
 
-fn should_use_textutil(path: &Path) -> bool {
-    let Some(ext) = path
-        .extension()
-        .and_then(OsStr::to_str)
-        .map(|s| s.to_ascii_lowercase())
-    else {
-        return false;
-    };
-    matches!(
-        ext.as_str(),
-        "rtf" | "rtfd" | "doc" | "docx" | "html" | "htm" | "odt" | "webarchive"
-    )
+    /// 安静模式
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    quiet: bool,
 }
 
-// 稍微重命名了一下，因为 handle_binary 现在只负责打印内容体
-fn handle_binary_content<W: Write>(
-    path: &Path,
-    bytes: &[u8],
-    strategy: BinaryStrategy,
-    logger: &Logger,
-    out: &mut W,
-) -> anyhow::Result<bool> {
-    match strategy {
-        BinaryStrategy::Skip => {
-            writeln!(out, "(skipped binary file)")?;
+fn main() -> anyhow::Result<()> {
+    let (args, config_warnings) = parse_args()?;
+
+    if let Some(SubCommand::Unpack(unpack_args)) = &args.command {
+        let logger = Logger::new(unpack_args.verbose, unpack_args.quiet);
+        let results = unpack::run(
+            unpack_args.input.as_deref(),
+            &unpack_args.to,
+            unpack_args.dry_run,
+            &logger,
+        )?;
+        let mut had_error = false;
+        for (path, outcome) in &results {
+            match outcome {
+                unpack::Outcome::Skipped(reason) => println!("skip {} ({})", path, reason),
+                unpack::Outcome::Written(action) => println!("{} {}", action.label(), path),
+                unpack::Outcome::Failed(_) => had_error = true,
+            }
         }
-        BinaryStrategy::Hex => {
-            let encoded = hex::encode(bytes);
-            writeln!(out, "{}", encoded)?;
+        if had_error {
+            std::process::exit(1);
         }
-        BinaryStrategy::Base64 => {
-            let encoded = Base64.encode(bytes);
-            writeln!(out, "{}", encoded)?;
+        return Ok(());
+    }
+
+    if let Some(SubCommand::Config(config_args)) = &args.command {
+        let ConfigAction::Show {
+            profile,
+            trust_config,
+        } = &config_args.action;
+        let config = config::Config::load(profile.as_deref(), *trust_config)?;
+        print!("{}", config.show(&Args::command())?);
+        return Ok(());
+    }
+
+    if let Some(SubCommand::Diff(diff_args)) = &args.command {
+        let logger = Logger::new(diff_args.verbose, diff_args.quiet);
+        let options = diff::DiffOptions {
+            left: diff_args.left.clone(),
+            right: diff_args.right.clone(),
+            ext: diff_args.ext.clone(),
+            max_size: diff_args.max_size,
+            binary: diff_args.binary,
+            divider: diff_args.divider,
+            context: diff_args.context,
+            no_ignore: diff_args.no_ignore,
+        };
+        if diff::run(&options, io::BufWriter::new(io::stdout()), &logger)? {
+            std::process::exit(1);
         }
-        BinaryStrategy::Print => unreachable!(),
+        return Ok(());
     }
-    logger.warn(&format!(
-        "提示: 二进制文件按 {:?} 处理: {}",
-        strategy,
-        path.display()
-    ));
-    Ok(true)
-}
-
-fn is_probably_binary(bytes: &[u8]) -> bool {
-    bytes.contains(&0)
-}
 
-fn write_clipped<W: Write>(
-    content: &str,
-    clip: ClipSpec,
-    out: &mut W,
-) -> anyhow::Result<bool> {
-    // ... (write_clipped 内容保持不变) ...
-    // 为了节省篇幅，省略具体实现，直接复制你原本的逻辑即可
-    let lines: Vec<&str> = content.split_inclusive('\n').collect();
-    let total = lines.len();
-    if total == 0 { return Ok(false); }
-    let ClipSpec { head, tail } = clip;
-    if head + tail >= total {
-        write!(out, "{}", content)?;
-        return Ok(content.ends_with('\n'));
-    }
-    let mut ended_with_newline = false;
-    let head_count = head.min(total);
-    for l in &lines[..head_count] {
-        write!(out, "{}", l)?;
-        ended_with_newline = l.ends_with('\n');
-    }
-    let start_tail = total.saturating_sub(tail);
-    let skipped = if start_tail > head_count { start_tail - head_count } else { 0 };
-    if skipped > 0 {
-        writeln!(out, "... (snipped {} lines) ...", skipped)?;
-        ended_with_newline = true;
-    }
-    for l in &lines[start_tail..] {
-        write!(out, "{}", l)?;
-        ended_with_newline = l.ends_with('\n');
+    let logger = Logger::new(args.verbose, args.quiet);
+    for warning in &config_warnings {
+        logger.warn(warning);
     }
-    Ok(ended_with_newline)
-}
 
-fn escape_xml_attr(s: &str) -> String {
-    s.replace('&', "&amp;")
-        .replace('"', "&quot;")
-        .replace('<', "&lt;")
-        .replace('>', "&gt;")
-}
+    let regex = |flag: &str, pattern: &Option<String>| {
+        pattern
+            .as_deref()
+            .map(|p| Regex::new(p).map_err(|e| anyhow::anyhow!("invalid {} regex: {}", flag, e)))
+            .transpose()
+    };
+    let options = PrintOptions {
+        items: args.items,
+        files_from: args.files_from,
+        null_separated: args.null_separated,
+        stdin_name: args.stdin_name,
+        reader: args.reader,
+        reader_cmd: args.reader_cmd,
+        reader_timeout: Duration::from_secs(args.reader_timeout),
+        notebook_outputs: args.notebook_outputs,
+        ext: args.ext,
+        relative_from: args.relative_from,
+        max_size: args.max_size,
+        binary: args.binary,
+        sort: args.sort,
+        follow_links: args.follow_links && !args.no_follow_links,
+        jobs: args.jobs,
+        git_tracked: args.git_tracked,
+        git_staged: args.git_staged,
+        git_modified: args.git_modified,
+        git_changed_since: args.git_changed_since,
+        rev: args.rev,
+        exclude: args.exclude,
+        no_ignore: args.no_ignore,
+        contains: regex("--contains", &args.contains)?,
+        not_contains: regex("--not-contains", &args.not_contains)?,
+        grep: regex("--grep", &args.grep)?,
+        context: args.context,
+        clip: args.clip.as_deref().map(parse_clip_spec).transpose()?,
+        line_numbers: args.line_numbers,
+        line_number_width: args.line_number_width,
+        line_number_separator: args.line_number_separator,
+        divider: args.divider,
+        tokenizer: args.tokenizer,
+        max_tokens: args.max_tokens,
+        budget_mode: args.budget_mode,
+        split_bytes: args.split_bytes,
+        split_tokens: args.split_tokens,
+        output_dir: args.output_dir,
+        tree: args.tree,
+        toc: args.toc,
+        summary: args.summary,
+        format: args.format,
+    };
+    match print::run(&options, io::BufWriter::new(io::stdout()), &logger)? {
+        Status::Done => {}
+        Status::ReadErrors => std::process::exit(1),
+        Status::NoFiles | Status::NoContentMatch | Status::BudgetExhausted => std::process::exit(2),
+    }
 
-// ... (Logger struct and impl 保持不变) ...
-#[derive(Clone)]
-struct Logger {
-    verbose: bool,
-    quiet: bool,
+    Ok(())
 }
 
-impl Logger {
-    fn new(verbose: bool, quiet: bool) -> Self {
-        Self { verbose, quiet }
-    }
-    fn info(&self, msg: &str) {
-        if self.quiet || !self.verbose { return; }
-        eprintln!("{}", msg);
-    }
-    fn warn(&self, msg: &str) {
-        if self.quiet { return; }
-        eprintln!("{}", msg);
+// 命令行参数之前插入配置文件中的设置；子命令与 --no-config 时不读取配置。
+// 同时返回被忽略的项目配置设置的提示，由 main 按 --quiet 输出
+fn parse_args() -> anyhow::Result<(Args, Vec<String>)> {
+    let raw: Vec<OsString> = std::env::args_os().collect();
+    let cmd = Args::command();
+    // 先宽松解析一遍，得到 --profile 与命令行上已给出的选项
+    let Ok(cli) = cmd.clone().ignore_errors(true).try_get_matches_from(&raw) else {
+        return Ok((Args::parse_from(raw), Vec::new()));
+    };
+    // 子命令名总是调用子命令；同名的文件或目录要写成 `./diff` 或放在 `--` 之后
+    if let Some(name) = cli.subcommand_name() {
+        return match Args::try_parse_from(&raw) {
+            Ok(args) => Ok((args, Vec::new())),
+            Err(e) => {
+                if Path::new(name).exists() {
+                    eprintln!(
+                        "提示: {} 是子命令；要输出同名的文件或目录请写成 ./{} 或放在 -- 之后",
+                        name, name
+                    );
+                }
+                e.exit()
+            }
+        };
     }
-    fn error(&self, msg: &str) {
-        eprintln!("{}", msg);
+    if cli.get_flag("no_config") {
+        return Ok((Args::parse_from(raw), Vec::new()));
     }
+    let profile = cli.get_one::<String>("profile");
+    let config = config::Config::load(profile.map(String::as_str), cli.get_flag("trust_config"))?;
+    let mut argv: Vec<OsString> = raw.iter().take(1).cloned().collect();
+    argv.extend(config.to_args(&cmd, &cli)?.into_iter().map(OsString::from));
+    argv.extend(raw.into_iter().skip(1));
+    Ok((Args::parse_from(argv), config.warnings()))
 }
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::path::Path;
-
-    #[test]
-    fn ext_match_is_case_insensitive() {
-        assert!(ext_match(Path::new("foo.rs"), "rs,md"));
-        assert!(ext_match(Path::new("foo.RS"), "rs,md"));
-        assert!(!ext_match(Path::new("foo.txt"), "rs,md"));
-        assert!(!ext_match(Path::new("foo"), "rs"));
-    }
-
-    #[test]
-    fn should_use_textutil_recognizes_known_extensions() {
-        assert!(should_use_textutil(Path::new("doc.DOCX")));
-        assert!(should_use_textutil(Path::new("note.html")));
-        assert!(!should_use_textutil(Path::new("note.txt")));
-        assert!(!should_use_textutil(Path::new("noext")));
-    }
-
-    #[test]
-    fn rel_display_strips_current_dir_prefix() {
-        let cwd = std::env::current_dir().expect("cwd");
-        let path = cwd.join("foo").join("bar.txt");
-        assert_eq!(rel_display(&path, None), "foo/bar.txt");
-    }
-
-    #[test]
-    fn strip_dot_slash_removes_prefix() {
-        let path = Path::new("./nested/value");
-        assert_eq!(strip_dot_slash(path), "nested/value");
-    }
-
-    #[test]
-    fn rel_display_uses_custom_base() {
-        let base = std::env::temp_dir().join("rel-display-base");
-        let path = base.join("project/file.txt");
-        assert_eq!(rel_display(&path, Some(&base)), "project/file.txt");
-    }
-
-    #[test]
-    fn file_len_handles_missing_file() {
-        let path = Path::new("unlikely_missing_file");
-        assert!(file_len(path).unwrap().is_none());
-    }
-
-    #[test]
-    fn binary_detection_by_null_byte() {
-        assert!(is_probably_binary(b"abc\0def"));
-        assert!(!is_probably_binary(b"plain text"));
-    }
-
-    #[test]
-    fn write_clipped_inserts_snipped_line() {
-        let content = "line1\nline2\nline3\nline4\nline5\nline6\n";
-        let clip = ClipSpec { head: 2, tail: 2 };
-        let mut buf = Vec::new();
-        let ended = write_clipped(content, clip, &mut buf).unwrap();
-        let s = String::from_utf8(buf).unwrap();
-
-        assert!(s.contains("line1\nline2\n"));
-        assert!(s.contains("line5\nline6\n"));
-        assert!(s.contains("... (snipped 2 lines) ..."));
-        assert!(ended);
-    }
-}
\ No newline at end of file
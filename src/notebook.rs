@@ -0,0 +1,137 @@
+//! Jupyter 笔记本（.ipynb）：按顺序把单元格渲染为 `# %% [markdown]` / `# %%` 段落。
+//!
+//! 文本输出（stdout/stderr、`text/plain` 结果、异常）按需以注释形式附在代码单元之后并截断，
+//! 图片等其余输出一律省略。
+
+use serde_json::Value;
+use std::ffi::OsStr;
+use std::path::Path;
+
+pub(crate) fn is_notebook(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("ipynb"))
+}
+
+/// 渲染笔记本；`outputs` 为每个文本输出保留的行数，None 时不输出
+pub(crate) fn render(bytes: &[u8], outputs: Option<usize>) -> anyhow::Result<String> {
+    let notebook: Value = serde_json::from_slice(bytes)?;
+    let Some(cells) = notebook.get("cells").and_then(Value::as_array) else {
+        anyhow::bail!("not an nbformat 4 notebook (no cells)");
+    };
+    let mut out = String::new();
+    for cell in cells {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        let kind = cell
+            .get("cell_type")
+            .and_then(Value::as_str)
+            .unwrap_or("code");
+        match kind {
+            "code" => out.push_str("# %%\n"),
+            other => out.push_str(&format!("# %% [{}]\n", other)),
+        }
+        let source = joined(cell.get("source"));
+        out.push_str(&source);
+        if !source.is_empty() && !source.ends_with('\n') {
+            out.push('\n');
+        }
+        if let (Some(limit), "code") = (outputs, kind) {
+            let count = cell.get("execution_count").and_then(Value::as_u64);
+            for output in cell
+                .get("outputs")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                if let Some(text) = output_text(output) {
+                    push_output(&mut out, count, &text, limit);
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+// 单元格的 source 与输出文本可以是字符串，也可以是按行拆开的字符串数组
+fn joined(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(parts)) => parts.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+// 输出中的文本部分；图片、HTML 等没有 text/plain 的输出返回 None
+fn output_text(output: &Value) -> Option<String> {
+    let text = match output.get("output_type").and_then(Value::as_str)? {
+        "stream" => joined(output.get("text")),
+        "execute_result" | "display_data" => joined(output.get("data")?.get("text/plain")),
+        "error" => {
+            let field = |name| output.get(name).and_then(Value::as_str).unwrap_or_default();
+            format!("{}: {}", field("ename"), field("evalue"))
+        }
+        _ => return None,
+    };
+    (!text.trim().is_empty()).then_some(text)
+}
+
+// 以注释形式追加一个输出，超出 limit 的行用 snipped 标记代替
+fn push_output(out: &mut String, count: Option<u64>, text: &str, limit: usize) {
+    match count {
+        Some(n) => out.push_str(&format!("# Out[{}]:\n", n)),
+        None => out.push_str("# Out:\n"),
+    }
+    let lines: Vec<&str> = text.lines().collect();
+    for line in lines.iter().take(limit) {
+        if line.is_empty() {
+            out.push_str("#\n");
+        } else {
+            out.push_str(&format!("# {}\n", line));
+        }
+    }
+    if lines.len() > limit {
+        out.push_str(&format!(
+            "# ... (snipped {} lines) ...\n",
+            lines.len() - limit
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_render_in_order_with_truncated_text_outputs() {
+        let notebook = r##"{
+            "nbformat": 4,
+            "cells": [
+                {"cell_type": "markdown", "source": ["# Title\n", "Intro"]},
+                {"cell_type": "code", "execution_count": 3, "source": "for i in range(3):\n    print(i)",
+                 "outputs": [
+                    {"output_type": "stream", "name": "stdout", "text": ["0\n", "1\n", "2\n"]},
+                    {"output_type": "display_data", "data": {"image/png": "iVBORw0KGgo="}},
+                    {"output_type": "error", "ename": "ValueError", "evalue": "bad", "traceback": []}
+                 ]},
+                {"cell_type": "raw", "source": ""}
+            ]
+        }"##;
+        assert_eq!(
+            render(notebook.as_bytes(), None).unwrap(),
+            "# %% [markdown]\n# Title\nIntro\n\n\
+             # %%\nfor i in range(3):\n    print(i)\n\n\
+             # %% [raw]\n"
+        );
+        assert_eq!(
+            render(notebook.as_bytes(), Some(2)).unwrap(),
+            "# %% [markdown]\n# Title\nIntro\n\n\
+             # %%\nfor i in range(3):\n    print(i)\n\
+             # Out[3]:\n# 0\n# 1\n# ... (snipped 1 lines) ...\n\
+             # Out[3]:\n# ValueError: bad\n\n\
+             # %% [raw]\n"
+        );
+        assert!(render(b"{\"worksheets\": []}", None).is_err());
+    }
+}
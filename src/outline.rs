@@ -0,0 +1,174 @@
+//! `--tree` / `--toc`：在文件块之前输出目录树与目录（各文件块在输出中的位置）。
+
+use crate::render::{escape_xml_attr, Divider, Renderer};
+use std::collections::BTreeMap;
+
+/// 树与目录中的一个文件
+pub struct OutlineEntry {
+    /// 与 Header 一致的显示路径
+    pub path: String,
+    pub size: Option<u64>,
+    pub detail: Detail,
+    /// 文件块渲染后的内容
+    pub block: Vec<u8>,
+}
+
+/// 树中附在大小后面的说明
+pub enum Detail {
+    Lines(usize),
+    Binary,
+    Skipped,
+    Failed,
+}
+
+impl OutlineEntry {
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(size) = self.size {
+            parts.push(format!("{} bytes", size));
+        }
+        parts.push(match self.detail {
+            Detail::Lines(1) => "1 line".to_string(),
+            Detail::Lines(n) => format!("{} lines", n),
+            Detail::Binary => "binary".to_string(),
+            Detail::Skipped => "skipped".to_string(),
+            Detail::Failed => "unreadable".to_string(),
+        });
+        parts.join(", ")
+    }
+}
+
+#[derive(Default)]
+struct Node<'a> {
+    children: BTreeMap<&'a str, Node<'a>>,
+    file: Option<&'a OutlineEntry>,
+}
+
+/// ASCII 目录树，按路径组织（与 --sort 无关）
+pub fn render_tree(entries: &[OutlineEntry], divider: Divider) -> String {
+    let mut root = Node::default();
+    for entry in entries {
+        let mut node = &mut root;
+        for part in entry.path.split('/').filter(|p| !p.is_empty()) {
+            node = node.children.entry(part).or_default();
+        }
+        node.file = Some(entry);
+    }
+
+    let mut body = String::from(".\n");
+    write_children(&root, "", &mut body);
+    if let Divider::XmlTag = divider {
+        body = escape_xml_attr(&body);
+    }
+    divider.section("tree", &body)
+}
+
+fn write_children(node: &Node, prefix: &str, out: &mut String) {
+    let count = node.children.len();
+    for (i, (name, child)) in node.children.iter().enumerate() {
+        let last = i + 1 == count;
+        let (branch, indent) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        out.push_str(prefix);
+        out.push_str(branch);
+        match child.file {
+            Some(entry) if child.children.is_empty() => {
+                out.push_str(&format!("{} ({})\n", name, entry.describe()));
+            }
+            _ => out.push_str(&format!("{}/\n", name)),
+        }
+        write_children(child, &format!("{}{}", prefix, indent), out);
+    }
+}
+
+/// 目录：每个文件块 Header 所在的字节偏移（从 0 开始）与行号（从 1 开始）。
+/// 偏移包含目录自身，因此反复渲染直到长度稳定
+pub fn render_toc(entries: &[OutlineEntry], preceding: &str, divider: Divider) -> String {
+    let mut toc = String::new();
+    for _ in 0..8 {
+        let mut offset = preceding.len() + toc.len();
+        let mut line = 1 + preceding.matches('\n').count() + toc.matches('\n').count();
+        let mut rows = Vec::with_capacity(entries.len());
+        for entry in entries {
+            rows.push((entry.path.as_str(), offset, line));
+            offset += entry.block.len();
+            line += entry.block.iter().filter(|&&b| b == b'\n').count();
+        }
+        let next = toc_section(&rows, divider);
+        if next.len() == toc.len() {
+            return next;
+        }
+        toc = next;
+    }
+    toc
+}
+
+fn toc_section(rows: &[(&str, usize, usize)], divider: Divider) -> String {
+    let mut body = String::new();
+    for (path, offset, line) in rows {
+        match divider {
+            Divider::XmlTag => body.push_str(&format!(
+                "<entry path=\"{}\" byte=\"{}\" line=\"{}\"/>\n",
+                escape_xml_attr(path),
+                offset,
+                line
+            )),
+            _ => body.push_str(&format!("{}: byte {}, line {}\n", path, offset, line)),
+        }
+    }
+    divider.section("toc", &body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, detail: Detail, block: &str) -> OutlineEntry {
+        OutlineEntry {
+            path: path.to_string(),
+            size: Some(block.len() as u64),
+            detail,
+            block: block.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn tree_groups_files_by_directory() {
+        let entries = vec![
+            entry("src/main.rs", Detail::Lines(2), "xx"),
+            entry("README.md", Detail::Lines(1), "y"),
+            entry("src/bin/tool.rs", Detail::Binary, "zzz"),
+        ];
+        assert_eq!(
+            render_tree(&entries, Divider::Equals),
+            "===tree===\n.\n\
+             ├── README.md (1 bytes, 1 line)\n\
+             └── src/\n    \
+                 ├── bin/\n    \
+                 │   └── tool.rs (3 bytes, binary)\n    \
+                 └── main.rs (2 bytes, 2 lines)\n\
+             ===end of tree===\n"
+        );
+    }
+
+    #[test]
+    fn toc_offsets_account_for_the_toc_itself() {
+        let entries = vec![
+            entry("a", Detail::Lines(1), "===a===\nA\n===end of 'a'===\n"),
+            entry("b", Detail::Lines(1), "===b===\nB\n===end of 'b'===\n"),
+        ];
+        let toc = render_toc(&entries, "", Divider::Equals);
+        let first = toc.len();
+        let second = first + entries[0].block.len();
+        assert_eq!(
+            toc,
+            format!(
+                "===toc===\na: byte {}, line 5\nb: byte {}, line 8\n===end of toc===\n",
+                first, second
+            )
+        );
+    }
+}
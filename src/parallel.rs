@@ -0,0 +1,187 @@
+//! `--jobs`：多个线程并行读取、解码与渲染文件，主线程按排序顺序逐个消费结果。
+//!
+//! 工作线程最多领先消费者固定数量的文件，重排缓冲因此有上限。
+
+use std::collections::BTreeMap;
+use std::ops::ControlFlow;
+use std::sync::{mpsc, Condvar, Mutex};
+use std::thread;
+
+/// 每个线程可以领先消费者的文件数
+const WINDOW_PER_JOB: usize = 4;
+
+/// 对每个元素调用 `work`，并按原顺序把结果交给 `consume`，`consume` 返回 Break 时停止。
+/// `jobs` 不超过 1 时在当前线程逐个执行
+pub fn for_each_ordered<T, R, W, C>(
+    items: &[T],
+    jobs: usize,
+    work: W,
+    mut consume: C,
+) -> anyhow::Result<()>
+where
+    T: Sync,
+    R: Send,
+    W: Fn(usize, &T) -> R + Sync,
+    C: FnMut(usize, R) -> anyhow::Result<ControlFlow<()>>,
+{
+    if jobs <= 1 || items.len() <= 1 {
+        for (index, item) in items.iter().enumerate() {
+            if consume(index, work(index, item))?.is_break() {
+                break;
+            }
+        }
+        return Ok(());
+    }
+
+    let gate = Gate::new(items.len(), jobs * WINDOW_PER_JOB);
+    thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..jobs.min(items.len()) {
+            let tx = tx.clone();
+            let (gate, work) = (&gate, &work);
+            scope.spawn(move || {
+                let _guard = StopOnPanic(gate);
+                while let Some(index) = gate.claim() {
+                    if tx.send((index, work(index, &items[index]))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(tx);
+
+        let result = consume_in_order(&rx, items.len(), &gate, &mut consume);
+        // 提前结束时唤醒等待中的线程；rx 随后释放，正在工作的线程发送失败后退出
+        gate.stop();
+        result
+    })
+}
+
+fn consume_in_order<R, C>(
+    rx: &mpsc::Receiver<(usize, R)>,
+    len: usize,
+    gate: &Gate,
+    consume: &mut C,
+) -> anyhow::Result<()>
+where
+    C: FnMut(usize, R) -> anyhow::Result<ControlFlow<()>>,
+{
+    let mut pending = BTreeMap::new();
+    for index in 0..len {
+        let result = loop {
+            if let Some(result) = pending.remove(&index) {
+                break result;
+            }
+            let (i, result) = rx
+                .recv()
+                .map_err(|_| anyhow::anyhow!("worker thread exited unexpectedly"))?;
+            pending.insert(i, result);
+        };
+        gate.advance();
+        if consume(index, result)?.is_break() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+struct GateState {
+    /// 下一个待领取的序号
+    next: usize,
+    /// 已交给消费者的结果数
+    consumed: usize,
+    stopped: bool,
+}
+
+/// 控制领取进度：序号不能超过 consumed + window
+struct Gate {
+    len: usize,
+    window: usize,
+    state: Mutex<GateState>,
+    cond: Condvar,
+}
+
+impl Gate {
+    fn new(len: usize, window: usize) -> Self {
+        Self {
+            len,
+            window,
+            state: Mutex::new(GateState {
+                next: 0,
+                consumed: 0,
+                stopped: false,
+            }),
+            cond: Condvar::new(),
+        }
+    }
+
+    fn claim(&self) -> Option<usize> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if state.stopped || state.next >= self.len {
+                return None;
+            }
+            if state.next < state.consumed + self.window {
+                state.next += 1;
+                return Some(state.next - 1);
+            }
+            state = self.cond.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn advance(&self) {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .consumed += 1;
+        self.cond.notify_all();
+    }
+
+    fn stop(&self) {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).stopped = true;
+        self.cond.notify_all();
+    }
+}
+
+// 工作线程 panic 时停止领取，其余线程退出后消费者的 recv 才会返回错误
+struct StopOnPanic<'a>(&'a Gate);
+
+impl Drop for StopOnPanic<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.stop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn results_are_consumed_in_order_and_break_stops_early() {
+        let items: Vec<u64> = (0..50).collect();
+        let mut seen = Vec::new();
+        for_each_ordered(
+            &items,
+            4,
+            |index, item| {
+                // 前面的元素更慢，结果会乱序到达
+                thread::sleep(Duration::from_millis((50 - item) % 7));
+                index * 10
+            },
+            |index, result| {
+                assert_eq!(result, index * 10);
+                seen.push(index);
+                Ok(if index == 29 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                })
+            },
+        )
+        .unwrap();
+        assert_eq!(seen, (0..30).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,919 @@
+//! 打印流程：收集条目、并行读取与渲染，按顺序应用 token 预算、内容过滤、分卷、目录与汇总后
+//! 写到给定的输出。命令行工具只负责把参数转换为 [`PrintOptions`] 并按 [`Status`] 设置退出码。
+
+use crate::collect::{
+    parse_file_list, range_key, resolve_relative_base, sort_entries, split_line_range, Collector,
+    FileEntry, SortKey,
+};
+use crate::external::{self, ExternalReader};
+use crate::read::{
+    load_file, BinaryStrategy, FileBody, FileOutcome, LoadedFile, ReadOptions, Reader, ReaderKind,
+    TextReader,
+};
+use crate::render::{
+    clamp_ranges, merge_ranges, write_block, BodyStyle, BudgetClip, ClipSpec, Divider, LineNumbers,
+    LineRange,
+};
+use crate::tokens::{self, TokenCounter, Tokenizer};
+use crate::{filter, git, json, outline, parallel, split, summary, Logger};
+use clap::ValueEnum;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// `--summary` 的输出位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SummaryTarget {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// 按 --divider 风格输出文本块（默认）
+    Text,
+    /// 输出一个 JSON 数组，每个文件一个对象
+    Json,
+    /// JSON Lines：每行一个对象
+    Jsonl,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BudgetMode {
+    /// 放不下的文件及其后的文件全部省略
+    Stop,
+    /// 截取放得下的前若干行，其后的文件省略
+    Clip,
+}
+
+/// 打印的全部设置，与命令行选项一一对应；`Default` 与命令行的默认值相同
+#[derive(Debug, Clone)]
+pub struct PrintOptions {
+    /// 以逗号分隔的模式、目录、`path:120-180` 行范围、`!pattern` 排除与 `-`（标准输入）
+    pub items: Vec<String>,
+    /// 路径列表文件（`-` 为标准输入）；路径按原样使用
+    pub files_from: Option<String>,
+    /// 路径列表以 NUL 分隔
+    pub null_separated: bool,
+    /// 条目 `-` 在 Header 中显示的名称
+    pub stdin_name: String,
+    pub reader: ReaderKind,
+    /// `EXT=COMMAND` 形式的外部读取命令，只在 `ReaderKind::Auto` 时生效
+    pub reader_cmd: Vec<String>,
+    pub reader_timeout: Duration,
+    pub notebook_outputs: Option<usize>,
+    pub ext: Option<String>,
+    pub relative_from: Option<PathBuf>,
+    pub max_size: Option<u64>,
+    pub binary: BinaryStrategy,
+    pub sort: SortKey,
+    pub follow_links: bool,
+    /// 并行线程数，0 表示按 CPU 核数
+    pub jobs: usize,
+    pub git_tracked: bool,
+    pub git_staged: bool,
+    pub git_modified: bool,
+    pub git_changed_since: Option<String>,
+    pub rev: Option<String>,
+    pub exclude: Vec<String>,
+    pub no_ignore: bool,
+    pub contains: Option<Regex>,
+    pub not_contains: Option<Regex>,
+    pub grep: Option<Regex>,
+    /// `grep` 匹配行前后各保留的行数
+    pub context: usize,
+    pub clip: Option<ClipSpec>,
+    pub line_numbers: bool,
+    pub line_number_width: Option<usize>,
+    pub line_number_separator: String,
+    pub divider: Divider,
+    pub tokenizer: Tokenizer,
+    pub max_tokens: Option<usize>,
+    pub budget_mode: BudgetMode,
+    pub split_bytes: Option<usize>,
+    pub split_tokens: Option<usize>,
+    /// 分卷与 index.txt 的输出目录
+    pub output_dir: Option<PathBuf>,
+    pub tree: bool,
+    pub toc: bool,
+    pub summary: Option<SummaryTarget>,
+    pub format: OutputFormat,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            items: Vec::new(),
+            files_from: None,
+            null_separated: false,
+            stdin_name: "stdin".to_string(),
+            reader: ReaderKind::Text,
+            reader_cmd: Vec::new(),
+            reader_timeout: Duration::from_secs(30),
+            notebook_outputs: None,
+            ext: None,
+            relative_from: None,
+            max_size: None,
+            binary: BinaryStrategy::Skip,
+            sort: SortKey::Name,
+            follow_links: true,
+            jobs: 1,
+            git_tracked: false,
+            git_staged: false,
+            git_modified: false,
+            git_changed_since: None,
+            rev: None,
+            exclude: Vec::new(),
+            no_ignore: false,
+            contains: None,
+            not_contains: None,
+            grep: None,
+            context: 0,
+            clip: None,
+            line_numbers: false,
+            line_number_width: None,
+            line_number_separator: ": ".to_string(),
+            divider: Divider::Equals,
+            tokenizer: Tokenizer::Cl100k,
+            max_tokens: None,
+            budget_mode: BudgetMode::Stop,
+            split_bytes: None,
+            split_tokens: None,
+            output_dir: None,
+            tree: false,
+            toc: false,
+            summary: None,
+            format: OutputFormat::Text,
+        }
+    }
+}
+
+/// [`run`] 的结果，命令行据此设置退出码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// 全部输出
+    Done,
+    /// 有文件读取失败；其余文件照常输出
+    ReadErrors,
+    /// 没有条目，或条目没有匹配到任何文件；没有输出
+    NoFiles,
+    /// 给出了内容过滤条件，但没有文件的内容匹配
+    NoContentMatch,
+    /// stop 模式下第一个文件就超过 --max-tokens，没有输出任何文件块
+    BudgetExhausted,
+}
+
+/// 按 `options` 收集并输出文件到 `out`；日志与 `--summary stderr` 写到标准错误
+pub fn run<W: Write>(
+    options: &PrintOptions,
+    mut out: W,
+    logger: &Logger,
+) -> anyhow::Result<Status> {
+    let line_numbers = options.line_numbers.then_some(LineNumbers {
+        width: options.line_number_width,
+        separator: &options.line_number_separator,
+    });
+    let body_style = BodyStyle {
+        clip: options.clip,
+        ranges: None,
+        numbers: line_numbers,
+        budget: None,
+    };
+
+    let relative_base = resolve_relative_base(options.relative_from.as_ref())?;
+
+    let content_filter = filter::ContentFilter {
+        contains: options.contains.clone(),
+        not_contains: options.not_contains.clone(),
+        grep: options.grep.clone().map(|re| (re, options.context)),
+    };
+    let content_filter = (content_filter.contains.is_some()
+        || content_filter.not_contains.is_some()
+        || content_filter.grep.is_some())
+    .then_some(content_filter);
+
+    let mut tokens: Vec<String> = Vec::new();
+    let mut exclude_patterns: Vec<String> = options.exclude.clone();
+    // 按规范化路径记录 `path:120-180` 条目的行范围
+    let mut line_ranges: HashMap<PathBuf, Vec<LineRange>> = HashMap::new();
+    let mut range_items: Vec<(PathBuf, String)> = Vec::new();
+    for it in options.items.iter() {
+        for piece in it.split(',') {
+            let s = piece.trim();
+            if let Some(pattern) = s.strip_prefix('!') {
+                // `!pattern` 与 --exclude 等价
+                if !pattern.trim().is_empty() {
+                    exclude_patterns.push(pattern.trim().to_string());
+                }
+            } else if let Some((file, range)) =
+                split_line_range(s).filter(|_| !Path::new(s).exists())
+            {
+                let key = range_key(Path::new(file));
+                line_ranges.entry(key.clone()).or_default().push(range);
+                range_items.push((key, s.to_string()));
+                tokens.push(file.to_string());
+            } else if !s.is_empty() {
+                tokens.push(s.to_string());
+            }
+        }
+    }
+    for ranges in line_ranges.values_mut() {
+        merge_ranges(ranges);
+    }
+
+    // 列表中的路径不做拆分、排除与通配展开
+    let listed = match options.files_from.as_deref() {
+        Some(source) => {
+            let bytes = if source == "-" {
+                let mut buf = Vec::new();
+                io::stdin().read_to_end(&mut buf)?;
+                buf
+            } else {
+                fs::read(source)
+                    .map_err(|e| anyhow::anyhow!("cannot read --files-from {}: {}", source, e))?
+            };
+            parse_file_list(&bytes, options.null_separated)?
+        }
+        None => Vec::new(),
+    };
+
+    // 条目 `-` 读取标准输入的内容
+    let stdin_item = tokens.iter().any(|token| token == "-");
+    tokens.retain(|token| token != "-");
+    if stdin_item && options.files_from.as_deref() == Some("-") {
+        anyhow::bail!("'-' cannot be used together with --files-from -");
+    }
+    let stdin_content = if stdin_item {
+        let mut buf = Vec::new();
+        io::stdin().read_to_end(&mut buf)?;
+        Some(buf)
+    } else {
+        None
+    };
+
+    let mut collector = Collector::new()
+        .ext(options.ext.as_deref())
+        .follow_links(options.follow_links)
+        .respect_ignore(!options.no_ignore)
+        .max_size(options.max_size)
+        .exclude(&exclude_patterns)?;
+    let rev = options.rev.as_deref();
+    if let Some(rev) = rev {
+        collector = collector.rev(rev)?;
+    }
+
+    let git_selectors = git::GitSelectors {
+        tracked: options.git_tracked,
+        staged: options.git_staged,
+        modified: options.git_modified,
+        changed_since: options.git_changed_since.as_deref(),
+    };
+
+    if tokens.is_empty() && listed.is_empty() && !stdin_item && git_selectors.is_empty() {
+        logger.warn("（未匹配到任何文件）");
+        return Ok(Status::NoFiles);
+    }
+
+    for token in &tokens {
+        if let Err(err) = collector.add(token) {
+            logger.warn(&format!("模式无效或没有匹配: {err}"));
+        }
+    }
+    for path in &listed {
+        match collector.add_path(Path::new(path)) {
+            Ok(true) => {}
+            Ok(false) => logger.warn(&format!("文件不存在: {path}")),
+            Err(err) => logger.warn(&format!("模式无效或没有匹配: {err}")),
+        }
+    }
+    if !git_selectors.is_empty() {
+        collector.select_git(&git_selectors)?;
+    }
+
+    let mut entries = collector.finish();
+    if entries.is_empty() && stdin_content.is_none() {
+        logger.warn("（未匹配到任何文件）");
+        return Ok(Status::NoFiles);
+    }
+    if let Some(content) = &stdin_content {
+        entries.push(FileEntry {
+            path: PathBuf::from(&options.stdin_name),
+            len: Some(content.len() as u64),
+            mtime: None,
+            stdin: true,
+            member: None,
+        });
+    }
+
+    sort_entries(&mut entries, options.sort);
+
+    // 行范围只作用于单个文件；目录、模式等条目上的行范围没有对应的文件
+    if !range_items.is_empty() {
+        let keys: HashSet<PathBuf> = entries
+            .iter()
+            .map(|entry| match entry.stdin {
+                true => range_key(Path::new("-")),
+                false => range_key(&entry.path),
+            })
+            .collect();
+        for (key, item) in &range_items {
+            if !keys.contains(key) {
+                logger.warn(&format!(
+                    "提示: 行范围只作用于单个文件，忽略 {} 的行范围",
+                    item
+                ));
+            }
+        }
+    }
+
+    let mut reader = options.reader.reader();
+    if !options.reader_cmd.is_empty() {
+        if let ReaderKind::Auto = options.reader {
+            let mut external = ExternalReader::new(reader, options.reader_timeout);
+            for spec in &options.reader_cmd {
+                let (ext, command) = external::parse_spec(spec)?;
+                external.add(ext, command)?;
+            }
+            reader = Box::new(external);
+        } else {
+            logger.info("提示: --reader-cmd 只在 --reader auto 时生效");
+        }
+    }
+    let read_opts = ReadOptions {
+        binary: options.binary,
+        rev,
+        notebook_outputs: options.notebook_outputs,
+        logger,
+    };
+
+    let mut had_error = false;
+    let mut json_writer = match options.format {
+        OutputFormat::Text => None,
+        OutputFormat::Json => Some(json::JsonWriter::new(false)),
+        OutputFormat::Jsonl => Some(json::JsonWriter::new(true)),
+    };
+
+    // 只有需要时才加载 BPE 词表
+    let counter = (logger.is_verbose()
+        || options.max_tokens.is_some()
+        || options.split_tokens.is_some()
+        || options.summary.is_some())
+    .then(|| TokenCounter::new(options.tokenizer));
+
+    let mut splitter = match (
+        options.split_bytes.or(options.split_tokens),
+        &options.output_dir,
+    ) {
+        (Some(_), _) if options.format == OutputFormat::Json => {
+            anyhow::bail!("--split-bytes/--split-tokens cannot be used with --format json")
+        }
+        (Some(limit), Some(dir)) => {
+            let extension = match options.format {
+                OutputFormat::Jsonl => "jsonl",
+                _ => "txt",
+            };
+            Some(split::Splitter::new(dir, limit, extension)?)
+        }
+        _ => None,
+    };
+    if options.summary == Some(SummaryTarget::Stdout) && options.format != OutputFormat::Text {
+        anyhow::bail!("--summary stdout requires --format text; use --summary stderr");
+    }
+    let mut summary = summary::Summary::default();
+
+    if (options.tree || options.toc) && options.format != OutputFormat::Text {
+        anyhow::bail!("--tree/--toc can only be used with --format text");
+    }
+    // 目录中的偏移依赖全部文件块，先缓存再输出
+    let mut outline: Option<Vec<outline::OutlineEntry>> =
+        (options.tree || options.toc).then(Vec::new);
+
+    // 无需分卷或目录缓存时，流式正文在主线程中直接写到输出
+    let direct_stream =
+        options.format == OutputFormat::Text && splitter.is_none() && outline.is_none();
+
+    // 分卷上限的度量口径
+    let measure = |s: &str| match (&counter, options.split_tokens) {
+        (Some(counter), Some(_)) => counter.count(s),
+        _ => s.len(),
+    };
+    let mut used_tokens = 0usize;
+    // 预算用完后不再处理的其余条目数；其中可能有本会被内容过滤等跳过的文件
+    let mut unreached = 0usize;
+    let mut budget_exhausted = false;
+    // 通过内容过滤的文件数
+    let mut matched = 0usize;
+
+    // 分隔符文本的文件块，或编码好的 JSON 记录（数组分隔由 JsonWriter 按顺序补上）
+    let render = |rel: &str, entry: &FileEntry, outcome: &FileOutcome, style: BodyStyle| {
+        let mut rendered = Vec::new();
+        match options.format {
+            OutputFormat::Text => {
+                write_block(&mut rendered, &options.divider, rel, outcome, style, rev)?;
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                rendered = json::encode(&json::record(rel, entry, outcome, style, rev)?)?;
+            }
+        }
+        anyhow::Ok(rendered)
+    };
+
+    // 读取、解码、计数与渲染可以在工作线程中进行
+    let prepare = |_index: usize, entry: &FileEntry| -> anyhow::Result<Option<Prepared>> {
+        let path = &entry.path;
+        let rel = entry.display(relative_base.as_deref());
+        logger.info(&format!("处理文件: {}", rel));
+
+        // 超过大小限制的文件不读取
+        let mut outcome = match (options.max_size, entry.len) {
+            (Some(limit), Some(size)) if size > limit => {
+                logger.warn(&format!(
+                    "提示: 跳过 {} (size={} > max_size={})",
+                    path.display(),
+                    size,
+                    limit
+                ));
+                FileOutcome::TooLarge
+            }
+            _ => {
+                let loaded = if entry.stdin {
+                    // 标准输入只按文本读取（编码探测、二进制策略）
+                    let content = stdin_content.as_deref().unwrap_or_default();
+                    TextReader.read(path, content, read_opts)
+                } else if let Some(member) = &entry.member {
+                    member
+                        .read()
+                        .and_then(|content| reader.read(path, &content, read_opts))
+                } else {
+                    load_file(reader.as_ref(), path, read_opts)
+                };
+                match loaded {
+                    Ok(loaded) => FileOutcome::Loaded(loaded),
+                    Err(err) => {
+                        logger.error(&format!("错误: 读取失败 {}: {err}", path.display()));
+                        FileOutcome::Failed(err)
+                    }
+                }
+            }
+        };
+
+        let mut ranges = match line_ranges.is_empty() {
+            true => None,
+            false if entry.stdin => line_ranges.get(&range_key(Path::new("-"))).cloned(),
+            false => line_ranges.get(&range_key(path)).cloned(),
+        };
+        if let Some(filter) = &content_filter {
+            match filter.check(&outcome)? {
+                filter::Verdict::Drop => {
+                    logger.info(&format!("内容不匹配，跳过: {}", rel));
+                    return Ok(None);
+                }
+                // 显式给出的行范围优先于 --grep 的区块
+                filter::Verdict::Keep(hunks) => ranges = ranges.or(hunks),
+            }
+        }
+        // 行范围只作用于文本正文；Header 只列出实际输出的行，超出文件末尾的部分去掉
+        let ranges = match &mut outcome {
+            FileOutcome::Loaded(loaded) if loaded.is_text() => {
+                let ranges = ranges.map(|ranges| match loaded.total_lines() {
+                    Ok(total) => clamp_ranges(ranges, total),
+                    Err(_) => ranges,
+                });
+                loaded.lines = ranges.clone();
+                ranges
+            }
+            _ => None,
+        };
+        let style = BodyStyle {
+            ranges: ranges.as_deref(),
+            ..body_style
+        };
+        let rendered = match &outcome {
+            FileOutcome::Loaded(loaded)
+                if direct_stream && matches!(loaded.body, FileBody::Stream { .. }) =>
+            {
+                None
+            }
+            _ => Some(render(&rel, entry, &outcome, style)?),
+        };
+        // 按输出的整个文件块（Header、正文、Footer）计数；流式正文边写边数
+        let tokens = match (&counter, &rendered) {
+            (Some(counter), Some(rendered)) => {
+                Some(counter.count(&String::from_utf8_lossy(rendered)))
+            }
+            (Some(counter), None) => Some(tokens::count_written(counter, |mut out| {
+                write_block(&mut out, &options.divider, &rel, &outcome, style, rev)
+            })?),
+            (None, _) => None,
+        };
+        Ok(Some(Prepared {
+            rel,
+            outcome,
+            ranges,
+            tokens,
+            rendered,
+        }))
+    };
+
+    // --tree/--toc 时放入目录的条目；目录本身超出预算时从末尾退回，
+    // 因此各条目的统计等确定输出哪些条目后才计入
+    let outlining = outline.is_some();
+    let mut outlined = Vec::new();
+
+    // token 预算、统计与输出按排序顺序在主线程进行
+    let mut consume_entry = |index: usize,
+                             prepared: anyhow::Result<Option<Prepared>>,
+                             summary: &mut summary::Summary| {
+        // 被内容过滤掉的文件
+        let Some(Prepared {
+            rel,
+            mut outcome,
+            ranges,
+            tokens,
+            mut rendered,
+        }) = prepared?
+        else {
+            summary.filtered += 1;
+            summary.skip(
+                &entries[index].display(relative_base.as_deref()),
+                "filtered",
+            );
+            return Ok(ControlFlow::Continue(()));
+        };
+        matched += 1;
+        let entry = &entries[index];
+        let mut style = BodyStyle {
+            ranges: ranges.as_deref(),
+            ..body_style
+        };
+        if let FileOutcome::Failed(_) = outcome {
+            had_error = true;
+        }
+
+        let mut budget_reached = false;
+        if let (Some(counter), Some(mut tokens)) = (&counter, tokens) {
+            let remaining = options
+                .max_tokens
+                .map(|budget| budget.saturating_sub(used_tokens));
+            if let Some(remaining) = remaining.filter(|&r| tokens > r) {
+                // 二进制编码内容无法按行截断
+                let clipped = match &outcome {
+                    FileOutcome::Loaded(loaded)
+                        if options.budget_mode == BudgetMode::Clip && loaded.is_text() =>
+                    {
+                        clip_block(counter, loaded, style, remaining, |outcome, style| {
+                            render(&rel, entry, outcome, style)
+                        })?
+                    }
+                    _ => None,
+                };
+                let Some(clipped) = clipped else {
+                    unreached = entries.len() - index;
+                    budget_exhausted = matched == 1;
+                    return Ok(ControlFlow::Break(()));
+                };
+                // 已按输出形态（含行号）截断，原样输出；行号等仍记录在 Header 中
+                outcome = clipped.outcome;
+                style.budget = Some(clipped.budget);
+                rendered = Some(clipped.block);
+                tokens = clipped.tokens;
+                budget_reached = true;
+            }
+            used_tokens += tokens;
+            logger.info(&format!("tokens: {} = {}", rel, tokens));
+        }
+
+        if options.summary.is_some() {
+            summary.record(&rel, &outcome, style);
+        }
+
+        let Some(rendered) = rendered else {
+            // 流式正文：边解码边写出，不在内存中拼出整个文件块
+            let mut counted = CountingWriter::new(&mut out);
+            write_block(&mut counted, &options.divider, &rel, &outcome, style, rev)?;
+            summary.bytes += counted.bytes;
+            summary.lines += counted.lines;
+            return Ok(ControlFlow::Continue(()));
+        };
+
+        let block = match json_writer.as_mut() {
+            Some(writer) => {
+                let mut block = Vec::new();
+                writer.write(&mut block, &rendered)?;
+                block
+            }
+            None => rendered,
+        };
+
+        summary.bytes += block.len();
+        summary.lines += block.iter().filter(|&&b| b == b'\n').count();
+
+        match splitter.as_mut() {
+            Some(splitter) => {
+                let size = measure(&String::from_utf8_lossy(&block));
+                let chunks = match &outcome {
+                    FileOutcome::Loaded(loaded)
+                        if size > splitter.limit() && json_writer.is_none() =>
+                    {
+                        split::split_oversized_block(
+                            &options.divider,
+                            &rel,
+                            loaded,
+                            style,
+                            rev,
+                            splitter.limit(),
+                            &measure,
+                        )?
+                    }
+                    _ => Vec::new(),
+                };
+                if chunks.is_empty() {
+                    if size > splitter.limit() {
+                        logger.warn(&format!(
+                            "提示: {} 超过分卷上限且无法按行拆分，单独放入一个分卷",
+                            rel
+                        ));
+                    }
+                    splitter.push(rel.clone(), &block, size)?;
+                }
+                let total = chunks.len();
+                for (i, chunk) in chunks.into_iter().enumerate() {
+                    let size = measure(&chunk);
+                    let label = format!("{} [part {}/{}]", rel, i + 1, total);
+                    splitter.push(label, chunk.as_bytes(), size)?;
+                }
+            }
+            None => match outline.as_mut() {
+                Some(outline) => {
+                    let detail = match &outcome {
+                        FileOutcome::Loaded(loaded) => match &loaded.body {
+                            FileBody::Text(_) | FileBody::Stream { .. } => {
+                                outline::Detail::Lines(loaded.total_lines()?)
+                            }
+                            FileBody::Binary { .. } => outline::Detail::Binary,
+                        },
+                        FileOutcome::TooLarge => outline::Detail::Skipped,
+                        FileOutcome::Failed(_) => outline::Detail::Failed,
+                    };
+                    outline.push(outline::OutlineEntry {
+                        path: rel,
+                        size: entry.len,
+                        detail,
+                        block,
+                    });
+                    outlined.push(index);
+                }
+                None => out.write_all(&block)?,
+            },
+        }
+
+        if budget_reached {
+            unreached = entries.len() - index - 1;
+            return Ok(ControlFlow::Break(()));
+        }
+        Ok(ControlFlow::Continue(()))
+    };
+    let mut deferred = Vec::new();
+    let consume = |index: usize, prepared: anyhow::Result<Option<Prepared>>| {
+        let mut entry_summary = summary::Summary::default();
+        let flow = consume_entry(index, prepared, &mut entry_summary);
+        match outlining {
+            true => deferred.push((index, entry_summary)),
+            false => summary.merge(entry_summary),
+        }
+        flow
+    };
+
+    let jobs = match options.jobs {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    parallel::for_each_ordered(&entries, jobs, prepare, consume)?;
+
+    let sections = |entries: &[outline::OutlineEntry]| {
+        let tree = if options.tree {
+            outline::render_tree(entries, options.divider)
+        } else {
+            String::new()
+        };
+        let toc = if options.toc {
+            outline::render_toc(entries, &tree, options.divider)
+        } else {
+            String::new()
+        };
+        tree + &toc
+    };
+    // 目录树与目录也计入 token 数，超出预算时从末尾退回文件块
+    if let (Some(outline), Some(counter)) = (outline.as_mut(), &counter) {
+        let budget = options.max_tokens.unwrap_or(usize::MAX);
+        let mut section_tokens = counter.count(&sections(outline));
+        let mut cut = None;
+        while used_tokens + section_tokens > budget {
+            let Some(last) = outline.pop() else {
+                break;
+            };
+            used_tokens -= counter.count(&String::from_utf8_lossy(&last.block));
+            section_tokens = counter.count(&sections(outline));
+            cut = outlined.pop();
+        }
+        if let Some(cut) = cut {
+            unreached = entries.len() - cut;
+            budget_exhausted = outline.is_empty();
+        }
+        used_tokens += section_tokens;
+    }
+    for (index, entry_summary) in deferred {
+        if index < entries.len() - unreached {
+            summary.merge(entry_summary);
+        }
+    }
+
+    if let Some(splitter) = splitter {
+        let parts = splitter.finish()?;
+        logger.info(&format!("已写出 {} 个分卷", parts));
+    }
+
+    if let Some(counter) = &counter {
+        logger.info(&format!(
+            "合计 tokens: {} ({})",
+            used_tokens,
+            counter.name()
+        ));
+    }
+    if budget_exhausted {
+        // --quiet 时也要说明为什么没有输出
+        logger.error(&format!(
+            "错误: 第一个文件 {} 就超过 token 上限 (max_tokens={})，没有输出任何文件；{}",
+            entries[entries.len() - unreached].display(relative_base.as_deref()),
+            options.max_tokens.unwrap_or_default(),
+            match options.budget_mode {
+                BudgetMode::Stop => "请提高 --max-tokens 或使用 --budget-mode clip",
+                // 连 Header、Footer 与 snipped 标记都放不下
+                BudgetMode::Clip => "请提高 --max-tokens",
+            }
+        ));
+    } else if unreached > 0 {
+        logger.warn(&format!(
+            "提示: 已达到 token 上限 (max_tokens={})，其余 {} 个条目未处理",
+            options.max_tokens.unwrap_or_default(),
+            unreached
+        ));
+    }
+
+    if let Some(entries) = outline {
+        out.write_all(sections(&entries).as_bytes())?;
+        for entry in &entries {
+            out.write_all(&entry.block)?;
+        }
+    }
+
+    if let Some(writer) = json_writer.as_mut() {
+        writer.finish(&mut out)?;
+    }
+
+    if let Some(target) = options.summary {
+        summary.unreached = unreached;
+        for entry in &entries[entries.len() - unreached..] {
+            summary.skip(
+                &entry.display(relative_base.as_deref()),
+                "not reached (token budget)",
+            );
+        }
+        summary.tokens = counter.as_ref().map(|c| (used_tokens, c.name()));
+        let text = summary.render(options.divider);
+        match target {
+            SummaryTarget::Stdout => out.write_all(text.as_bytes())?,
+            SummaryTarget::Stderr => eprint!("{}", text),
+        }
+    }
+
+    out.flush()?;
+    if budget_exhausted {
+        return Ok(Status::BudgetExhausted);
+    }
+    if content_filter.is_some() && matched == 0 {
+        logger.warn("（没有文件的内容匹配）");
+        return Ok(Status::NoContentMatch);
+    }
+    Ok(match had_error {
+        true => Status::ReadErrors,
+        false => Status::Done,
+    })
+}
+
+/// 并行阶段的产物：读取结果与按当前方式渲染好的文件块，主线程按顺序消费
+struct Prepared {
+    rel: String,
+    outcome: FileOutcome,
+    /// 只输出的行范围（行范围条目或 --grep）
+    ranges: Option<Vec<LineRange>>,
+    /// 整个文件块的 token 数（需要计数时）
+    tokens: Option<usize>,
+    /// 分隔符文本的文件块，或编码好的 JSON 记录；None 表示流式正文留到输出时再写
+    rendered: Option<Vec<u8>>,
+}
+
+/// 记录写过的字节数与行数（--summary），内容原样转给内层
+struct CountingWriter<W> {
+    inner: W,
+    bytes: usize,
+    lines: usize,
+}
+
+impl<W: Write> CountingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            bytes: 0,
+            lines: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes += written;
+        self.lines += buf[..written].iter().filter(|&&b| b == b'\n').count();
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// 按 token 预算裁剪后的文件块
+struct ClippedBlock {
+    outcome: FileOutcome,
+    budget: BudgetClip,
+    /// 渲染好的文件块
+    block: Vec<u8>,
+    tokens: usize,
+}
+
+/// 把文本文件块裁剪到 `remaining` 个 token 以内；连 Header、Footer 与 snipped 标记都放不下时返回 None。
+/// 正文的预算先扣除空文件块的 token；合在一起计数时有出入（如 JSON 转义）则按超出量收紧再试
+fn clip_block(
+    counter: &TokenCounter,
+    loaded: &LoadedFile,
+    style: BodyStyle,
+    remaining: usize,
+    render: impl Fn(&FileOutcome, BodyStyle) -> anyhow::Result<Vec<u8>>,
+) -> anyhow::Result<Option<ClippedBlock>> {
+    let source_lines = loaded.total_lines()?;
+    let with_body = |text: String| {
+        FileOutcome::Loaded(LoadedFile {
+            encoding: loaded.encoding,
+            pages: loaded.pages,
+            extracted: loaded.extracted,
+            lines: loaded.lines.clone(),
+            body: FileBody::Text(text),
+        })
+    };
+    let count = |block: &[u8]| counter.count(&String::from_utf8_lossy(block));
+    let budget = BudgetClip {
+        source_lines,
+        snipped_lines: source_lines,
+    };
+    let frame = count(&render(
+        &with_body(String::new()),
+        BodyStyle {
+            budget: Some(budget),
+            ..style
+        },
+    )?);
+    let mut body_budget = remaining.saturating_sub(frame);
+    loop {
+        let clipped = tokens::clip_to_budget(counter, loaded, style, body_budget)?;
+        let budget = BudgetClip {
+            source_lines,
+            snipped_lines: clipped.snipped_lines,
+        };
+        let outcome = with_body(clipped.text);
+        let block = render(
+            &outcome,
+            BodyStyle {
+                budget: Some(budget),
+                ..style
+            },
+        )?;
+        let tokens = count(&block);
+        if tokens <= remaining {
+            return Ok(Some(ClippedBlock {
+                outcome,
+                budget,
+                block,
+                tokens,
+            }));
+        }
+        if body_budget == 0 {
+            return Ok(None);
+        }
+        body_budget = body_budget.saturating_sub(tokens - remaining);
+    }
+}
@@ -0,0 +1,511 @@
+//! 读取后端：文本（探测编码）、macOS textutil、内置提取器，二进制文件按 `--binary` 策略处理。
+
+use crate::extract::{self, DocFormat};
+use crate::render::HeaderMeta;
+use crate::{git, notebook, stream, LineRange, Logger};
+use base64::engine::general_purpose::STANDARD as Base64;
+use base64::Engine;
+use chardetng::EncodingDetector;
+use clap::ValueEnum;
+use encoding_rs::Encoding;
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// 内置的读取后端
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ReaderKind {
+    /// 直接按文本读取（默认）
+    Text,
+    /// 调用 macOS `textutil` 读取（适合 doc/docx/rtf/html 等）
+    Textutil,
+    /// 内置提取器（docx/odt/rtf/html/pdf/ipynb），不依赖外部命令
+    Native,
+    /// 自动：docx/odt/rtf/html/pdf/ipynb 用内置提取器，其它富文本用 textutil，其余走 Text
+    Auto,
+}
+
+/// 判定为二进制文件时的处理策略
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum BinaryStrategy {
+    /// 跳过二进制文件
+    Skip,
+    /// 按十六进制输出
+    Hex,
+    /// 按 Base64 输出
+    Base64,
+    /// 强制按文本处理
+    Print,
+}
+
+impl BinaryStrategy {
+    pub fn name(self) -> &'static str {
+        match self {
+            BinaryStrategy::Skip => "skip",
+            BinaryStrategy::Hex => "hex",
+            BinaryStrategy::Base64 => "base64",
+            BinaryStrategy::Print => "print",
+        }
+    }
+}
+
+/// 读取时的公共设置
+#[derive(Clone, Copy)]
+pub struct ReadOptions<'a> {
+    /// 判定为二进制文件时的处理策略
+    pub binary: BinaryStrategy,
+    /// 从该 git 版本读取；内容来自对象库，工作区中不一定有对应文件
+    pub rev: Option<&'a str>,
+    /// Jupyter 笔记本每个文本输出保留的行数；None 时不输出
+    pub notebook_outputs: Option<usize>,
+    pub logger: &'a Logger,
+}
+
+/// 读取后端：把文件的原始内容转换为正文
+pub trait Reader: Send + Sync {
+    /// 转换 `path` 的内容 `bytes`；二进制内容按 `opts.binary` 处理
+    fn read(&self, path: &Path, bytes: &[u8], opts: ReadOptions) -> anyhow::Result<LoadedFile>;
+
+    /// 该文件是否按纯文本读取；是则超过流式阈值时不整体读入内存
+    fn is_plain_text(&self, _path: &Path) -> bool {
+        false
+    }
+}
+
+impl ReaderKind {
+    pub fn reader(self) -> Box<dyn Reader> {
+        match self {
+            ReaderKind::Text => Box::new(TextReader),
+            ReaderKind::Textutil => Box::new(TextutilReader),
+            ReaderKind::Native => Box::new(NativeReader),
+            ReaderKind::Auto => Box::new(AutoReader),
+        }
+    }
+}
+
+/// 直接按文本读取，探测编码
+pub struct TextReader;
+
+impl Reader for TextReader {
+    fn read(&self, path: &Path, bytes: &[u8], opts: ReadOptions) -> anyhow::Result<LoadedFile> {
+        load_text(path, bytes, opts.binary, opts.logger)
+    }
+
+    fn is_plain_text(&self, _path: &Path) -> bool {
+        true
+    }
+}
+
+/// macOS `textutil`；不可用或失败时回退到内置提取器，再回退到文本
+pub struct TextutilReader;
+
+impl Reader for TextutilReader {
+    fn read(&self, path: &Path, bytes: &[u8], opts: ReadOptions) -> anyhow::Result<LoadedFile> {
+        // --rev 与归档中的内容不一定在工作区中，不能交给 textutil
+        if opts.rev.is_some() || !path.is_file() {
+            return load_native_then_fallback(path, bytes, opts);
+        }
+        load_textutil_then_fallback(path, bytes, opts)
+    }
+}
+
+/// 内置提取器（docx/odt/rtf/html/pdf/ipynb），其余按文本读取
+pub struct NativeReader;
+
+impl Reader for NativeReader {
+    fn read(&self, path: &Path, bytes: &[u8], opts: ReadOptions) -> anyhow::Result<LoadedFile> {
+        load_native_then_fallback(path, bytes, opts)
+    }
+}
+
+/// 按扩展名选择：内置提取器能处理的格式、其它富文本交给 textutil，其余按文本读取
+pub struct AutoReader;
+
+impl Reader for AutoReader {
+    fn read(&self, path: &Path, bytes: &[u8], opts: ReadOptions) -> anyhow::Result<LoadedFile> {
+        if DocFormat::from_path(path).is_some()
+            || extract::is_pdf(path)
+            || notebook::is_notebook(path)
+        {
+            load_native_then_fallback(path, bytes, opts)
+        } else if should_use_textutil(path) && opts.rev.is_none() && path.is_file() {
+            load_textutil_then_fallback(path, bytes, opts)
+        } else {
+            load_text(path, bytes, opts.binary, opts.logger)
+        }
+    }
+
+    fn is_plain_text(&self, path: &Path) -> bool {
+        DocFormat::from_path(path).is_none()
+            && !extract::is_pdf(path)
+            && !notebook::is_notebook(path)
+            && !should_use_textutil(path)
+    }
+}
+
+/// 读取一个文件：大的纯文本文件只扫描判断二进制与编码，输出时再流式解码；其余读入后交给 `reader`
+pub fn load_file(
+    reader: &dyn Reader,
+    path: &Path,
+    opts: ReadOptions,
+) -> anyhow::Result<LoadedFile> {
+    if opts.rev.is_none()
+        && reader.is_plain_text(path)
+        && fs::metadata(path)?.len() > stream::STREAM_THRESHOLD
+    {
+        return load_text_streaming(path, opts.binary, opts.logger);
+    }
+    let bytes = match opts.rev {
+        Some(rev) => git::read_blob(rev, path)?,
+        None => fs::read(path)?,
+    };
+    reader.read(path, &bytes, opts)
+}
+
+// 修改：返回 (解码内容, 编码名称)
+// 如果是 UTF-8，编码名称为 None
+pub(crate) fn decode_content(bytes: &[u8]) -> (Cow<'_, str>, Option<&'static str>) {
+    if let Ok(s) = std::str::from_utf8(bytes) {
+        return (Cow::Borrowed(s), None);
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    let encoding = detector.guess(None, true);
+    let (cow, _, _) = encoding.decode(bytes);
+    // 返回检测到的编码名称（如 GBK, EUC-JP 等）
+    (cow, Some(encoding.name()))
+}
+
+/// 读取阶段的产物：正文与 Header 附加信息，渲染阶段（分隔符文本或 JSON）据此输出
+pub struct LoadedFile {
+    /// 非 UTF-8 文本探测到的编码
+    pub encoding: Option<&'static str>,
+    /// PDF 的页数
+    pub pages: Option<usize>,
+    /// 正文是从文档中提取（或由外部命令转换）的文本，不是文件的原始内容
+    pub extracted: bool,
+    /// 只输出指定行范围时记录在 Header 中
+    pub lines: Option<Vec<LineRange>>,
+    pub body: FileBody,
+}
+
+pub enum FileBody {
+    /// 已解码的文本（尚未裁剪）
+    Text(String),
+    /// 超过流式阈值的纯文本文件，输出时按块读取解码
+    Stream {
+        path: PathBuf,
+        /// 不是 UTF-8 时探测到的编码
+        encoding: Option<&'static Encoding>,
+        /// 统计过的总行数；每统计一次都要重读文件，只统计一次
+        total_lines: OnceCell<usize>,
+    },
+    /// 按 --binary 策略处理的二进制文件；Skip 时没有内容
+    Binary {
+        strategy: BinaryStrategy,
+        encoded: Option<String>,
+    },
+}
+
+impl LoadedFile {
+    pub fn text(s: String, encoding: Option<&'static str>) -> Self {
+        Self {
+            encoding,
+            pages: None,
+            extracted: false,
+            lines: None,
+            body: FileBody::Text(s),
+        }
+    }
+
+    /// 提取器或外部命令转换得到的文本；Header 中带有 `[extracted]`，unpack 不会写回
+    pub fn extracted(s: String, encoding: Option<&'static str>) -> Self {
+        Self {
+            extracted: true,
+            ..Self::text(s, encoding)
+        }
+    }
+
+    /// 正文是文本（可以按行裁剪、加行号）
+    pub fn is_text(&self) -> bool {
+        matches!(self.body, FileBody::Text(_) | FileBody::Stream { .. })
+    }
+
+    /// 文本正文的总行数；流式正文第一次调用时需要再读一遍文件
+    pub fn total_lines(&self) -> io::Result<usize> {
+        match &self.body {
+            FileBody::Text(s) => Ok(s.split_inclusive('\n').count()),
+            FileBody::Stream {
+                path, total_lines, ..
+            } => match total_lines.get() {
+                Some(&total) => Ok(total),
+                None => {
+                    let total = stream::count_lines(path)?;
+                    Ok(*total_lines.get_or_init(|| total))
+                }
+            },
+            FileBody::Binary { .. } => Ok(0),
+        }
+    }
+
+    pub(crate) fn header_meta(&self) -> HeaderMeta<'_> {
+        let binary = match &self.body {
+            FileBody::Binary {
+                strategy,
+                encoded: Some(_),
+            } => Some(*strategy),
+            _ => None,
+        };
+        HeaderMeta {
+            change: None,
+            rev: None,
+            encoding: self.encoding,
+            pages: self.pages,
+            extracted: self.extracted,
+            binary,
+            lines: self.lines.as_deref(),
+            numbered: false,
+            part: None,
+        }
+    }
+}
+
+/// 单个文件的处理结果
+pub enum FileOutcome {
+    Loaded(LoadedFile),
+    /// 超过 --max-size，未读取
+    TooLarge,
+    Failed(anyhow::Error),
+}
+
+fn load_text(
+    path: &Path,
+    bytes: &[u8],
+    binary: BinaryStrategy,
+    logger: &Logger,
+) -> anyhow::Result<LoadedFile> {
+    // 判定为二进制时不探测编码，直接按策略处理
+    if is_probably_binary(bytes) && !matches!(binary, BinaryStrategy::Print) {
+        return Ok(load_binary(path, bytes, binary, logger));
+    }
+
+    // 文本处理：先探测编码
+    let (s, encoding_name) = decode_content(bytes);
+    Ok(LoadedFile::text(s.into_owned(), encoding_name))
+}
+
+// 扫描整个文件判断二进制与编码（与整体读入的结果一致），正文留到输出时读取
+fn load_text_streaming(
+    path: &Path,
+    binary: BinaryStrategy,
+    logger: &Logger,
+) -> anyhow::Result<LoadedFile> {
+    let scanned = stream::scan(path)?;
+    if scanned.binary && !matches!(binary, BinaryStrategy::Print) {
+        // hex/base64 需要完整内容；skip 不必读取
+        let bytes = match binary {
+            BinaryStrategy::Skip => Vec::new(),
+            _ => fs::read(path)?,
+        };
+        return Ok(load_binary(path, &bytes, binary, logger));
+    }
+    Ok(LoadedFile {
+        encoding: scanned.encoding.map(Encoding::name),
+        pages: None,
+        extracted: false,
+        lines: None,
+        body: FileBody::Stream {
+            path: path.to_path_buf(),
+            encoding: scanned.encoding,
+            total_lines: OnceCell::new(),
+        },
+    })
+}
+
+fn load_textutil_then_fallback(
+    path: &Path,
+    bytes: &[u8],
+    opts: ReadOptions,
+) -> anyhow::Result<LoadedFile> {
+    let logger = opts.logger;
+    if which::which("textutil").is_ok() {
+        let output = Command::new("textutil")
+            .arg("-convert")
+            .arg("txt")
+            .arg("-stdout")
+            .arg(path)
+            .output();
+        match output {
+            Ok(outp) if outp.status.success() => {
+                // textutil 转换后一定是 UTF-8，所以 Header 不显示特殊编码；
+                // 依然做一个 decode 以防万一
+                let (s, _) = decode_content(&outp.stdout);
+                return Ok(LoadedFile::extracted(s.into_owned(), None));
+            }
+            Ok(outp) => {
+                logger.warn(&format!(
+                    "警告: textutil 处理失败 ({}), 回退到内置读取: {}",
+                    outp.status,
+                    path.display()
+                ));
+            }
+            Err(e) => {
+                logger.warn(&format!(
+                    "警告: textutil 调用异常 ({}), 回退到内置读取: {}",
+                    e,
+                    path.display()
+                ));
+            }
+        }
+    } else {
+        logger.warn(&format!(
+            "提示: 未检测到 textutil，回退到内置读取。文件: {}",
+            path.display()
+        ));
+    }
+    // 回退：能内置提取的格式先提取，其余按文本读取
+    load_native_then_fallback(path, bytes, opts)
+}
+
+fn load_native_then_fallback(
+    path: &Path,
+    bytes: &[u8],
+    opts: ReadOptions,
+) -> anyhow::Result<LoadedFile> {
+    let (binary, logger) = (opts.binary, opts.logger);
+    if extract::is_pdf(path) {
+        return load_pdf(path, bytes, binary, logger);
+    }
+    let extracted = match DocFormat::from_path(path) {
+        Some(format) => extract::extract_text(format, bytes),
+        None if notebook::is_notebook(path) => notebook::render(bytes, opts.notebook_outputs),
+        None => return load_text(path, bytes, binary, logger),
+    };
+    match extracted {
+        // 提取结果一定是 UTF-8，Header 不显示特殊编码
+        Ok(text) => Ok(LoadedFile::extracted(text, None)),
+        Err(e) => {
+            logger.warn(&format!(
+                "警告: 内置提取失败 ({}), 回退到文本读取: {}",
+                e,
+                path.display()
+            ));
+            load_text(path, bytes, binary, logger)
+        }
+    }
+}
+
+fn should_use_textutil(path: &Path) -> bool {
+    let Some(ext) = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(|s| s.to_ascii_lowercase())
+    else {
+        return false;
+    };
+    matches!(
+        ext.as_str(),
+        "rtf" | "rtfd" | "doc" | "docx" | "html" | "htm" | "odt" | "webarchive"
+    )
+}
+
+// 按策略编码二进制内容（Print 策略不会走到这里）
+fn load_binary(path: &Path, bytes: &[u8], strategy: BinaryStrategy, logger: &Logger) -> LoadedFile {
+    let encoded = match strategy {
+        BinaryStrategy::Skip => None,
+        BinaryStrategy::Hex => Some(hex::encode(bytes)),
+        BinaryStrategy::Base64 => Some(Base64.encode(bytes)),
+        BinaryStrategy::Print => unreachable!(),
+    };
+    logger.warn(&format!(
+        "提示: 二进制文件按 {:?} 处理: {}",
+        strategy,
+        path.display()
+    ));
+    LoadedFile {
+        encoding: None,
+        pages: None,
+        extracted: false,
+        lines: None,
+        body: FileBody::Binary { strategy, encoded },
+    }
+}
+
+pub(crate) fn is_probably_binary(bytes: &[u8]) -> bool {
+    bytes.contains(&0)
+}
+
+// PDF：逐页输出并以 `--- page N ---` 分隔，Header 中给出页数；
+// 加密或纯图片（没有任何文字）的 PDF 按 --binary 策略处理
+fn load_pdf(
+    path: &Path,
+    bytes: &[u8],
+    binary: BinaryStrategy,
+    logger: &Logger,
+) -> anyhow::Result<LoadedFile> {
+    let reason = match extract::pdf_pages(bytes) {
+        Ok(pages) if pages.iter().any(|p| !p.trim().is_empty()) => {
+            let mut text = String::new();
+            for (i, page) in pages.iter().enumerate() {
+                text.push_str(&format!("--- page {} ---\n", i + 1));
+                text.push_str(page);
+            }
+            return Ok(LoadedFile {
+                encoding: None,
+                pages: Some(pages.len()),
+                extracted: true,
+                lines: None,
+                body: FileBody::Text(text),
+            });
+        }
+        Ok(_) => "没有可提取的文本".to_string(),
+        Err(e) => e.to_string(),
+    };
+    logger.warn(&format!(
+        "提示: PDF 无法提取文本 ({}), 按二进制处理: {}",
+        reason,
+        path.display()
+    ));
+    if matches!(binary, BinaryStrategy::Print) {
+        return load_text(path, bytes, binary, logger);
+    }
+    Ok(load_binary(path, bytes, binary, logger))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_use_textutil_recognizes_known_extensions() {
+        assert!(should_use_textutil(Path::new("doc.DOCX")));
+        assert!(should_use_textutil(Path::new("note.html")));
+        assert!(!should_use_textutil(Path::new("note.txt")));
+        assert!(!should_use_textutil(Path::new("noext")));
+    }
+
+    #[test]
+    fn binary_detection_by_null_byte() {
+        assert!(is_probably_binary(b"abc\0def"));
+        assert!(!is_probably_binary(b"plain text"));
+    }
+
+    #[test]
+    fn streamed_line_count_is_read_once() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = temp.path().join("big.log");
+        fs::write(&path, "row\n".repeat(300_000)).unwrap();
+        let logger = crate::Logger::new(false, true);
+        let loaded = load_text_streaming(&path, BinaryStrategy::Skip, &logger).unwrap();
+        assert_eq!(loaded.total_lines().unwrap(), 300_000);
+
+        // 之后的调用（Header、裁剪、--summary）不再重读文件
+        fs::write(&path, "row\n").unwrap();
+        assert_eq!(loaded.total_lines().unwrap(), 300_000);
+    }
+}
@@ -0,0 +1,589 @@
+//! 文件块的渲染：分隔符风格的 Header/Footer，以及正文的行范围、裁剪与行号。
+
+use crate::diff;
+use crate::read::{BinaryStrategy, FileBody, FileOutcome, LoadedFile};
+use crate::stream;
+use clap::ValueEnum;
+use encoding_rs::Encoding;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// 内置的分隔符风格
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Divider {
+    /// 形如 ===path=== / ===end of 'path'===
+    Equals,
+    /// 形如 ``` path/to/file
+    TripleBacktick,
+    /// 形如 <file path="path/to/file">
+    XmlTag,
+}
+
+/// 文件块的 Header/Footer 风格
+pub trait Renderer: Send + Sync {
+    fn header(&self, path: &str, meta: HeaderMeta<'_>) -> String;
+
+    fn footer(&self, path: &str) -> String;
+
+    /// --tree / --toc / --summary 等附加区块；名称不带路径引号，unpack 不会误认为文件块
+    fn section(&self, name: &str, body: &str) -> String;
+}
+
+impl Renderer for Divider {
+    fn section(&self, name: &str, body: &str) -> String {
+        match self {
+            Divider::Equals => format!("==={}===\n{}===end of {}===\n", name, body, name),
+            Divider::TripleBacktick => format!("```{}\n{}```\n", name, body),
+            Divider::XmlTag => format!("<{}>\n{}</{}>\n", name, body, name),
+        }
+    }
+
+    fn header(&self, rel: &str, meta: HeaderMeta<'_>) -> String {
+        match self {
+            Divider::Equals => format!("==={}{}===", rel, meta.bracketed()),
+            Divider::TripleBacktick => format!("``` {}{}", rel, meta.bracketed()),
+            Divider::XmlTag => {
+                format!(
+                    "<file path=\"{}\"{}>",
+                    escape_xml_attr(rel),
+                    meta.xml_attrs()
+                )
+            }
+        }
+    }
+
+    fn footer(&self, rel: &str) -> String {
+        match self {
+            Divider::Equals => format!("===end of '{}'===", rel),
+            Divider::TripleBacktick => "```".to_string(),
+            Divider::XmlTag => "</file>".to_string(),
+        }
+    }
+}
+
+/// Header 中除路径外的附加信息
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HeaderMeta<'a> {
+    /// diff 模式下的变更类型
+    pub change: Option<diff::Change<'a>>,
+    /// `--rev` 指定的版本
+    pub rev: Option<&'a str>,
+    /// 非 UTF-8 时探测到的编码
+    pub encoding: Option<&'a str>,
+    /// PDF 等分页文档的页数
+    pub pages: Option<usize>,
+    /// 正文是提取出的文本而不是原始内容，unpack 据此跳过
+    pub extracted: bool,
+    /// 正文为 hex/base64 编码的二进制内容，unpack 据此还原
+    pub binary: Option<BinaryStrategy>,
+    /// 只输出了部分行（`path:120-180` 形式的条目）
+    pub lines: Option<&'a [LineRange]>,
+    /// 正文每行带有 `--line-numbers` 的行号，unpack 据此跳过
+    pub numbered: bool,
+    /// 超大文件拆分到多个分卷时的 (序号, 总数)
+    pub part: Option<(usize, usize)>,
+}
+
+impl HeaderMeta<'_> {
+    // ===path [GBK] [3 pages]=== 形式的后缀
+    fn bracketed(&self) -> String {
+        let mut s = String::new();
+        if let Some(rev) = self.rev {
+            s.push_str(&format!(" [rev {}]", rev));
+        }
+        match self.change {
+            Some(diff::Change::Renamed { from }) => {
+                s.push_str(&format!(" [renamed from {}]", from))
+            }
+            Some(change) => s.push_str(&format!(" [{}]", change.name())),
+            None => {}
+        }
+        if let Some(e) = self.encoding {
+            s.push_str(&format!(" [{}]", e));
+        }
+        if let Some(b) = self.binary {
+            s.push_str(&format!(" [{}]", b.name()));
+        }
+        if let Some(n) = self.pages {
+            s.push_str(&format!(
+                " [{} {}]",
+                n,
+                if n == 1 { "page" } else { "pages" }
+            ));
+        }
+        if self.extracted {
+            s.push_str(" [extracted]");
+        }
+        if let Some(ranges) = self.lines {
+            s.push_str(&format!(" [lines {}]", format_ranges(ranges)));
+        }
+        if self.numbered {
+            s.push_str(" [numbered]");
+        }
+        if let Some((i, n)) = self.part {
+            s.push_str(&format!(" [part {}/{}]", i, n));
+        }
+        s
+    }
+
+    fn xml_attrs(&self) -> String {
+        let mut s = String::new();
+        if let Some(rev) = self.rev {
+            s.push_str(&format!(" rev=\"{}\"", escape_xml_attr(rev)));
+        }
+        if let Some(change) = self.change {
+            s.push_str(&format!(" change=\"{}\"", change.name()));
+            if let diff::Change::Renamed { from } = change {
+                s.push_str(&format!(" from=\"{}\"", escape_xml_attr(from)));
+            }
+        }
+        if let Some(e) = self.encoding {
+            s.push_str(&format!(" encoding=\"{}\"", e));
+        }
+        if let Some(b) = self.binary {
+            s.push_str(&format!(" binary=\"{}\"", b.name()));
+        }
+        if let Some(n) = self.pages {
+            s.push_str(&format!(" pages=\"{}\"", n));
+        }
+        if self.extracted {
+            s.push_str(" extracted=\"true\"");
+        }
+        if let Some(ranges) = self.lines {
+            s.push_str(&format!(" lines=\"{}\"", format_ranges(ranges)));
+        }
+        if self.numbered {
+            s.push_str(" numbered=\"true\"");
+        }
+        if let Some((i, n)) = self.part {
+            s.push_str(&format!(" part=\"{}/{}\"", i, n));
+        }
+        s
+    }
+}
+
+/// `path:120-180` / `path:#L10-L40` 指定的行范围（从 1 开始，含两端）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// 排序并合并重叠或相邻的范围
+pub fn merge_ranges(ranges: &mut Vec<LineRange>) {
+    ranges.sort_by_key(|r| (r.start, r.end));
+    let mut merged: Vec<LineRange> = Vec::with_capacity(ranges.len());
+    for range in ranges.drain(..) {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end.saturating_add(1) => {
+                last.end = last.end.max(range.end)
+            }
+            _ => merged.push(range),
+        }
+    }
+    *ranges = merged;
+}
+
+/// 把范围截到文件的总行数内，去掉完全超出文件末尾的范围
+pub fn clamp_ranges(ranges: Vec<LineRange>, total_lines: usize) -> Vec<LineRange> {
+    ranges
+        .into_iter()
+        .filter(|r| r.start <= total_lines)
+        .map(|r| LineRange {
+            start: r.start,
+            end: r.end.min(total_lines),
+        })
+        .collect()
+}
+
+/// `1-8,30-50` 形式，用于 Header；没有任何行时为 `none`
+pub fn format_ranges(ranges: &[LineRange]) -> String {
+    if ranges.is_empty() {
+        return "none".to_string();
+    }
+    ranges
+        .iter()
+        .map(|r| {
+            if r.start == r.end {
+                r.start.to_string()
+            } else {
+                format!("{}-{}", r.start, r.end)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// `--clip`：保留开头 head 行与末尾 tail 行
+#[derive(Debug, Clone, Copy)]
+pub struct ClipSpec {
+    pub head: usize,
+    pub tail: usize,
+}
+
+/// 解析 `--clip` 的 `N[:M]`
+pub fn parse_clip_spec(raw: &str) -> anyhow::Result<ClipSpec> {
+    let s = raw.trim();
+    if s.is_empty() {
+        return Ok(ClipSpec { head: 5, tail: 3 });
+    }
+
+    let (head_str, tail_str_opt) = match s.split_once(':') {
+        Some((h, t)) => (h.trim(), Some(t.trim())),
+        None => (s, None),
+    };
+
+    let head = if head_str.is_empty() {
+        0
+    } else {
+        head_str.parse::<usize>().map_err(|e| {
+            anyhow::anyhow!("invalid --clip value (head part '{}'): {}", head_str, e)
+        })?
+    };
+
+    let tail = match tail_str_opt {
+        Some(t) if !t.is_empty() => t
+            .parse::<usize>()
+            .map_err(|e| anyhow::anyhow!("invalid --clip value (tail part '{}'): {}", t, e))?,
+        _ => 0,
+    };
+
+    if head == 0 && tail == 0 {
+        anyhow::bail!(
+            "invalid --clip value '{}': head and tail cannot both be 0",
+            raw
+        );
+    }
+
+    Ok(ClipSpec { head, tail })
+}
+
+/// `--line-numbers` 的格式
+#[derive(Debug, Clone, Copy)]
+pub struct LineNumbers<'a> {
+    pub width: Option<usize>,
+    pub separator: &'a str,
+}
+
+impl LineNumbers<'_> {
+    fn width_for(&self, total_lines: usize) -> usize {
+        self.width.unwrap_or_else(|| total_lines.to_string().len())
+    }
+}
+
+/// 正文的输出方式：行范围或裁剪，以及行号
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BodyStyle<'a> {
+    pub clip: Option<ClipSpec>,
+    /// 指定了行范围时只输出这些行，优先于 clip
+    pub ranges: Option<&'a [LineRange]>,
+    pub numbers: Option<LineNumbers<'a>>,
+    /// 正文已按上面的方式渲染并被 token 预算截断；原样输出，其余字段只用于 Header 与记录
+    pub budget: Option<BudgetClip>,
+}
+
+/// `--budget-mode clip` 截断正文的结果
+#[derive(Debug, Clone, Copy)]
+pub struct BudgetClip {
+    /// 截断前正文的总行数
+    pub source_lines: usize,
+    /// 因 token 预算省略的行数
+    pub snipped_lines: usize,
+}
+
+impl BodyStyle<'_> {
+    /// 原样输出，不需要逐行处理
+    pub fn is_plain(&self) -> bool {
+        self.budget.is_some()
+            || (self.clip.is_none() && self.ranges.is_none() && self.numbers.is_none())
+    }
+}
+
+/// 以分隔符风格输出一个文件块（Header、正文、Footer）
+pub fn write_block<W: Write>(
+    out: &mut W,
+    renderer: &dyn Renderer,
+    rel_path: &str,
+    outcome: &FileOutcome,
+    style: BodyStyle,
+    rev: Option<&str>,
+) -> anyhow::Result<()> {
+    match outcome {
+        FileOutcome::Loaded(loaded) => {
+            writeln!(
+                out,
+                "{}",
+                renderer.header(
+                    rel_path,
+                    HeaderMeta {
+                        rev,
+                        numbered: style.numbers.is_some() && loaded.is_text(),
+                        ..loaded.header_meta()
+                    }
+                )
+            )?;
+            let ended_with_newline = match &loaded.body {
+                FileBody::Text(s) => write_body(s, style, out)?,
+                FileBody::Stream { path, encoding, .. } => {
+                    write_stream(path, *encoding, || loaded.total_lines(), style, out)?
+                }
+                FileBody::Binary { encoded, .. } => {
+                    match encoded {
+                        Some(encoded) => writeln!(out, "{}", encoded)?,
+                        None => writeln!(out, "(skipped binary file)")?,
+                    }
+                    true
+                }
+            };
+            if !ended_with_newline {
+                writeln!(out)?;
+            }
+        }
+        FileOutcome::TooLarge => {
+            // 因为没有读取，不知道编码，传入 None
+            let meta = HeaderMeta {
+                rev,
+                ..HeaderMeta::default()
+            };
+            writeln!(out, "{}", renderer.header(rel_path, meta))?;
+            writeln!(out, "(skipped: file exceeds max size)")?;
+        }
+        FileOutcome::Failed(_) => {
+            // 读取失败时只输出空的文件块
+            let meta = HeaderMeta {
+                rev,
+                ..HeaderMeta::default()
+            };
+            writeln!(out, "{}", renderer.header(rel_path, meta))?;
+            writeln!(out)?;
+        }
+    }
+    writeln!(out, "{}", renderer.footer(rel_path))?;
+    Ok(())
+}
+
+/// 正文按输出时的样子（含裁剪与二进制编码）渲染为字符串
+pub fn rendered_body(loaded: &LoadedFile, style: BodyStyle) -> anyhow::Result<String> {
+    if let FileBody::Binary { encoded, .. } = &loaded.body {
+        return Ok(encoded.clone().unwrap_or_default());
+    }
+    let mut buf = Vec::new();
+    write_rendered_body(&mut buf, loaded, style)?;
+    Ok(String::from_utf8(buf)?)
+}
+
+/// 同 [`rendered_body`]，但写入 `out`；流式正文按块解码写出，不整体读入内存
+pub fn write_rendered_body<W: Write>(
+    out: &mut W,
+    loaded: &LoadedFile,
+    style: BodyStyle,
+) -> anyhow::Result<()> {
+    match &loaded.body {
+        FileBody::Text(s) => {
+            write_body(s, style, out)?;
+        }
+        FileBody::Stream { path, encoding, .. } => {
+            write_stream(path, *encoding, || loaded.total_lines(), style, out)?;
+        }
+        FileBody::Binary { encoded, .. } => {
+            out.write_all(encoded.as_deref().unwrap_or_default().as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+// 输出已解码的正文，返回是否以换行结尾
+fn write_body<W: Write>(content: &str, style: BodyStyle, out: &mut W) -> anyhow::Result<bool> {
+    if style.is_plain() {
+        write!(out, "{}", content)?;
+        return Ok(content.ends_with('\n'));
+    }
+    let total = content.split_inclusive('\n').count();
+    let width = style.numbers.map_or(0, |n| n.width_for(total));
+    let lines = content.split_inclusive('\n').map(Ok::<_, io::Error>);
+    write_line_stream(lines, style, width, total, out)
+}
+
+// 流式输出大文件的正文：不裁剪时按块解码输出，否则逐行处理
+fn write_stream<W: Write>(
+    path: &Path,
+    encoding: Option<&'static Encoding>,
+    total_lines: impl FnOnce() -> io::Result<usize>,
+    style: BodyStyle,
+    out: &mut W,
+) -> anyhow::Result<bool> {
+    let mut lines = stream::DecodedLines::open(path, encoding)?;
+    if style.is_plain() {
+        let mut ended_with_newline = false;
+        while let Some(chunk) = lines.next_chunk()? {
+            out.write_all(chunk.as_bytes())?;
+            ended_with_newline = chunk.ends_with('\n');
+        }
+        return Ok(ended_with_newline);
+    }
+    // 自动行号宽度与裁剪的尾部位置取决于总行数，需要先数一遍
+    let auto_width = matches!(style.numbers, Some(LineNumbers { width: None, .. }));
+    let clipped = style.clip.is_some() && style.ranges.is_none();
+    let total = match auto_width || clipped {
+        true => total_lines()?,
+        false => 0,
+    };
+    let width = style.numbers.map_or(0, |n| n.width_for(total));
+    write_line_stream(lines, style, width, total, out)
+}
+
+/// 裁剪时省略的行数，与 write_line_stream 的 "... (snipped N lines) ..." 一致
+pub fn snipped_lines(total_lines: usize, clip: ClipSpec) -> usize {
+    total_lines.saturating_sub(clip.head + clip.tail)
+}
+
+// 逐段处理正文（行范围、裁剪、行号）。每段是一整行或长行的一部分，以换行结尾的段结束一行；
+// 裁剪按总行数 `total` 确定尾部从哪一行开始，不在内存中缓存行。
+// `width` 为行号宽度。返回最后输出的内容是否以换行结尾
+fn write_line_stream<W, I, S>(
+    pieces: I,
+    style: BodyStyle,
+    width: usize,
+    total: usize,
+    out: &mut W,
+) -> anyhow::Result<bool>
+where
+    W: Write,
+    I: Iterator<Item = io::Result<S>>,
+    S: AsRef<str>,
+{
+    // 范围已排序合并，优先于 clip；读过最后一个范围后不再读取
+    let mut ranges = style.ranges.map(|ranges| ranges.iter().peekable());
+    let clip = style.clip.filter(|_| ranges.is_none());
+    let mut ended_with_newline = false;
+    let mut lineno = 0usize;
+    let mut line_start = true;
+    let mut keep = true;
+    // 上一个输出的行号，据此在省略的行处插入 snipped 标记
+    let mut previous: Option<usize> = None;
+
+    for piece in pieces {
+        let piece = piece?;
+        let piece = piece.as_ref();
+        if line_start {
+            lineno += 1;
+            keep = match (&mut ranges, clip) {
+                (Some(ranges), _) => {
+                    while ranges.peek().is_some_and(|r| r.end < lineno) {
+                        ranges.next();
+                    }
+                    match ranges.peek() {
+                        Some(range) => lineno >= range.start,
+                        None => break,
+                    }
+                }
+                (None, Some(ClipSpec { head, tail })) => {
+                    lineno <= head || lineno > total.saturating_sub(tail)
+                }
+                (None, None) => true,
+            };
+            if keep {
+                // 行范围只标记范围之间的间隔，裁剪还标记开头省略的行
+                let gap = match previous {
+                    Some(previous) => lineno - previous - 1,
+                    None if clip.is_some() => lineno - 1,
+                    None => 0,
+                };
+                if gap > 0 {
+                    writeln!(out, "... (snipped {} lines) ...", gap)?;
+                }
+                previous = Some(lineno);
+                if let Some(numbers) = style.numbers {
+                    write!(
+                        out,
+                        "{:>width$}{}",
+                        lineno,
+                        numbers.separator,
+                        width = width
+                    )?;
+                }
+            }
+        }
+        line_start = piece.ends_with('\n');
+        if keep {
+            out.write_all(piece.as_bytes())?;
+            ended_with_newline = line_start;
+        }
+    }
+
+    // tail 为 0 时末尾的行都省略了
+    let last = previous.unwrap_or(0);
+    if clip.is_some() && lineno > last {
+        writeln!(out, "... (snipped {} lines) ...", lineno - last)?;
+        ended_with_newline = true;
+    }
+    Ok(ended_with_newline)
+}
+
+pub(crate) fn escape_xml_attr(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_merge_up_to_the_last_line_and_clamp_to_the_file() {
+        let range = |start, end| LineRange { start, end };
+        let mut ranges = vec![range(5, usize::MAX), range(1, 2), range(3, 4), range(7, 9)];
+        merge_ranges(&mut ranges);
+        assert_eq!(ranges, [range(1, usize::MAX)]);
+
+        let clamped = clamp_ranges(vec![range(2, 3), range(8, 300), range(12, 20)], 10);
+        assert_eq!(clamped, [range(2, 3), range(8, 10)]);
+        assert_eq!(format_ranges(&clamped), "2-3,8-10");
+        assert_eq!(
+            format_ranges(&clamp_ranges(vec![range(12, 20)], 10)),
+            "none"
+        );
+    }
+
+    #[test]
+    fn clip_inserts_snipped_line() {
+        let content = "line1\nline2\nline3\nline4\nline5\nline6\n";
+        let clip = ClipSpec { head: 2, tail: 2 };
+        let mut buf = Vec::new();
+        let style = BodyStyle {
+            clip: Some(clip),
+            ..BodyStyle::default()
+        };
+        let ended = write_body(content, style, &mut buf).unwrap();
+        let s = String::from_utf8(buf).unwrap();
+
+        assert!(s.contains("line1\nline2\n"));
+        assert!(s.contains("line5\nline6\n"));
+        assert!(s.contains("... (snipped 2 lines) ..."));
+        assert!(ended);
+    }
+
+    #[test]
+    fn line_numbers_survive_the_snipped_gap() {
+        let content: String = (1..=12).map(|i| format!("l{}\n", i)).collect();
+        let numbers = LineNumbers {
+            width: None,
+            separator: " | ",
+        };
+        let clip = ClipSpec { head: 1, tail: 2 };
+        let mut buf = Vec::new();
+        let style = BodyStyle {
+            clip: Some(clip),
+            numbers: Some(numbers),
+            ..BodyStyle::default()
+        };
+        write_body(&content, style, &mut buf).unwrap();
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            " 1 | l1\n... (snipped 9 lines) ...\n11 | l11\n12 | l12\n"
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! `--split-bytes` / `--split-tokens`：把输出拆分为多个分卷文件。
+
+use crate::read::LoadedFile;
+use crate::render::{rendered_body, BodyStyle, HeaderMeta, Renderer};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 依次接收完整的文件块，装满上限后写出一个分卷；文件块本身不会被拆开
+pub struct Splitter {
+    dir: PathBuf,
+    limit: usize,
+    extension: &'static str,
+    current: Vec<u8>,
+    current_size: usize,
+    current_paths: Vec<String>,
+    /// 已写出的分卷及其中的路径，用于生成索引
+    parts: Vec<(String, Vec<String>)>,
+}
+
+impl Splitter {
+    pub fn new(dir: &Path, limit: usize, extension: &'static str) -> anyhow::Result<Self> {
+        if limit == 0 {
+            anyhow::bail!("split limit must be greater than 0");
+        }
+        fs::create_dir_all(dir)?;
+        remove_stale_parts(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            limit,
+            extension,
+            current: Vec::new(),
+            current_size: 0,
+            current_paths: Vec::new(),
+            parts: Vec::new(),
+        })
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// 追加一个文件块；`size` 为按上限口径（字节或 token）度量的大小
+    pub fn push(&mut self, label: String, block: &[u8], size: usize) -> anyhow::Result<()> {
+        if !self.current.is_empty() && self.current_size + size > self.limit {
+            self.flush()?;
+        }
+        self.current.extend_from_slice(block);
+        self.current_size += size;
+        self.current_paths.push(label);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        if self.current.is_empty() {
+            return Ok(());
+        }
+        let name = format!("part-{:03}.{}", self.parts.len() + 1, self.extension);
+        fs::write(self.dir.join(&name), &self.current)?;
+        self.current.clear();
+        self.current_size = 0;
+        self.parts
+            .push((name, std::mem::take(&mut self.current_paths)));
+        Ok(())
+    }
+
+    /// 写出最后一个分卷与 index.txt（每行 `分卷<TAB>路径`），返回分卷数
+    pub fn finish(mut self) -> anyhow::Result<usize> {
+        self.flush()?;
+        let mut index = String::new();
+        for (name, paths) in &self.parts {
+            for path in paths {
+                index.push_str(&format!("{}\t{}\n", name, path));
+            }
+        }
+        fs::write(self.dir.join("index.txt"), index)?;
+        Ok(self.parts.len())
+    }
+}
+
+// 上次运行留下的分卷会与新的 index.txt 不一致，开始前删除；目录中的其它文件不动
+fn remove_stale_parts(dir: &Path) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if is_part_name(&entry.file_name().to_string_lossy()) && entry.file_type()?.is_file() {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+// `part-001.txt` / `part-012.jsonl`
+fn is_part_name(name: &str) -> bool {
+    let Some((number, extension)) = name.strip_prefix("part-").and_then(|n| n.split_once('.'))
+    else {
+        return false;
+    };
+    !number.is_empty()
+        && number.bytes().all(|b| b.is_ascii_digit())
+        && matches!(extension, "txt" | "jsonl")
+}
+
+/// 单个文件超过分卷上限时按行切成多个带 [part i/n] 标记的文件块；
+/// 无法切分（二进制、单行过长或只有一块）时返回空
+pub fn split_oversized_block(
+    renderer: &dyn Renderer,
+    rel_path: &str,
+    loaded: &LoadedFile,
+    style: BodyStyle,
+    rev: Option<&str>,
+    limit: usize,
+    measure: &dyn Fn(&str) -> usize,
+) -> anyhow::Result<Vec<String>> {
+    if !loaded.is_text() {
+        return Ok(Vec::new());
+    }
+    let body = rendered_body(loaded, style)?;
+    let meta = HeaderMeta {
+        rev,
+        numbered: style.numbers.is_some(),
+        ..loaded.header_meta()
+    };
+    let widest = HeaderMeta {
+        part: Some((999, 999)),
+        ..meta
+    };
+    let overhead = measure(&format!(
+        "{}\n{}\n",
+        renderer.header(rel_path, widest),
+        renderer.footer(rel_path)
+    ));
+
+    let mut pieces: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut current_size = overhead;
+    for line in body.split_inclusive('\n') {
+        let line_size = measure(line);
+        if !current.is_empty() && current_size + line_size > limit {
+            pieces.push(std::mem::take(&mut current));
+            current_size = overhead;
+        }
+        current.push_str(line);
+        current_size += line_size;
+    }
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    if pieces.len() < 2 {
+        return Ok(Vec::new());
+    }
+
+    let total = pieces.len();
+    let mut chunks = Vec::with_capacity(total);
+    for (i, piece) in pieces.iter().enumerate() {
+        let meta = HeaderMeta {
+            part: Some((i + 1, total)),
+            ..meta
+        };
+        let mut chunk = format!("{}\n{}", renderer.header(rel_path, meta), piece);
+        if !piece.ends_with('\n') {
+            chunk.push('\n');
+        }
+        chunk.push_str(&renderer.footer(rel_path));
+        chunk.push('\n');
+        chunks.push(chunk);
+    }
+    Ok(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_are_packed_without_crossing_parts() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let dir = temp.path().join("parts");
+        // 上次运行留下的分卷被清除，其它文件保留
+        fs::create_dir_all(&dir).unwrap();
+        for stale in [
+            "part-001.txt",
+            "part-007.txt",
+            "part-002.jsonl",
+            "notes.txt",
+        ] {
+            fs::write(dir.join(stale), "old\n").unwrap();
+        }
+        let mut splitter = Splitter::new(&dir, 10, "txt").unwrap();
+        splitter.push("a".into(), b"aaaa\n", 5).unwrap();
+        splitter.push("b".into(), b"bbbb\n", 5).unwrap();
+        splitter.push("c".into(), b"cccc\n", 5).unwrap();
+        assert_eq!(splitter.finish().unwrap(), 2);
+
+        assert_eq!(
+            fs::read_to_string(dir.join("part-001.txt")).unwrap(),
+            "aaaa\nbbbb\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("part-002.txt")).unwrap(),
+            "cccc\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("index.txt")).unwrap(),
+            "part-001.txt\ta\npart-001.txt\tb\npart-002.txt\tc\n"
+        );
+        let mut names: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            ["index.txt", "notes.txt", "part-001.txt", "part-002.txt"]
+        );
+    }
+}
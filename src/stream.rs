@@ -0,0 +1,273 @@
+//! 大文件的流式读取：先扫描一遍判断二进制与编码，输出时按块解码。
+
+use chardetng::EncodingDetector;
+use encoding_rs::{CoderResult, Decoder, Encoding, UTF_8};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// 超过该大小的纯文本文件不整体读入内存
+pub(crate) const STREAM_THRESHOLD: u64 = 1 << 20;
+
+const CHUNK_LEN: usize = 64 * 1024;
+
+/// 没有换行的长行按不超过该字节数的段产出，缓冲不随行长增长
+pub(crate) const MAX_PIECE: usize = 64 * 1024;
+
+/// 整个文件的扫描结果，与整体读入时的 `is_probably_binary` / `decode_content` 一致
+pub(crate) struct Scanned {
+    pub(crate) binary: bool,
+    /// 不是合法 UTF-8 时由 chardetng 猜测的编码
+    pub(crate) encoding: Option<&'static Encoding>,
+}
+
+/// 按块扫描整个文件：找 NUL 字节并校验 UTF-8；不是 UTF-8 时再读一遍交给 chardetng 探测编码
+pub(crate) fn scan(path: &Path) -> io::Result<Scanned> {
+    let mut file = File::open(path)?;
+    // 末尾多留 3 字节，放上一块结尾被截断的多字节字符
+    let mut buf = vec![0u8; CHUNK_LEN + 3];
+    let mut carry = 0;
+    let mut utf8 = true;
+    loop {
+        let n = file.read(&mut buf[carry..carry + CHUNK_LEN])?;
+        if n == 0 {
+            break;
+        }
+        if buf[carry..carry + n].contains(&0) {
+            return Ok(Scanned {
+                binary: true,
+                encoding: None,
+            });
+        }
+        if !utf8 {
+            continue;
+        }
+        let data = &buf[..carry + n];
+        carry = match std::str::from_utf8(data) {
+            Ok(_) => 0,
+            Err(e) if e.error_len().is_none() => {
+                let valid = e.valid_up_to();
+                buf.copy_within(valid..carry + n, 0);
+                carry + n - valid
+            }
+            Err(_) => {
+                utf8 = false;
+                0
+            }
+        };
+    }
+    // 文件在多字节字符中间结束也不是合法 UTF-8
+    if utf8 && carry == 0 {
+        return Ok(Scanned {
+            binary: false,
+            encoding: None,
+        });
+    }
+    let mut file = File::open(path)?;
+    let mut detector = EncodingDetector::new();
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        detector.feed(&buf[..n], false);
+    }
+    detector.feed(&[], true);
+    Ok(Scanned {
+        binary: false,
+        encoding: Some(detector.guess(None, true)),
+    })
+}
+
+/// 统计行数（与 `split_inclusive('\n')` 的计数一致），不做解码
+pub(crate) fn count_lines(path: &Path) -> io::Result<usize> {
+    let mut file = File::open(path)?;
+    let mut buf = vec![0u8; CHUNK_LEN];
+    let mut newlines = 0;
+    let mut last = None;
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        newlines += buf[..n].iter().filter(|&&b| b == b'\n').count();
+        last = Some(buf[n - 1]);
+    }
+    Ok(newlines + usize::from(last.is_some_and(|b| b != b'\n')))
+}
+
+/// 按块读取并解码，逐行产出（保留行尾换行符）；超过 [`MAX_PIECE`] 的行分成多段产出，
+/// 以换行结尾（或位于文件末尾）的段结束一行
+pub(crate) struct DecodedLines<R> {
+    reader: R,
+    decoder: Decoder,
+    raw: Vec<u8>,
+    text: String,
+    /// `text` 中尚未产出部分的起点
+    pos: usize,
+    done: bool,
+}
+
+impl DecodedLines<File> {
+    pub(crate) fn open(path: &Path, encoding: Option<&'static Encoding>) -> io::Result<Self> {
+        Ok(Self::new(File::open(path)?, encoding))
+    }
+}
+
+impl<R: Read> DecodedLines<R> {
+    pub(crate) fn new(reader: R, encoding: Option<&'static Encoding>) -> Self {
+        Self {
+            reader,
+            // 与整体读取时的 decode_content 一致：UTF-8 保留 BOM，其他编码识别 BOM
+            decoder: match encoding {
+                Some(encoding) => encoding.new_decoder(),
+                None => UTF_8.new_decoder_without_bom_handling(),
+            },
+            raw: vec![0u8; CHUNK_LEN],
+            text: String::new(),
+            pos: 0,
+            done: false,
+        }
+    }
+
+    /// 读入并解码下一块，返回是否已到文件末尾
+    fn fill(&mut self) -> io::Result<bool> {
+        self.text.drain(..self.pos);
+        self.pos = 0;
+        let n = self.reader.read(&mut self.raw)?;
+        let last = n == 0;
+        decode_into(&mut self.decoder, &self.raw[..n], &mut self.text, last);
+        Ok(last)
+    }
+
+    /// 不分行，直接产出下一块解码后的文本
+    pub(crate) fn next_chunk(&mut self) -> io::Result<Option<String>> {
+        while !self.done && self.pos == self.text.len() {
+            self.done = self.fill()?;
+        }
+        if self.pos == self.text.len() {
+            return Ok(None);
+        }
+        let chunk = self.text[self.pos..].to_string();
+        self.pos = self.text.len();
+        Ok(Some(chunk))
+    }
+}
+
+impl<R: Read> Iterator for DecodedLines<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let rest = &self.text[self.pos..];
+            let piece_len = match rest.find('\n') {
+                Some(i) if i < MAX_PIECE => Some(i + 1),
+                // 长行：在字符边界处切出一段
+                _ if rest.len() >= MAX_PIECE => {
+                    let mut len = MAX_PIECE;
+                    while !rest.is_char_boundary(len) {
+                        len -= 1;
+                    }
+                    Some(len)
+                }
+                _ if self.done && !rest.is_empty() => Some(rest.len()),
+                _ if self.done => return None,
+                _ => None,
+            };
+            if let Some(len) = piece_len {
+                let piece = rest[..len].to_string();
+                self.pos += len;
+                return Some(Ok(piece));
+            }
+            match self.fill() {
+                Ok(last) => self.done = last,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+fn decode_into(decoder: &mut Decoder, mut input: &[u8], out: &mut String, last: bool) {
+    loop {
+        let needed = decoder
+            .max_utf8_buffer_length(input.len())
+            .unwrap_or(input.len() * 3 + 16);
+        out.reserve(needed);
+        let (result, read, _) = decoder.decode_to_string(input, out, last);
+        input = &input[read..];
+        if let CoderResult::InputEmpty = result {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decoded_lines_keep_newlines_across_chunks() {
+        // GBK 的双字节字符跨越块边界也能正确解码
+        let text = "中文行\n".repeat(40_000);
+        let (bytes, _, _) = encoding_rs::GBK.encode(&text);
+        let lines: Vec<String> = DecodedLines::new(&bytes[..], Some(encoding_rs::GBK))
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(lines.len(), 40_000);
+        assert!(lines.iter().all(|l| l == "中文行\n"));
+
+        let lines: Vec<String> = DecodedLines::new(&b"a\nb"[..], None)
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(lines, ["a\n", "b"]);
+    }
+
+    #[test]
+    fn long_lines_are_split_into_bounded_pieces() {
+        let text = format!("{}\nend", "长".repeat(100_000));
+        let pieces: Vec<String> = DecodedLines::new(text.as_bytes(), None)
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert!(pieces.len() > 4);
+        assert!(pieces.iter().all(|p| p.len() <= MAX_PIECE));
+        assert_eq!(pieces.concat(), text);
+        assert_eq!(pieces.iter().filter(|p| p.ends_with('\n')).count(), 1);
+        assert_eq!(pieces.last().unwrap(), "end");
+    }
+
+    #[test]
+    fn scan_checks_the_whole_file() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let write = |name: &str, bytes: &[u8]| {
+            let path = temp.path().join(name);
+            std::fs::write(&path, bytes).unwrap();
+            path
+        };
+        let ascii = "a".repeat(3 * CHUNK_LEN + 1);
+        let (gbk, _, _) = encoding_rs::GBK.encode("中文内容，编码探测\n");
+        let mut legacy = ascii.clone().into_bytes();
+        for _ in 0..100 {
+            legacy.extend_from_slice(&gbk);
+        }
+
+        let scanned = scan(&write("legacy", &legacy)).unwrap();
+        assert!(!scanned.binary);
+        assert_eq!(scanned.encoding, Some(encoding_rs::GBK));
+
+        // 块边界截断的多字节字符仍是 UTF-8；文件末尾截断则不是
+        let text = format!("{}中\n", "a".repeat(CHUNK_LEN - 1));
+        assert_eq!(
+            scan(&write("utf8", text.as_bytes())).unwrap().encoding,
+            None
+        );
+        let truncated = &text.as_bytes()[..CHUNK_LEN + 1];
+        assert!(scan(&write("truncated", truncated))
+            .unwrap()
+            .encoding
+            .is_some());
+
+        let mut nul = ascii.into_bytes();
+        nul.push(0);
+        assert!(scan(&write("nul", &nul)).unwrap().binary);
+    }
+}
@@ -0,0 +1,155 @@
+//! `--summary`：输出结束后汇总打印、裁剪、跳过与失败的文件。
+
+use crate::read::{FileBody, FileOutcome};
+use crate::render::{escape_xml_attr, snipped_lines, BodyStyle, Divider, Renderer};
+
+/// 按文件累计的统计
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub printed: usize,
+    pub clipped: usize,
+    pub skipped_size: usize,
+    pub skipped_binary: usize,
+    pub failed: usize,
+    pub non_utf8: usize,
+    /// 被 --contains / --not-contains / --grep 过滤掉的文件
+    pub filtered: usize,
+    /// 达到 token 上限后未处理的其余条目（未读取，不区分是否会被过滤或跳过）
+    pub unreached: usize,
+    /// 输出的字节数与行数（含 Header/Footer）
+    pub bytes: usize,
+    pub lines: usize,
+    /// 输出的 token 数（不含汇总本身）与分词器名称
+    pub tokens: Option<(usize, &'static str)>,
+    /// 没有输出内容的文件及原因
+    pub skipped: Vec<(String, String)>,
+}
+
+impl Summary {
+    pub fn skip(&mut self, path: &str, reason: impl Into<String>) {
+        self.skipped.push((path.to_string(), reason.into()));
+    }
+
+    /// 计入另一份按文件累计的统计（不含 `unreached` 与 `tokens`）
+    pub fn merge(&mut self, other: Summary) {
+        self.printed += other.printed;
+        self.clipped += other.clipped;
+        self.skipped_size += other.skipped_size;
+        self.skipped_binary += other.skipped_binary;
+        self.failed += other.failed;
+        self.non_utf8 += other.non_utf8;
+        self.filtered += other.filtered;
+        self.bytes += other.bytes;
+        self.lines += other.lines;
+        self.skipped.extend(other.skipped);
+    }
+
+    /// 按读取结果与输出方式累计一个文件
+    pub fn record(&mut self, rel_path: &str, outcome: &FileOutcome, style: BodyStyle) {
+        match outcome {
+            FileOutcome::Loaded(loaded) => match &loaded.body {
+                FileBody::Text(_) | FileBody::Stream { .. } => {
+                    self.printed += 1;
+                    if loaded.encoding.is_some() {
+                        self.non_utf8 += 1;
+                    }
+                    let clipped = style.budget.is_some()
+                        || style.ranges.is_some()
+                        || style.clip.is_some_and(|clip| {
+                            loaded
+                                .total_lines()
+                                .is_ok_and(|total| snipped_lines(total, clip) > 0)
+                        });
+                    if clipped {
+                        self.clipped += 1;
+                    }
+                }
+                FileBody::Binary { encoded: None, .. } => {
+                    self.skipped_binary += 1;
+                    self.skip(rel_path, "binary");
+                }
+                FileBody::Binary { .. } => self.printed += 1,
+            },
+            FileOutcome::TooLarge => {
+                self.skipped_size += 1;
+                self.skip(rel_path, "exceeds max size");
+            }
+            FileOutcome::Failed(err) => {
+                self.failed += 1;
+                self.skip(rel_path, format!("read error: {}", err));
+            }
+        }
+    }
+
+    pub fn render(&self, divider: Divider) -> String {
+        let mut body = format!(
+            "printed: {}\n\
+             clipped: {}\n\
+             skipped (max size): {}\n\
+             skipped (binary): {}\n\
+             failed: {}\n\
+             non-UTF-8: {}\n",
+            self.printed,
+            self.clipped,
+            self.skipped_size,
+            self.skipped_binary,
+            self.failed,
+            self.non_utf8
+        );
+        if self.filtered > 0 {
+            body.push_str(&format!("skipped (filtered): {}\n", self.filtered));
+        }
+        if self.unreached > 0 {
+            body.push_str(&format!("not reached (token budget): {}\n", self.unreached));
+        }
+        body.push_str(&format!(
+            "total: {} bytes, {} lines",
+            self.bytes, self.lines
+        ));
+        if let Some((tokens, tokenizer)) = self.tokens {
+            body.push_str(&format!(", {} tokens ({})", tokens, tokenizer));
+        }
+        body.push('\n');
+        if !self.skipped.is_empty() {
+            body.push_str("skipped files:\n");
+            for (path, reason) in &self.skipped {
+                body.push_str(&format!("  {}: {}\n", path, reason));
+            }
+        }
+        if let Divider::XmlTag = divider {
+            body = escape_xml_attr(&body);
+        }
+        divider.section("summary", &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_lists_counts_and_skipped_paths() {
+        let mut summary = Summary {
+            printed: 2,
+            skipped_binary: 1,
+            bytes: 120,
+            lines: 9,
+            tokens: Some((30, "cl100k")),
+            ..Summary::default()
+        };
+        summary.skip("logo.png", "binary");
+        assert_eq!(
+            summary.render(Divider::TripleBacktick),
+            "```summary\n\
+             printed: 2\n\
+             clipped: 0\n\
+             skipped (max size): 0\n\
+             skipped (binary): 1\n\
+             failed: 0\n\
+             non-UTF-8: 0\n\
+             total: 120 bytes, 9 lines, 30 tokens (cl100k)\n\
+             skipped files:\n  logo.png: binary\n\
+             ```\n"
+        );
+    }
+}
@@ -0,0 +1,392 @@
+//! Token 估算：内置 tiktoken 的 BPE 词表（离线），另有按字符数/4 的粗略估算。
+//! 未启用 `tokens` 特性时没有词表，各分词器都按字符数/4 估算。
+
+use crate::read::LoadedFile;
+use crate::render::{write_rendered_body, BodyStyle};
+use clap::ValueEnum;
+use std::io::{self, Write};
+
+// 流式正文按块计数时每块的大致字节数
+const CHUNK_BYTES: usize = 64 * 1024;
+
+// 找不到合适切点（很长的单行）时缓冲的上限，超过后强制切开计数
+const MAX_PENDING: usize = 4 * CHUNK_BYTES;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Tokenizer {
+    /// GPT-4o 系列使用的 o200k_base
+    O200k,
+    /// GPT-4 / GPT-3.5 使用的 cl100k_base
+    Cl100k,
+    /// Codex 系列使用的 p50k_base
+    P50k,
+    /// GPT-3 使用的 r50k_base
+    R50k,
+    /// 不加载词表，按字符数/4 估算
+    Chars,
+}
+
+#[cfg(feature = "tokens")]
+type Bpe = &'static tiktoken_rs::CoreBPE;
+
+#[cfg(feature = "tokens")]
+fn load_bpe(tokenizer: Tokenizer) -> Option<Bpe> {
+    match tokenizer {
+        Tokenizer::O200k => Some(tiktoken_rs::o200k_base_singleton()),
+        Tokenizer::Cl100k => Some(tiktoken_rs::cl100k_base_singleton()),
+        Tokenizer::P50k => Some(tiktoken_rs::p50k_base_singleton()),
+        Tokenizer::R50k => Some(tiktoken_rs::r50k_base_singleton()),
+        Tokenizer::Chars => None,
+    }
+}
+
+#[cfg(feature = "tokens")]
+fn bpe_count(bpe: Bpe, text: &str) -> usize {
+    // 文件内容中的 <|endoftext|> 等按普通文本处理
+    bpe.encode_ordinary(text).len()
+}
+
+#[cfg(not(feature = "tokens"))]
+type Bpe = std::convert::Infallible;
+
+#[cfg(not(feature = "tokens"))]
+fn load_bpe(_: Tokenizer) -> Option<Bpe> {
+    None
+}
+
+#[cfg(not(feature = "tokens"))]
+fn bpe_count(bpe: Bpe, _: &str) -> usize {
+    match bpe {}
+}
+
+/// 按选定的分词器计数；BPE 词表在创建时加载（每种词表在进程内只加载一次）
+pub struct TokenCounter {
+    tokenizer: Tokenizer,
+    bpe: Option<Bpe>,
+}
+
+impl TokenCounter {
+    pub fn new(tokenizer: Tokenizer) -> Self {
+        let bpe = load_bpe(tokenizer);
+        Self { tokenizer, bpe }
+    }
+
+    pub fn name(&self) -> &'static str {
+        if self.bpe.is_none() {
+            return "chars/4";
+        }
+        match self.tokenizer {
+            Tokenizer::O200k => "o200k",
+            Tokenizer::Cl100k => "cl100k",
+            Tokenizer::P50k => "p50k",
+            Tokenizer::R50k => "r50k",
+            Tokenizer::Chars => "chars/4",
+        }
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        match self.bpe {
+            Some(bpe) => bpe_count(bpe, text),
+            None => text.chars().count().div_ceil(4),
+        }
+    }
+}
+
+/// 计数写出的文本（如流式正文的整个文件块）；按块计数，不在内存中拼出整个正文
+pub fn count_written(
+    counter: &TokenCounter,
+    write: impl FnOnce(&mut dyn Write) -> anyhow::Result<()>,
+) -> anyhow::Result<usize> {
+    let mut chunks = ChunkCounter {
+        counter,
+        pending: Vec::new(),
+        tokens: 0,
+    };
+    write(&mut chunks)?;
+    chunks.count_through(chunks.pending.len());
+    Ok(chunks.tokens)
+}
+
+// 写入的文本攒够一块后在换行处切开计数；切点后紧跟非空白字符，
+// 分词不会跨过这样的换行合并，结果与整体计数基本一致
+struct ChunkCounter<'a> {
+    counter: &'a TokenCounter,
+    pending: Vec<u8>,
+    tokens: usize,
+}
+
+impl ChunkCounter<'_> {
+    fn count_through(&mut self, end: usize) {
+        let chunk: Vec<u8> = self.pending.drain(..end).collect();
+        self.tokens += self.counter.count(&String::from_utf8_lossy(&chunk));
+    }
+}
+
+impl Write for ChunkCounter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // 上次已攒够一块却找不到切点时，只需检查新写入的部分
+        let from = match self.pending.len() >= CHUNK_BYTES {
+            true => self.pending.len() - 1,
+            false => 0,
+        };
+        self.pending.extend_from_slice(buf);
+        if self.pending.len() >= CHUNK_BYTES {
+            let cut = self.pending[from..]
+                .windows(2)
+                .rposition(|pair| pair[0] == b'\n' && !pair[1].is_ascii_whitespace());
+            if let Some(cut) = cut {
+                self.count_through(from + cut + 1);
+            }
+        }
+        if self.pending.len() >= MAX_PENDING {
+            // 在最后一个空白之前切开（空白通常与其后的词合为一个 token），
+            // 没有空白时在字符边界处切开
+            let cut = self
+                .pending
+                .iter()
+                .rposition(u8::is_ascii_whitespace)
+                .filter(|&i| i > 0)
+                .unwrap_or_else(|| utf8_boundary(&self.pending));
+            self.count_through(cut);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// 不切开多字节字符的切点：末尾不完整的字符留到下次
+fn utf8_boundary(bytes: &[u8]) -> usize {
+    match std::str::from_utf8(bytes) {
+        Err(e) if e.error_len().is_none() && e.valid_up_to() > 0 => e.valid_up_to(),
+        _ => bytes.len(),
+    }
+}
+
+/// [`clip_to_budget`] 的结果
+pub struct BudgetClipped {
+    pub text: String,
+    /// 保留部分与 snipped 标记的 token 数
+    pub tokens: usize,
+    pub snipped_lines: usize,
+}
+
+/// 按输出形态（裁剪、行号）渲染正文，从头开始保留放得进预算的整行，其余用 snipped 标记代替；
+/// 预算包括标记本身。流式正文边解码边截断，内存中只有保留的部分与当前行已计数的前缀
+pub fn clip_to_budget(
+    counter: &TokenCounter,
+    loaded: &LoadedFile,
+    style: BodyStyle,
+    budget: usize,
+) -> anyhow::Result<BudgetClipped> {
+    let mut clipper = BudgetClipper {
+        counter,
+        budget,
+        kept: String::new(),
+        kept_sizes: Vec::new(),
+        used: 0,
+        line: String::new(),
+        line_tokens: 0,
+        pending: Vec::new(),
+        in_line: false,
+        full: false,
+        kept_lines: 0,
+        total_lines: 0,
+    };
+    write_rendered_body(&mut clipper, loaded, style)?;
+    Ok(clipper.finish())
+}
+
+// 逐行累计 token：行结束时放得进预算就保留，否则此后只数行数。
+// 很长的行攒够一块就先计数，已超出预算时立即丢弃
+struct BudgetClipper<'a> {
+    counter: &'a TokenCounter,
+    budget: usize,
+    kept: String,
+    /// 保留的各行的字节数与 token 数，放 snipped 标记时从末尾退回
+    kept_sizes: Vec<(usize, usize)>,
+    used: usize,
+    /// 当前行已计数的部分
+    line: String,
+    line_tokens: usize,
+    /// 当前行尚未计数的部分
+    pending: Vec<u8>,
+    /// 当前行已开始但还没有换行
+    in_line: bool,
+    /// 预算已用完
+    full: bool,
+    kept_lines: usize,
+    total_lines: usize,
+}
+
+impl BudgetClipper<'_> {
+    fn count_pending(&mut self) {
+        let end = utf8_boundary(&self.pending);
+        let text = String::from_utf8_lossy(&self.pending[..end]).into_owned();
+        self.pending.drain(..end);
+        self.line_tokens += self.counter.count(&text);
+        self.line.push_str(&text);
+        if self.used + self.line_tokens > self.budget {
+            self.full = true;
+            self.line = String::new();
+        }
+    }
+
+    fn end_line(&mut self) {
+        self.total_lines += 1;
+        self.in_line = false;
+        if self.full {
+            return;
+        }
+        self.count_pending();
+        if !self.full {
+            self.used += self.line_tokens;
+            self.kept.push_str(&self.line);
+            self.kept_sizes.push((self.line.len(), self.line_tokens));
+            self.kept_lines += 1;
+        }
+        self.line.clear();
+        self.line_tokens = 0;
+    }
+
+    fn finish(mut self) -> BudgetClipped {
+        if self.in_line {
+            self.end_line();
+        }
+        let mut marker_tokens = 0;
+        let mut skipped = self.total_lines - self.kept_lines;
+        while skipped > 0 {
+            let marker = self.marker(skipped);
+            marker_tokens = self.counter.count(&marker);
+            if self.used + marker_tokens <= self.budget {
+                self.kept.push_str(&marker);
+                break;
+            }
+            // 标记放不下时退回最后一行
+            let Some((len, tokens)) = self.kept_sizes.pop() else {
+                self.kept.push_str(&marker);
+                break;
+            };
+            self.kept.truncate(self.kept.len() - len);
+            self.used -= tokens;
+            skipped += 1;
+        }
+        BudgetClipped {
+            text: self.kept,
+            tokens: self.used + marker_tokens,
+            snipped_lines: skipped,
+        }
+    }
+
+    // 接在保留部分之后的 snipped 标记；最后保留的一行没有换行时先补上
+    fn marker(&self, skipped: usize) -> String {
+        let newline = match self.kept.is_empty() || self.kept.ends_with('\n') {
+            true => "",
+            false => "\n",
+        };
+        format!("{}... (snipped {} lines) ...\n", newline, skipped)
+    }
+}
+
+impl Write for BudgetClipper<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for segment in buf.split_inclusive(|&b| b == b'\n') {
+            self.in_line = true;
+            if !self.full {
+                self.pending.extend_from_slice(segment);
+            }
+            if segment.ends_with(b"\n") {
+                self.end_line();
+            } else if !self.full && self.pending.len() >= CHUNK_BYTES {
+                self.count_pending();
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chars_fallback_rounds_up() {
+        let counter = TokenCounter::new(Tokenizer::Chars);
+        assert_eq!(counter.count(""), 0);
+        assert_eq!(counter.count("abcde"), 2);
+        assert_eq!(counter.count("中文字符"), 1);
+    }
+
+    #[cfg(feature = "tokens")]
+    #[test]
+    fn bpe_counts_known_text() {
+        let counter = TokenCounter::new(Tokenizer::Cl100k);
+        assert_eq!(counter.count("hello world"), 2);
+        assert_eq!(counter.count("<|endoftext|>"), 7);
+    }
+
+    #[cfg(feature = "tokens")]
+    #[test]
+    fn chunked_counts_match_whole_text() {
+        let counter = TokenCounter::new(Tokenizer::Cl100k);
+        let text: String = (0..6_000)
+            .map(|i| format!("fn item_{i}() -> u32 {{ {i} }}\n\n    // 注释 {i}\n"))
+            .collect();
+        let mut chunks = ChunkCounter {
+            counter: &counter,
+            pending: Vec::new(),
+            tokens: 0,
+        };
+        for piece in text.as_bytes().chunks(3000) {
+            chunks.write_all(piece).unwrap();
+        }
+        // 已按块计数，缓冲不会增长到整个文本
+        assert!(chunks.pending.len() < 2 * CHUNK_BYTES);
+        chunks.count_through(chunks.pending.len());
+        assert_eq!(chunks.tokens, counter.count(&text));
+    }
+
+    #[test]
+    fn newline_free_text_is_counted_in_bounded_chunks() {
+        let counter = TokenCounter::new(Tokenizer::Chars);
+        let mut chunks = ChunkCounter {
+            counter: &counter,
+            pending: Vec::new(),
+            tokens: 0,
+        };
+        let piece = "x".repeat(1000);
+        for _ in 0..2_000 {
+            chunks.write_all(piece.as_bytes()).unwrap();
+            assert!(chunks.pending.len() < MAX_PENDING);
+        }
+        chunks.count_through(chunks.pending.len());
+        assert_eq!(chunks.tokens, 500_000);
+    }
+
+    #[test]
+    fn budget_keeps_whole_lines_that_fit() {
+        let counter = TokenCounter::new(Tokenizer::Chars);
+        let loaded = LoadedFile::text("abcdefg\n".repeat(6), None);
+        // snipped 标记（7 个 token）也计入预算，放不下时退回已保留的行
+        let clipped = clip_to_budget(&counter, &loaded, BodyStyle::default(), 11).unwrap();
+        assert_eq!(
+            clipped.text,
+            "abcdefg\nabcdefg\n... (snipped 4 lines) ...\n"
+        );
+        assert_eq!((clipped.tokens, clipped.snipped_lines), (11, 4));
+        let clipped = clip_to_budget(&counter, &loaded, BodyStyle::default(), 12).unwrap();
+        assert_eq!((clipped.tokens, clipped.snipped_lines), (12, 0));
+
+        // 超出预算的长行在读完之前就丢弃
+        let loaded = LoadedFile::text(format!("ok\n{}\ntail", "x".repeat(300_000)), None);
+        let clipped = clip_to_budget(&counter, &loaded, BodyStyle::default(), 10).unwrap();
+        assert_eq!(clipped.text, "ok\n... (snipped 2 lines) ...\n");
+        assert_eq!((clipped.tokens, clipped.snipped_lines), (8, 2));
+    }
+}
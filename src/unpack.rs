@@ -0,0 +1,636 @@
+//! `printfiles unpack`：把 printfiles 的输出还原为目录树。
+//!
+//! 支持三种分隔符风格（可混用），识别 Header 中的编码与 hex/base64 标记。
+
+use crate::Logger;
+use base64::engine::general_purpose::STANDARD as Base64;
+use base64::Engine;
+use encoding_rs::Encoding;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+/// 从输出中解析出的一个文件块
+#[derive(Debug, PartialEq, Eq)]
+struct Block {
+    path: String,
+    encoding: Option<String>,
+    /// `hex` 或 `base64`
+    binary: Option<String>,
+    pages: Option<usize>,
+    /// `[extracted]`：正文是从文档中提取的文本
+    extracted: bool,
+    /// `printfiles diff` 的输出块，正文是差异而不是文件内容
+    diff: bool,
+    /// `[lines 120-180]`：只包含部分行
+    lines: Option<String>,
+    /// `[numbered]`：每行前加了行号
+    numbered: bool,
+    /// 分卷输出中被拆开的文件：(序号, 总数)
+    part: Option<(usize, usize)>,
+    body: String,
+}
+
+/// 写入一个文件时进行的操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Create,
+    Update,
+    Unchanged,
+}
+
+impl Action {
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Create => "create",
+            Action::Update => "update",
+            Action::Unchanged => "unchanged",
+        }
+    }
+}
+
+/// 一个文件块的处理结果
+#[derive(Debug)]
+pub enum Outcome {
+    /// 不是原始内容，没有写入；附带原因
+    Skipped(&'static str),
+    /// 已进行（`dry_run` 时为将要进行）的操作
+    Written(Action),
+    /// 路径被拒绝或写入失败，已记录到日志
+    Failed(anyhow::Error),
+}
+
+/// 执行 unpack，按输入中的顺序返回每个文件块的路径与处理结果
+pub fn run(
+    input: Option<&Path>,
+    root: &Path,
+    dry_run: bool,
+    logger: &Logger,
+) -> anyhow::Result<Vec<(String, Outcome)>> {
+    let mut raw = Vec::new();
+    match input {
+        Some(path) if path != Path::new("-") => raw = fs::read(path)?,
+        _ => {
+            io::stdin().read_to_end(&mut raw)?;
+        }
+    }
+    let text = String::from_utf8_lossy(&raw);
+    let blocks = merge_parts(parse_blocks(&text));
+    if blocks.is_empty() {
+        logger.warn("（输入中没有可识别的文件块）");
+    }
+
+    let mut results = Vec::with_capacity(blocks.len());
+    for block in blocks {
+        let outcome = match skip_reason(&block) {
+            Some(reason) => {
+                logger.warn(&format!("提示: 跳过 {} ({})", block.path, reason));
+                Outcome::Skipped(reason)
+            }
+            None => match unpack_block(&block, root, dry_run) {
+                Ok(action) => {
+                    logger.info(&format!("{}: {}", action.label(), block.path));
+                    Outcome::Written(action)
+                }
+                Err(err) => {
+                    logger.error(&format!("错误: 无法写入 {}: {err}", block.path));
+                    Outcome::Failed(err)
+                }
+            },
+        };
+        results.push((block.path, outcome));
+    }
+    Ok(results)
+}
+
+// 输出中本来就不是原始内容的块不能写回
+fn skip_reason(block: &Block) -> Option<&'static str> {
+    let body = block.body.trim_end();
+    if body == "(skipped binary file)" || body == "(skipped: file exceeds max size)" {
+        return Some("content not included in dump");
+    }
+    if block.pages.is_some() || block.extracted {
+        return Some("extracted document text");
+    }
+    if block.diff {
+        return Some("diff output");
+    }
+    if block.lines.is_some() {
+        return Some("line range only");
+    }
+    if block.numbered {
+        return Some("line numbers added");
+    }
+    if block.part.is_some() {
+        return Some("incomplete split file");
+    }
+    if block.body.lines().any(is_snip_marker) {
+        return Some("clipped content");
+    }
+    None
+}
+
+fn is_snip_marker(line: &str) -> bool {
+    line.strip_prefix("... (snipped ")
+        .and_then(|rest| rest.strip_suffix(" lines) ..."))
+        .is_some_and(|n| n.parse::<usize>().is_ok())
+}
+
+fn unpack_block(block: &Block, root: &Path, dry_run: bool) -> anyhow::Result<Action> {
+    let bytes = block_bytes(block)?;
+    let target = safe_target(root, &block.path)?;
+    ensure_within(root, &target)?;
+
+    let action = match fs::read(&target) {
+        Ok(existing) if existing == bytes => Action::Unchanged,
+        Ok(_) => Action::Update,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Action::Create,
+        Err(err) => return Err(err.into()),
+    };
+    if dry_run || action == Action::Unchanged {
+        return Ok(action);
+    }
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&target, bytes)?;
+    Ok(action)
+}
+
+fn block_bytes(block: &Block) -> anyhow::Result<Vec<u8>> {
+    match block.binary.as_deref() {
+        Some("hex") => return Ok(hex::decode(block.body.trim())?),
+        Some("base64") => return Ok(Base64.decode(block.body.trim())?),
+        _ => {}
+    }
+    if let Some(label) = &block.encoding {
+        let encoding = Encoding::for_label(label.as_bytes())
+            .ok_or_else(|| anyhow::anyhow!("unknown encoding '{}'", label))?;
+        let (bytes, _, _) = encoding.encode(&block.body);
+        return Ok(bytes.into_owned());
+    }
+    Ok(block.body.clone().into_bytes())
+}
+
+// 只接受根目录之下的相对路径
+fn safe_target(root: &Path, rel: &str) -> anyhow::Result<PathBuf> {
+    let rel_path = Path::new(rel);
+    let mut clean = PathBuf::new();
+    for component in rel_path.components() {
+        match component {
+            Component::Normal(part) => clean.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                anyhow::bail!("path escapes target root: {}", rel)
+            }
+        }
+    }
+    if clean.as_os_str().is_empty() {
+        anyhow::bail!("empty path");
+    }
+    Ok(root.join(clean))
+}
+
+// 在创建任何目录之前检查：已存在的上级目录可能是指向根目录之外的符号链接，
+// 目标本身是符号链接时写入会落到链接指向的文件
+fn ensure_within(root: &Path, target: &Path) -> anyhow::Result<()> {
+    if fs::symlink_metadata(target).is_ok_and(|meta| meta.file_type().is_symlink()) {
+        anyhow::bail!("refusing to write through symlink: {}", target.display());
+    }
+    let root = resolve_existing(root)?;
+    let parent = target.parent().unwrap_or(target);
+    if !resolve_existing(parent)?.starts_with(&root) {
+        anyhow::bail!("refusing to write outside {}", root.display());
+    }
+    Ok(())
+}
+
+// 规范化路径中已存在的部分（解析符号链接），不存在的部分原样接在后面
+fn resolve_existing(path: &Path) -> io::Result<PathBuf> {
+    let mut existing = path;
+    let mut missing = Vec::new();
+    while let Err(err) = fs::symlink_metadata(existing) {
+        let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+            return Err(err);
+        };
+        if err.kind() != io::ErrorKind::NotFound {
+            return Err(err);
+        }
+        missing.push(name);
+        existing = match parent.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => parent,
+        };
+    }
+    // 悬空的符号链接在这里报错
+    let resolved = existing.canonicalize()?;
+    Ok(missing
+        .iter()
+        .rev()
+        .fold(resolved, |path, name| path.join(name)))
+}
+
+/// 把 `[part i/n]` 续接块按顺序拼回一个完整的块；
+/// 拼不完整的保留 `part` 标记，由 `skip_reason` 跳过
+fn merge_parts(blocks: Vec<Block>) -> Vec<Block> {
+    let mut merged: Vec<Block> = Vec::new();
+    // 当前块是否从 part 1 开始连续拼接
+    let mut from_first: Vec<bool> = Vec::new();
+    for block in blocks {
+        if let (Some((i, n)), Some(last)) = (block.part, merged.last_mut()) {
+            if i > 1 && last.path == block.path && last.part == Some((i - 1, n)) {
+                last.body.push_str(&block.body);
+                last.part = Some((i, n));
+                continue;
+            }
+        }
+        from_first.push(matches!(block.part, Some((1, _))));
+        merged.push(block);
+    }
+    for (block, from_first) in merged.iter_mut().zip(from_first) {
+        if from_first && matches!(block.part, Some((i, n)) if i == n) {
+            block.part = None;
+        }
+    }
+    merged
+}
+
+/// 解析 Equals / TripleBacktick / XmlTag 三种风格的文件块
+/// 只按 `\n` 分行，正文中的 `\r` 原样保留
+fn parse_blocks(text: &str) -> Vec<Block> {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut blocks = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let parsed = parse_equals_block(&lines, i)
+            .or_else(|| parse_backtick_block(&lines, i))
+            .or_else(|| parse_xml_block(&lines, i));
+        match parsed {
+            Some((block, next)) => {
+                blocks.push(block);
+                i = next;
+            }
+            None => i += 1,
+        }
+    }
+    blocks
+}
+
+// Header、Footer 与围栏行按去掉行尾 `\r` 后匹配，兼容整体转成 CRLF 的输出
+fn marker(line: &str) -> &str {
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+// ===path [GBK]=== ... ===end of 'path'===
+// 路径以 Footer 为准，Header 中路径之后的部分是附加标记
+fn parse_equals_block(lines: &[&str], start: usize) -> Option<(Block, usize)> {
+    let inner = marker(lines[start])
+        .strip_prefix("===")?
+        .strip_suffix("===")?;
+    if inner.is_empty() || inner.starts_with("end of '") {
+        return None;
+    }
+    for (offset, line) in lines[start + 1..].iter().enumerate() {
+        let Some(name) = marker(line)
+            .strip_prefix("===end of '")
+            .and_then(|l| l.strip_suffix("'==="))
+        else {
+            continue;
+        };
+        let Some(suffix) = inner.strip_prefix(name) else {
+            continue;
+        };
+        if !suffix.is_empty() && !suffix.starts_with(" [") {
+            continue;
+        }
+        let end = start + 1 + offset;
+        let mut block = new_block(name, &lines[start + 1..end]);
+        apply_suffix_marks(&mut block, suffix);
+        return Some((block, end + 1));
+    }
+    None
+}
+
+// ``` path [GBK] ... ```
+// 正文中也可能出现 ```，因此取下一个块之前的最后一个围栏
+fn parse_backtick_block(lines: &[&str], start: usize) -> Option<(Block, usize)> {
+    let header = backtick_header(marker(lines[start]))?;
+    let end = (start + 1..next_boundary(lines, start, "```"))
+        .rev()
+        .find(|&k| marker(lines[k]) == "```")?;
+
+    let (path, marks) = split_trailing_marks(header);
+    let mut block = new_block(path, &lines[start + 1..end]);
+    for mark in marks {
+        apply_mark(&mut block, mark);
+    }
+    Some((block, end + 1))
+}
+
+// 附加段落的名称（--tree、--toc、--summary）
+const SECTIONS: [&str; 3] = ["tree", "toc", "summary"];
+
+// 下一个块（或 summary 等附加段落）开始的行号，没有时为行数。
+// 只有紧跟在当前风格的 Footer 之后、带路径的完整 Header 才算边界：正文里的
+// "```" 后面接 "```lang"（相邻的两个 Markdown 代码块）不会截断当前块
+fn next_boundary(lines: &[&str], start: usize, footer: &str) -> usize {
+    (start + 2..lines.len())
+        .find(|&k| marker(lines[k - 1]) == footer && is_opening(marker(lines[k])))
+        .unwrap_or(lines.len())
+}
+
+// 任意风格的文件 Header 或附加段落的开头（```summary、<summary>、===summary===）
+fn is_opening(line: &str) -> bool {
+    let equals = line.len() > 6
+        && line.starts_with("===")
+        && line.ends_with("===")
+        && !line.starts_with("===end of ");
+    let backtick = backtick_header(line).is_some();
+    let xml = xml_header(line).is_some_and(|attrs| xml_attr(attrs, "path").is_some());
+    let section = SECTIONS.iter().any(|name| {
+        line.strip_prefix("```") == Some(name)
+            || line.strip_prefix('<').and_then(|l| l.strip_suffix('>')) == Some(name)
+    });
+    equals || backtick || xml || section
+}
+
+fn backtick_header(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix("``` ")?;
+    (!rest.trim().is_empty()).then_some(rest)
+}
+
+// <file path="..." encoding="..."> ... </file>
+fn parse_xml_block(lines: &[&str], start: usize) -> Option<(Block, usize)> {
+    let attrs = xml_header(marker(lines[start]))?;
+    let end = (start + 1..next_boundary(lines, start, "</file>"))
+        .rev()
+        .find(|&k| marker(lines[k]) == "</file>")?;
+
+    let path = xml_attr(attrs, "path")?;
+    let mut block = new_block(&path, &lines[start + 1..end]);
+    block.encoding = xml_attr(attrs, "encoding");
+    block.binary = xml_attr(attrs, "binary");
+    block.pages = xml_attr(attrs, "pages").and_then(|p| p.parse().ok());
+    block.extracted = xml_attr(attrs, "extracted").is_some();
+    block.diff = xml_attr(attrs, "change").is_some();
+    block.lines = xml_attr(attrs, "lines");
+    block.numbered = xml_attr(attrs, "numbered").is_some();
+    block.part = xml_attr(attrs, "part").and_then(|p| parse_part(&format!("part {}", p)));
+    Some((block, end + 1))
+}
+
+fn xml_header(line: &str) -> Option<&str> {
+    line.strip_prefix("<file ")?.strip_suffix('>')
+}
+
+fn xml_attr(attrs: &str, name: &str) -> Option<String> {
+    let needle = format!("{}=\"", name);
+    let mut search = attrs;
+    loop {
+        let pos = search.find(&needle)?;
+        // 确保匹配的是完整属性名
+        let boundary = pos == 0 || search.as_bytes()[pos - 1] == b' ';
+        let value_start = pos + needle.len();
+        if boundary {
+            let len = search[value_start..].find('"')?;
+            return Some(unescape_xml_attr(&search[value_start..value_start + len]));
+        }
+        search = &search[value_start..];
+    }
+}
+
+fn unescape_xml_attr(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn new_block(path: &str, body_lines: &[&str]) -> Block {
+    // 空文件输出时只占一个空行
+    let body = if body_lines == [""] {
+        String::new()
+    } else {
+        body_lines.iter().map(|l| format!("{}\n", l)).collect()
+    };
+    Block {
+        path: path.to_string(),
+        encoding: None,
+        binary: None,
+        pages: None,
+        extracted: false,
+        diff: false,
+        lines: None,
+        numbered: false,
+        part: None,
+        body,
+    }
+}
+
+fn apply_suffix_marks(block: &mut Block, suffix: &str) {
+    let mut rest = suffix;
+    while let Some(after) = rest.strip_prefix(" [") {
+        let Some(close) = after.find(']') else { break };
+        apply_mark(block, &after[..close]);
+        rest = &after[close + 1..];
+    }
+}
+
+// 从 `path [GBK] [hex]` 的尾部剥离能识别的标记
+fn split_trailing_marks(header: &str) -> (&str, Vec<&str>) {
+    let mut path = header;
+    let mut marks = Vec::new();
+    while path.ends_with(']') {
+        let Some(open) = path.rfind(" [") else { break };
+        let mark = &path[open + 2..path.len() - 1];
+        if !is_known_mark(mark) {
+            break;
+        }
+        marks.insert(0, mark);
+        path = &path[..open];
+    }
+    (path, marks)
+}
+
+fn is_known_mark(mark: &str) -> bool {
+    mark == "hex"
+        || mark == "base64"
+        || mark == "numbered"
+        || mark == "extracted"
+        || parse_pages(mark).is_some()
+        || parse_part(mark).is_some()
+        || parse_lines(mark).is_some()
+        || is_rev_mark(mark)
+        || is_change_mark(mark)
+        || Encoding::for_label(mark.as_bytes()).is_some()
+}
+
+fn parse_pages(mark: &str) -> Option<usize> {
+    let n = mark
+        .strip_suffix(" pages")
+        .or_else(|| mark.strip_suffix(" page"))?;
+    n.parse().ok()
+}
+
+// `printfiles diff` 的 `[added]` / `[renamed from old]` 等标记
+fn is_change_mark(mark: &str) -> bool {
+    matches!(mark, "added" | "deleted" | "modified") || mark.starts_with("renamed from ")
+}
+
+// `[rev main]`：内容是某个版本的完整文件，照常写回
+fn is_rev_mark(mark: &str) -> bool {
+    mark.strip_prefix("rev ").is_some_and(|rev| !rev.is_empty())
+}
+
+fn parse_lines(mark: &str) -> Option<&str> {
+    let ranges = mark.strip_prefix("lines ")?;
+    let valid = !ranges.is_empty()
+        && ranges
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '-' || c == ',');
+    valid.then_some(ranges)
+}
+
+fn parse_part(mark: &str) -> Option<(usize, usize)> {
+    let (i, n) = mark.strip_prefix("part ")?.split_once('/')?;
+    Some((i.parse().ok()?, n.parse().ok()?))
+}
+
+fn apply_mark(block: &mut Block, mark: &str) {
+    if mark == "hex" || mark == "base64" {
+        block.binary = Some(mark.to_string());
+    } else if let Some(n) = parse_pages(mark) {
+        block.pages = Some(n);
+    } else if is_change_mark(mark) {
+        block.diff = true;
+    } else if is_rev_mark(mark) {
+        // 版本号不影响写回的内容
+    } else if let Some(ranges) = parse_lines(mark) {
+        block.lines = Some(ranges.to_string());
+    } else if mark == "numbered" {
+        block.numbered = true;
+    } else if mark == "extracted" {
+        block.extracted = true;
+    } else if let Some(part) = parse_part(mark) {
+        block.part = Some(part);
+    } else {
+        block.encoding = Some(mark.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_all_divider_styles() {
+        let text = "===a b.txt [GBK]===\n中文\n===end of 'a b.txt'===\n\
+                    ``` bin/x.bin [hex]\n00ff\n```\n\
+                    <file path=\"q&quot;.md\">\n```rust\n```\n</file>\n";
+        let blocks = parse_blocks(text);
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0].path, "a b.txt");
+        assert_eq!(blocks[0].encoding.as_deref(), Some("GBK"));
+        assert_eq!(blocks[0].body, "中文\n");
+        assert_eq!(blocks[1].path, "bin/x.bin");
+        assert_eq!(blocks[1].binary.as_deref(), Some("hex"));
+        assert_eq!(blocks[2].path, "q\".md");
+        assert_eq!(blocks[2].body, "```rust\n```\n");
+    }
+
+    #[test]
+    fn carriage_returns_in_content_are_kept() {
+        let text = "===a.txt===\na\r\nb\n===end of 'a.txt'===\n\
+                    <file path=\"b.txt\">\r\nx\r\n</file>\r\n";
+        let blocks = parse_blocks(text);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].body, "a\r\nb\n");
+        assert_eq!(blocks[1].path, "b.txt");
+        assert_eq!(blocks[1].body, "x\r\n");
+    }
+
+    #[test]
+    fn split_parts_are_merged_in_order() {
+        let text = "===a.txt [part 1/2]===\none\n===end of 'a.txt'===\n\
+                    <file path=\"a.txt\" part=\"2/2\">\ntwo\n</file>\n\
+                    ===b.txt [part 2/2]===\ntail\n===end of 'b.txt'===\n";
+        let blocks = merge_parts(parse_blocks(text));
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].body, "one\ntwo\n");
+        assert_eq!(blocks[0].part, None);
+        assert_eq!(skip_reason(&blocks[1]), Some("incomplete split file"));
+    }
+
+    #[test]
+    fn backtick_fences_inside_content_are_kept() {
+        let text = "``` README.md\n```\ncode\n```\n```\n``` b.txt\nb\n```\n";
+        let blocks = parse_blocks(text);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].body, "```\ncode\n```\n");
+        assert_eq!(blocks[1].body, "b\n");
+    }
+
+    #[test]
+    fn back_to_back_markdown_fences_are_kept() {
+        let md = "# Notes\n```rust\nfn a() {}\n```\n```toml\n[x]\n```\nend\n";
+        let text = format!("``` notes.md\n{md}```\n``` b.txt\nb\n```\n```summary\nfiles: 2\n```\n");
+        let blocks = parse_blocks(&text);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].path, "notes.md");
+        assert_eq!(blocks[0].body, md);
+        assert_eq!(blocks[1].body, "b\n");
+    }
+
+    #[test]
+    fn safe_target_rejects_traversal() {
+        let root = Path::new("/tmp/root");
+        assert!(safe_target(root, "../etc/passwd").is_err());
+        assert!(safe_target(root, "a/../../b").is_err());
+        assert!(safe_target(root, "/etc/passwd").is_err());
+        assert_eq!(
+            safe_target(root, "./src/lib.rs").unwrap(),
+            root.join("src/lib.rs")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn safe_target_rejects_symlinks_out_of_root() -> anyhow::Result<()> {
+        use std::os::unix::fs::symlink;
+        let temp = assert_fs::TempDir::new()?;
+        let root = temp.path().join("out");
+        let outside = temp.path().join("outside");
+        fs::create_dir_all(&root)?;
+        fs::create_dir_all(&outside)?;
+        fs::write(outside.join("victim.txt"), "keep\n")?;
+        symlink("../outside/victim.txt", root.join("link.txt"))?;
+        symlink("../outside", root.join("dir"))?;
+
+        for path in ["link.txt", "dir/new.txt", "dir/sub/new.txt"] {
+            let block = new_block(path, &["pwned"]);
+            assert!(unpack_block(&block, &root, false).is_err(), "{path}");
+        }
+        assert_eq!(fs::read_to_string(outside.join("victim.txt"))?, "keep\n");
+        assert!(!outside.join("new.txt").exists());
+        assert!(!outside.join("sub").exists());
+
+        // 根目录尚不存在时照常创建
+        let fresh = temp.path().join("fresh/root");
+        let block = new_block("a/b.txt", &["ok"]);
+        assert_eq!(unpack_block(&block, &fresh, false)?, Action::Create);
+        Ok(())
+    }
+
+    #[test]
+    fn clipped_and_placeholder_blocks_are_skipped() {
+        let text = "===a===\nx\n... (snipped 3 lines) ...\ny\n===end of 'a'===\n\
+                    ===b===\n(skipped binary file)\n===end of 'b'===\n";
+        let blocks = parse_blocks(text);
+        assert_eq!(skip_reason(&blocks[0]), Some("clipped content"));
+        assert_eq!(
+            skip_reason(&blocks[1]),
+            Some("content not included in dump")
+        );
+    }
+}
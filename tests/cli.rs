@@ -228,3 +228,2138 @@ fn relative_from_rebases_output() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn gitignore_rules_apply_to_directories_and_globs() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child(".gitignore").write_str("target/\n*.log\n")?;
+    temp.child("src/.gitignore")
+        .write_str("!keep.log\ngenerated/\n")?;
+    temp.child("src/lib.rs").write_str("lib\n")?;
+    temp.child("src/keep.log").write_str("keep\n")?;
+    temp.child("src/drop.log").write_str("drop\n")?;
+    temp.child("src/generated/out.rs").write_str("gen\n")?;
+    temp.child("target/debug/build.rs").write_str("build\n")?;
+    temp.child(".git/HEAD")
+        .write_str("ref: refs/heads/main\n")?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).arg(".");
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    let text = String::from_utf8(stdout)?;
+
+    assert!(text.contains("===src/lib.rs==="));
+    assert!(text.contains("===src/keep.log==="));
+    assert!(!text.contains("===src/drop.log==="));
+    assert!(!text.contains("===src/generated/out.rs==="));
+    assert!(!text.contains("===target/debug/build.rs==="));
+    assert!(!text.contains("===.git/HEAD==="));
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).arg("**/*.rs");
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    let text = String::from_utf8(stdout)?;
+
+    assert_eq!(text, "===src/lib.rs===\nlib\n===end of 'src/lib.rs'===\n");
+
+    Ok(())
+}
+
+#[test]
+fn glob_into_ignored_directory_matches_like_the_directory() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child(".gitignore").write_str("target/\n*.log\n")?;
+    temp.child("target/notes.txt").write_str("notes\n")?;
+    temp.child("target/run.log").write_str("log\n")?;
+    temp.child("target/debug/deep.txt").write_str("deep\n")?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).arg("target/*.txt");
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    let text = String::from_utf8(stdout)?;
+    assert_eq!(
+        text,
+        "===target/notes.txt===\nnotes\n===end of 'target/notes.txt'===\n"
+    );
+
+    // 模式本身不在被忽略的目录下时，目录仍被跳过；目录下的规则照常生效
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).arg("**/*.txt");
+    cmd.assert()
+        .code(2)
+        .stderr(predicate::str::contains("未匹配到任何文件"));
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).arg("target/**/*.log");
+    cmd.assert()
+        .code(2)
+        .stderr(predicate::str::contains("未匹配到任何文件"));
+
+    Ok(())
+}
+
+#[test]
+fn ignore_file_and_git_excludes_are_respected() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("repo/.git/info/exclude")
+        .write_str("local.txt\n")?;
+    temp.child("repo/.ignore")
+        .write_str("scratch/\n.ignore\n")?;
+    temp.child("config/git/ignore").write_str("*.bak\n")?;
+    temp.child("repo/a.txt").write_str("A\n")?;
+    temp.child("repo/local.txt").write_str("local\n")?;
+    temp.child("repo/scratch/notes.txt").write_str("notes\n")?;
+    temp.child("repo/old.bak").write_str("old\n")?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.child("repo").path())
+        .env("HOME", temp.path())
+        .env("XDG_CONFIG_HOME", temp.child("config").path())
+        .arg(".");
+
+    cmd.assert()
+        .success()
+        .stdout("===a.txt===\nA\n===end of 'a.txt'===\n");
+
+    Ok(())
+}
+
+#[test]
+fn no_ignore_includes_ignored_files() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("dir/.gitignore").write_str("*.log\n")?;
+    temp.child("dir/app.log").write_str("log\n")?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["dir", "--ext", "log", "--no-ignore"]);
+
+    cmd.assert()
+        .success()
+        .stdout("===dir/app.log===\nlog\n===end of 'dir/app.log'===\n");
+
+    Ok(())
+}
+
+#[test]
+fn exclude_flag_and_negated_items_remove_paths() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("src/lib.rs").write_str("lib\n")?;
+    temp.child("src/generated/api.rs").write_str("api\n")?;
+    temp.child("src/notes.md").write_str("notes\n")?;
+    temp.child("tests/it.rs").write_str("it\n")?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["src,!src/generated/**", "tests", "--exclude", "*.md"]);
+
+    let expected = "===src/lib.rs===\nlib\n===end of 'src/lib.rs'===\n===tests/it.rs===\nit\n===end of 'tests/it.rs'===\n";
+    cmd.assert().success().stdout(expected);
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["src/**/*.rs", "--exclude", "src/generated"]);
+
+    cmd.assert()
+        .success()
+        .stdout("===src/lib.rs===\nlib\n===end of 'src/lib.rs'===\n");
+
+    Ok(())
+}
+
+#[test]
+fn excluding_everything_exits_with_two() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("src/lib.rs").write_str("lib\n")?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).args(["src", "!*.rs"]);
+
+    cmd.assert()
+        .code(2)
+        .stderr(predicate::str::contains("未匹配到任何文件"));
+
+    Ok(())
+}
+
+#[cfg(any(feature = "office", feature = "archives"))]
+fn write_zip(path: &std::path::Path, entries: &[(&str, &str)]) -> anyhow::Result<()> {
+    use std::io::Write;
+    let file = std::fs::File::create(path)?;
+    let mut zip = zip::ZipWriter::new(file);
+    for (name, body) in entries {
+        zip.start_file(*name, zip::write::SimpleFileOptions::default())?;
+        zip.write_all(body.as_bytes())?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+#[cfg(feature = "office")]
+#[test]
+fn reader_auto_extracts_docx_natively() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    let docx = temp.child("docs/report.docx");
+    docx.touch()?;
+    write_zip(
+        docx.path(),
+        &[(
+            "word/document.xml",
+            r#"<w:document xmlns:w="w"><w:body><w:p><w:r><w:t>Quarterly report</w:t></w:r></w:p><w:tbl><w:tr><w:tc><w:p><w:r><w:t>Q1</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>42</w:t></w:r></w:p></w:tc></w:tr></w:tbl></w:body></w:document>"#,
+        )],
+    )?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["docs/report.docx", "--reader", "auto"]);
+
+    cmd.assert().success().stdout(
+        "===docs/report.docx [extracted]===\nQuarterly report\nQ1\t42\n===end of 'docs/report.docx'===\n",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn reader_native_strips_html_and_rtf() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("a.html")
+        .write_str("<html><body><h1>Title</h1><p>Some <b>bold</b> text</p></body></html>")?;
+    temp.child("b.rtf")
+        .write_str(r"{\rtf1\ansi{\fonttbl{\f0 Arial;}}\f0 Plain \i italic\i0\par Next}")?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["a.html", "b.rtf", "--reader", "native"]);
+
+    let expected = "===a.html [extracted]===\nTitle\nSome bold text\n===end of 'a.html'===\n===b.rtf [extracted]===\nPlain italic\nNext\n===end of 'b.rtf'===\n";
+    cmd.assert().success().stdout(expected);
+
+    Ok(())
+}
+
+#[test]
+fn reader_native_falls_back_to_text_on_broken_docx() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("broken.docx").write_str("not a zip\n")?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["broken.docx", "--reader", "auto"]);
+
+    let output = cmd.assert().success().get_output().clone();
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        "===broken.docx===\nnot a zip\n===end of 'broken.docx'===\n"
+    );
+    assert!(String::from_utf8(output.stderr)?.contains("内置提取失败"));
+
+    Ok(())
+}
+
+// 生成一个最小的 PDF：每页一行 Helvetica 文本（传入空字符串则该页没有文字）
+fn minimal_pdf(pages: &[&str]) -> Vec<u8> {
+    let font_id = 3 + pages.len() * 2;
+    let kids: Vec<String> = (0..pages.len())
+        .map(|i| format!("{} 0 R", 3 + i * 2))
+        .collect();
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len()
+        ),
+    ];
+    for (i, text) in pages.iter().enumerate() {
+        let content = if text.is_empty() {
+            String::new()
+        } else {
+            format!("BT /F1 12 Tf 72 712 Td ({}) Tj ET", text)
+        };
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Contents {} 0 R /Resources << /Font << /F1 {} 0 R >> >> >>",
+            4 + i * 2,
+            font_id
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}\nendstream",
+            content.len(),
+            content
+        ));
+    }
+    objects.push("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string());
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (i, obj) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, obj).as_bytes());
+    }
+    let xref = pdf.len();
+    pdf.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+    );
+    for off in offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", off).as_bytes());
+    }
+    pdf.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .as_bytes(),
+    );
+    pdf
+}
+
+#[cfg(feature = "pdf")]
+#[test]
+fn reader_auto_extracts_pdf_pages() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("paper.pdf")
+        .write_binary(&minimal_pdf(&["Hello page one", "Second page"]))?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["paper.pdf", "--reader", "auto", "--divider", "xml-tag"]);
+
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    let text = String::from_utf8(stdout)?;
+
+    assert!(text
+        .starts_with("<file path=\"paper.pdf\" pages=\"2\" extracted=\"true\">\n--- page 1 ---\n"));
+    assert!(text.contains("Hello page one"));
+    assert!(text.contains("--- page 2 ---\n"));
+    assert!(text.contains("Second page"));
+    assert!(text.ends_with("</file>\n"));
+
+    Ok(())
+}
+
+#[test]
+fn pdf_without_text_uses_binary_strategy() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("scan.pdf").write_binary(&minimal_pdf(&[""]))?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["scan.pdf", "--reader", "auto"]);
+
+    let output = cmd.assert().success().get_output().clone();
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        "===scan.pdf===\n(skipped binary file)\n===end of 'scan.pdf'===\n"
+    );
+    assert!(String::from_utf8(output.stderr)?.contains("PDF 无法提取文本"));
+
+    Ok(())
+}
+
+#[test]
+fn reader_auto_renders_notebook_cells() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("nb.ipynb").write_str(
+        r##"{"nbformat": 4, "nbformat_minor": 5, "metadata": {}, "cells": [
+            {"cell_type": "markdown", "metadata": {}, "source": ["# Notes\n", "Intro"]},
+            {"cell_type": "code", "execution_count": 1, "metadata": {}, "source": ["print('a')\n", "print('b')"],
+             "outputs": [
+                {"output_type": "stream", "name": "stdout", "text": ["a\n", "b\n"]},
+                {"output_type": "display_data", "metadata": {}, "data": {"image/png": "iVBORw0KGgoAAAANSUhEUg=="}}
+             ]}
+        ]}"##,
+    )?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["nb.ipynb", "--reader", "auto"]);
+    cmd.assert().success().stdout(
+        "===nb.ipynb [extracted]===\n# %% [markdown]\n# Notes\nIntro\n\n# %%\nprint('a')\nprint('b')\n===end of 'nb.ipynb'===\n",
+    );
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["nb.ipynb", "--reader", "auto", "--notebook-outputs", "1"]);
+    let stdout = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    assert!(stdout.contains("print('b')\n# Out[1]:\n# a\n# ... (snipped 1 lines) ...\n===end"));
+    assert!(!stdout.contains("iVBORw0KGgo"));
+
+    // 默认的 text 读取仍输出原始 JSON
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).arg("nb.ipynb");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"nbformat\": 4"));
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn format_jsonl_emits_one_object_per_file() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("data/a.txt")
+        .write_str("one\ntwo\nthree\nfour\n")?;
+    temp.child("data/b.bin").write_binary(&[0u8, 255])?;
+    temp.child("data/c.log").write_str(&"x".repeat(64))?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).args([
+        "data",
+        "--format",
+        "jsonl",
+        "--binary",
+        "hex",
+        "--max-size",
+        "32",
+        "--clip",
+        "1:1",
+    ]);
+
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    let text = String::from_utf8(stdout)?;
+    let records: Vec<serde_json::Value> = text
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(records.len(), 3);
+
+    let a = &records[0];
+    assert_eq!(a["path"], "data/a.txt");
+    assert_eq!(a["size"], 19);
+    assert!(a["mtime"].is_u64());
+    assert_eq!(a["encoding"], "UTF-8");
+    assert_eq!(a["binary"], serde_json::Value::Null);
+    assert_eq!(a["clip"]["snipped_lines"], 2);
+    assert_eq!(a["content"], "one\n... (snipped 2 lines) ...\nfour\n");
+    assert_eq!(a["error"], serde_json::Value::Null);
+
+    let b = &records[1];
+    assert_eq!(b["path"], "data/b.bin");
+    assert_eq!(b["binary"], "hex");
+    assert_eq!(b["content"], "00ff");
+
+    let c = &records[2];
+    assert_eq!(c["skipped"], "max-size");
+    assert_eq!(c["content"], serde_json::Value::Null);
+
+    Ok(())
+}
+
+#[test]
+fn format_json_emits_array_with_detected_encoding() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    // "中文" 的 GBK 编码
+    temp.child("gbk.txt")
+        .write_binary(&[0xD6, 0xD0, 0xCE, 0xC4, b'\n'])?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["gbk.txt", "--format", "json"]);
+
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    let value: serde_json::Value = serde_json::from_slice(&stdout)?;
+    let records = value.as_array().expect("array");
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["path"], "gbk.txt");
+    assert_eq!(records[0]["encoding"], "GBK");
+    assert_eq!(records[0]["content"], "中文\n");
+
+    Ok(())
+}
+
+#[test]
+fn unpack_round_trips_every_divider_style() -> anyhow::Result<()> {
+    for divider in ["equals", "triple-backtick", "xml-tag"] {
+        let temp = assert_fs::TempDir::new()?;
+        temp.child("src/src/lib.rs").write_str("pub fn f() {}\n")?;
+        temp.child("src/docs/gbk.txt")
+            .write_binary(&[0xD6, 0xD0, 0xCE, 0xC4, b'\n'])?;
+        temp.child("src/assets/logo.bin")
+            .write_binary(&[0u8, 1, 2, 255])?;
+        temp.child("src/docs/crlf.txt").write_str("a\r\nb\r\n")?;
+        // 正文中形似其它风格 Header / Footer 的行
+        temp.child("src/docs/fence.md")
+            .write_str("Run:\n``` bash\nls\n```\n\n```\n")?;
+        temp.child("src/docs/equals.txt")
+            .write_str("===x===\nbody\n===end of 'x'===\n")?;
+        temp.child("src/docs/tags.xml")
+            .write_str("<file path=\"y\">\nz\n</file>\ntail\n")?;
+
+        let mut cmd = Command::cargo_bin("printfiles")?;
+        cmd.current_dir(temp.child("src").path()).args([
+            ".",
+            "--binary",
+            "base64",
+            "--divider",
+            divider,
+            "--summary",
+            "--quiet",
+        ]);
+        let dump = cmd.assert().success().get_output().stdout.clone();
+        temp.child("dump.txt").write_binary(&dump)?;
+
+        let mut cmd = Command::cargo_bin("printfiles")?;
+        cmd.current_dir(temp.path())
+            .args(["unpack", "dump.txt", "--to", "out"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::function(|out: &str| {
+                out.lines().all(|line| line.starts_with("create "))
+            }));
+
+        for rel in [
+            "src/lib.rs",
+            "docs/gbk.txt",
+            "docs/crlf.txt",
+            "docs/fence.md",
+            "docs/equals.txt",
+            "docs/tags.xml",
+            "assets/logo.bin",
+        ] {
+            let original = std::fs::read(temp.child("src").child(rel).path())?;
+            let restored = std::fs::read(temp.child("out").child(rel).path())?;
+            assert_eq!(original, restored, "{divider}: {rel}");
+        }
+        temp.child("out/bash").assert(predicate::path::missing());
+        temp.child("out/x").assert(predicate::path::missing());
+        temp.child("out/y").assert(predicate::path::missing());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn subcommand_names_always_run_the_subcommand() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("diff/a.txt").write_str("a\n")?;
+    temp.child("unpack/b.txt").write_str("b\n")?;
+    temp.child("config/c.txt").write_str("c\n")?;
+    temp.child("old/x.txt").write_str("1\n")?;
+    temp.child("new/x.txt").write_str("2\n")?;
+    let printfiles = || -> anyhow::Result<Command> {
+        let mut cmd = Command::cargo_bin("printfiles")?;
+        cmd.current_dir(temp.path())
+            .env("XDG_CONFIG_HOME", temp.child("xdg").path());
+        Ok(cmd)
+    };
+
+    // 同名目录存在时仍是子命令
+    printfiles()?
+        .args(["config", "show"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("# config files:\n"));
+    printfiles()?
+        .args(["diff", "unpack"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("./diff"));
+    printfiles()?
+        .args(["diff", "old", "new"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("===x.txt [modified]===\n"));
+
+    // 路径形式与 `--` 之后的参数是条目
+    let expected = "===diff/a.txt===\na\n===end of 'diff/a.txt'===\n===unpack/b.txt===\nb\n===end of 'unpack/b.txt'===\n";
+    printfiles()?
+        .args(["./diff", "./unpack"])
+        .assert()
+        .success()
+        .stdout(expected);
+    printfiles()?
+        .args(["--", "diff", "unpack"])
+        .assert()
+        .success()
+        .stdout(expected);
+
+    Ok(())
+}
+
+#[test]
+fn unpack_dry_run_lists_changes_without_writing() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("out/same.txt").write_str("same\n")?;
+    temp.child("out/old.txt").write_str("old\n")?;
+    temp.child("dump.txt").write_str(
+        "===same.txt===\nsame\n===end of 'same.txt'===\n\
+         ===old.txt===\nnew\n===end of 'old.txt'===\n\
+         ===new/file.txt===\nhello\n===end of 'new/file.txt'===\n",
+    )?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["unpack", "dump.txt", "--to", "out", "--dry-run"]);
+    cmd.assert()
+        .success()
+        .stdout("unchanged same.txt\nupdate old.txt\ncreate new/file.txt\n");
+
+    temp.child("out/old.txt").assert("old\n");
+    temp.child("out/new/file.txt")
+        .assert(predicate::path::missing());
+
+    Ok(())
+}
+
+#[test]
+fn unpack_skips_numbered_dumps() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("src/a.txt").write_str("one\ntwo\n")?;
+
+    for divider in ["equals", "triple-backtick", "xml-tag"] {
+        let mut dump = Command::cargo_bin("printfiles")?;
+        let output = dump
+            .current_dir(temp.path())
+            .args(["src", "-n", "--divider", divider])
+            .output()?;
+        assert!(output.status.success());
+
+        let mut cmd = Command::cargo_bin("printfiles")?;
+        cmd.current_dir(temp.path())
+            .args(["unpack", "--to", "out"])
+            .write_stdin(output.stdout);
+        cmd.assert()
+            .success()
+            .stdout("skip src/a.txt (line numbers added)\n");
+        temp.child("out").assert(predicate::path::missing());
+    }
+
+    // 不带行号的输出照常还原
+    let mut dump = Command::cargo_bin("printfiles")?;
+    let output = dump.current_dir(temp.path()).arg("src").output()?;
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["unpack", "--to", "out"])
+        .write_stdin(output.stdout);
+    cmd.assert().success().stdout("create src/a.txt\n");
+    temp.child("out/src/a.txt").assert("one\ntwo\n");
+
+    Ok(())
+}
+
+#[test]
+fn unpack_keeps_sources_of_extracted_text() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    let html = "<html><body><p>Hi</p><p>para</p></body></html>\n";
+    let notebook = r#"{"nbformat": 4, "nbformat_minor": 5, "metadata": {}, "cells": [
+        {"cell_type": "code", "execution_count": 1, "metadata": {}, "source": ["print(1)"], "outputs": []}
+    ]}
+"#;
+    temp.child("index.html").write_str(html)?;
+    temp.child("nb.ipynb").write_str(notebook)?;
+
+    for format in [
+        &["--divider", "equals"][..],
+        &["--divider", "triple-backtick"],
+        &["--divider", "xml-tag"],
+    ] {
+        let mut dump = Command::cargo_bin("printfiles")?;
+        let output = dump
+            .current_dir(temp.path())
+            .args(["--reader", "auto", "index.html", "nb.ipynb"])
+            .args(format)
+            .output()?;
+        assert!(output.status.success());
+
+        let mut cmd = Command::cargo_bin("printfiles")?;
+        cmd.current_dir(temp.path())
+            .args(["unpack", "--to", "."])
+            .write_stdin(output.stdout);
+        cmd.assert().success().stdout(
+            "skip index.html (extracted document text)\n\
+             skip nb.ipynb (extracted document text)\n",
+        );
+        temp.child("index.html").assert(html);
+        temp.child("nb.ipynb").assert(notebook);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn unpack_refuses_path_traversal() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("dump.txt").write_str(
+        "===../escape.txt===\nx\n===end of '../escape.txt'===\n\
+         ===ok.txt===\nok\n===end of 'ok.txt'===\n",
+    )?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["unpack", "dump.txt", "--to", "out"]);
+    cmd.assert()
+        .code(1)
+        .stderr(predicate::str::contains("path escapes target root"));
+
+    temp.child("escape.txt").assert(predicate::path::missing());
+    temp.child("out/ok.txt").assert("ok\n");
+
+    Ok(())
+}
+
+#[test]
+fn max_tokens_stops_or_clips_in_sorted_order() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("docs/a.txt").write_str("abcdefg\n")?;
+    temp.child("docs/b.txt")
+        .write_str(&"abcdefg\n".repeat(20))?;
+    temp.child("docs/c.txt").write_str("x\n")?;
+
+    // 预算按整个文件块计数：docs/a.txt 的文件块是 13 个 token
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["docs", "--tokenizer", "chars", "--max-tokens", "20"]);
+    let output = cmd.assert().success().get_output().clone();
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        "===docs/a.txt===\nabcdefg\n===end of 'docs/a.txt'===\n"
+    );
+    assert!(String::from_utf8(output.stderr)?.contains("其余 2 个条目未处理"));
+
+    // 未处理的条目不读取，--summary 按 not reached 列出
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).args([
+        "docs",
+        "--tokenizer",
+        "chars",
+        "--max-tokens",
+        "20",
+        "--summary",
+        "stderr",
+        "--quiet",
+    ]);
+    let stderr = String::from_utf8(cmd.assert().success().get_output().stderr.clone())?;
+    assert!(stderr.contains("not reached (token budget): 2\n"));
+    assert!(stderr.contains("  docs/c.txt: not reached (token budget)\n"));
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).args([
+        "docs",
+        "--tokenizer",
+        "chars",
+        "--max-tokens",
+        "40",
+        "--budget-mode",
+        "clip",
+    ]);
+    // 裁剪后的文件块连同 snipped 标记放得进剩余的 27 个 token
+    let output = cmd.assert().success().get_output().clone();
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        "===docs/a.txt===\nabcdefg\n===end of 'docs/a.txt'===\n\
+         ===docs/b.txt===\nabcdefg\nabcdefg\nabcdefg\nabcdefg\n... (snipped 16 lines) ...\n\
+         ===end of 'docs/b.txt'===\n"
+    );
+    assert!(String::from_utf8(output.stderr)?.contains("其余 1 个条目未处理"));
+
+    // 第一个文件就超过预算：没有文件块，给出说明并以 2 退出
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).args([
+        "docs/b.txt",
+        "docs/c.txt",
+        "--tokenizer",
+        "chars",
+        "--max-tokens",
+        "6",
+        "--quiet",
+    ]);
+    cmd.assert()
+        .code(2)
+        .stdout("")
+        .stderr(predicate::str::contains(
+            "第一个文件 docs/b.txt 就超过 token 上限 (max_tokens=6)",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn budget_clip_keeps_line_numbers_in_header_and_json() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("b.txt").write_str(&"abcdefg\n".repeat(20))?;
+    let args = [
+        "b.txt",
+        "-n",
+        "--tokenizer",
+        "chars",
+        "--budget-mode",
+        "clip",
+        "--max-tokens",
+    ];
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).args(args).arg("26");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    assert_eq!(
+        String::from_utf8(output.clone())?,
+        "===b.txt [numbered]===\n 1: abcdefg\n 2: abcdefg\n... (snipped 18 lines) ...\n\
+         ===end of 'b.txt'===\n"
+    );
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["unpack", "--to", "out"])
+        .write_stdin(output);
+    cmd.assert()
+        .success()
+        .stdout("skip b.txt (line numbers added)\n");
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(args)
+        .args(["80", "--format", "jsonl"]);
+    // JSON 记录的字段与转义也计入预算
+    let stdout = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    assert!(
+        stdout.contains("\"clip\":null,\"numbered\":true,\"budget_clip\":{\"snipped_lines\":16}")
+    );
+    assert!(stdout.contains(
+        "\"content\":\" 1: abcdefg\\n 2: abcdefg\\n 3: abcdefg\\n 4: abcdefg\\n... (snipped 16 lines) ...\\n\""
+    ));
+    assert!(stdout.len().div_ceil(4) <= 80);
+
+    Ok(())
+}
+
+#[cfg(feature = "tokens")]
+#[test]
+fn max_tokens_bounds_the_whole_output() -> anyhow::Result<()> {
+    use printfiles::tokens::{TokenCounter, Tokenizer};
+    let temp = assert_fs::TempDir::new()?;
+    for i in 0..200 {
+        temp.child(format!("f{i:03}.txt"))
+            .write_str(&format!("line {i}\n"))?;
+    }
+    temp.child("zz.txt")
+        .write_str(&"some longer text\n".repeat(100))?;
+    let counter = TokenCounter::new(Tokenizer::Cl100k);
+
+    for extra in [
+        &[][..],
+        &["--budget-mode", "clip"],
+        &["--tree", "--toc"],
+        &["--format", "jsonl"],
+        &["-n", "--divider", "xml-tag"],
+    ] {
+        let mut cmd = Command::cargo_bin("printfiles")?;
+        cmd.current_dir(temp.path())
+            .args([".", "--max-tokens", "500", "--quiet"])
+            .args(extra);
+        let output = cmd.assert().success().get_output().stdout.clone();
+        let tokens = counter.count(&String::from_utf8(output)?);
+        assert!((400..=500).contains(&tokens), "{extra:?}: {tokens}");
+    }
+
+    // --tree/--toc 的 token 也计入合计
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args([".", "--max-tokens", "500", "--tree", "--toc"])
+        .args(["--summary", "stderr"]);
+    let output = cmd.assert().success().get_output().clone();
+    let tokens = counter.count(&String::from_utf8(output.stdout)?);
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(
+        stderr.contains(&format!(", {tokens} tokens (cl100k)")),
+        "{stderr}"
+    );
+    assert!(stderr.contains("  zz.txt: not reached (token budget)\n"));
+
+    // 只差最后一个文件时按 clip 裁剪，连同 snipped 标记不超出预算
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["f000.txt", "zz.txt", "--max-tokens", "200"])
+        .args(["--budget-mode", "clip", "--summary", "stderr"]);
+    let output = cmd.assert().success().get_output().clone();
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.contains(" lines) ...\n===end of 'zz.txt'===\n"));
+    let tokens = counter.count(&stdout);
+    assert!((180..=200).contains(&tokens), "{tokens}");
+    assert!(String::from_utf8(output.stderr)?.contains(&format!(", {tokens} tokens (cl100k)")));
+
+    temp.close()?;
+    Ok(())
+}
+
+#[cfg(feature = "tokens")]
+#[test]
+fn verbose_reports_per_file_and_total_tokens() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("a.txt").write_str("hello world")?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).args(["a.txt", "--verbose"]);
+    let output = cmd.assert().success().get_output().clone();
+    let stderr = String::from_utf8(output.stderr)?;
+    // 计数整个文件块（Header、正文、Footer）
+    assert!(stderr.contains("tokens: a.txt = 15"));
+    assert!(stderr.contains("合计 tokens: 15 (cl100k)"));
+
+    Ok(())
+}
+
+#[test]
+fn split_bytes_packs_whole_blocks_and_writes_index() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("a.txt").write_str("A\n")?;
+    temp.child("b.txt").write_str("B\n")?;
+    temp.child("c.txt").write_str("C\n")?;
+
+    // 每个块 33 字节，上限 70 时前两个块装入第一个分卷
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).args([
+        "a.txt,b.txt,c.txt",
+        "--split-bytes",
+        "70",
+        "--output-dir",
+        "parts",
+    ]);
+    cmd.assert().success().stdout(predicate::str::is_empty());
+
+    temp.child("parts/part-001.txt")
+        .assert("===a.txt===\nA\n===end of 'a.txt'===\n===b.txt===\nB\n===end of 'b.txt'===\n");
+    temp.child("parts/part-002.txt")
+        .assert("===c.txt===\nC\n===end of 'c.txt'===\n");
+    temp.child("parts/index.txt")
+        .assert("part-001.txt\ta.txt\npart-001.txt\tb.txt\npart-002.txt\tc.txt\n");
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn oversized_file_is_split_with_continuation_headers() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    let content: String = (1..=20).map(|i| format!("line {:02}\n", i)).collect();
+    temp.child("src/big.txt").write_str(&content)?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.child("src").path()).args([
+        "big.txt",
+        "--split-bytes",
+        "100",
+        "--output-dir",
+        "../parts",
+    ]);
+    cmd.assert().success();
+
+    let mut joined = String::new();
+    for i in 1..=4 {
+        let part = std::fs::read_to_string(temp.child(format!("parts/part-00{i}.txt")).path())?;
+        assert!(part.len() <= 100, "part {i} too large: {}", part.len());
+        assert!(part.starts_with(&format!("===big.txt [part {i}/4]===\n")));
+        joined.push_str(&part);
+    }
+    temp.child("parts/part-005.txt")
+        .assert(predicate::path::missing());
+    temp.child("parts/index.txt")
+        .assert(predicate::str::contains(
+            "part-004.txt\tbig.txt [part 4/4]\n",
+        ));
+
+    // 按顺序拼接分卷后可以 unpack 回原文件
+    temp.child("joined.txt").write_str(&joined)?;
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["unpack", "joined.txt", "--to", "out"]);
+    cmd.assert().success();
+    temp.child("out/big.txt").assert(content.as_str());
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn line_numbers_work_with_clip_and_every_divider() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    let content: String = (1..=10).map(|i| format!("row {i}\n")).collect();
+    temp.child("a.txt").write_str(&content)?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["a.txt", "--line-numbers", "--clip", "2:1"]);
+    cmd.assert().success().stdout(
+        "===a.txt [numbered]===\n 1: row 1\n 2: row 2\n... (snipped 7 lines) ...\n10: row 10\n===end of 'a.txt'===\n",
+    );
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).args([
+        "a.txt",
+        "-n",
+        "--line-number-width",
+        "4",
+        "--line-number-separator",
+        "\t",
+        "--clip",
+        "1:0",
+        "--divider",
+        "xml-tag",
+    ]);
+    cmd.assert()
+        .success()
+        .stdout("<file path=\"a.txt\" numbered=\"true\">\n   1\trow 1\n... (snipped 9 lines) ...\n</file>\n");
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn line_range_items_print_only_the_requested_lines() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    let content: String = (1..=10).map(|i| format!("row {i}\n")).collect();
+    temp.child("a.txt").write_str(&content)?;
+    temp.child("b.txt").write_str("whole\n")?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["a.txt:#L8-L9,b.txt", "a.txt:2-3", "a.txt:3-4", "-n"]);
+    cmd.assert().success().stdout(
+        "===a.txt [lines 2-4,8-9] [numbered]===\n 2: row 2\n 3: row 3\n 4: row 4\n... (snipped 3 lines) ...\n 8: row 8\n 9: row 9\n===end of 'a.txt'===\n\
+         ===b.txt [numbered]===\n1: whole\n===end of 'b.txt'===\n",
+    );
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["a.txt:5", "--divider", "xml-tag"]);
+    let dump = cmd.assert().success().get_output().stdout.clone();
+    assert_eq!(
+        String::from_utf8(dump.clone())?,
+        "<file path=\"a.txt\" lines=\"5\">\nrow 5\n</file>\n"
+    );
+
+    // Header 只列出实际输出的行；目录条目上的行范围给出提示
+    temp.child("sub/c.txt").write_str("c\n")?;
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["a.txt:9-300", "b.txt:5-6", "sub:1-2"]);
+    cmd.assert()
+        .success()
+        .stdout(
+            "===a.txt [lines 9-10]===\nrow 9\nrow 10\n===end of 'a.txt'===\n\
+             ===b.txt [lines none]===\n\n===end of 'b.txt'===\n\
+             ===sub/c.txt===\nc\n===end of 'sub/c.txt'===\n",
+        )
+        .stderr(predicate::str::contains(
+            "提示: 行范围只作用于单个文件，忽略 sub:1-2 的行范围",
+        ));
+
+    // 部分内容不会被 unpack 写回
+    temp.child("dump.txt").write_binary(&dump)?;
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["unpack", "dump.txt", "--to", "out"]);
+    cmd.assert()
+        .success()
+        .stdout("skip a.txt (line range only)\n");
+
+    temp.close()?;
+    Ok(())
+}
+
+fn git(dir: &std::path::Path, args: &[&str]) -> anyhow::Result<()> {
+    let status = std::process::Command::new("git")
+        .args(["-c", "user.name=t", "-c", "user.email=t@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .status()?;
+    anyhow::ensure!(status.success(), "git {:?} failed", args);
+    Ok(())
+}
+
+#[test]
+fn git_selectors_pick_tracked_staged_modified_and_branch_files() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    let root = temp.path();
+    git(root, &["init", "-q", "-b", "main"])?;
+    temp.child("base.rs").write_str("base\n")?;
+    temp.child("notes.md").write_str("notes\n")?;
+    git(root, &["add", "."])?;
+    git(root, &["commit", "-q", "-m", "base"])?;
+
+    git(root, &["checkout", "-q", "-b", "feature"])?;
+    temp.child("feature.rs").write_str("feature\n")?;
+    git(root, &["add", "feature.rs"])?;
+    git(root, &["commit", "-q", "-m", "feature"])?;
+    temp.child("staged.rs").write_str("staged\n")?;
+    git(root, &["add", "staged.rs"])?;
+    temp.child("base.rs").write_str("base changed\n")?;
+    temp.child("untracked.rs").write_str("untracked\n")?;
+
+    let headers = |args: &[&str]| -> anyhow::Result<Vec<String>> {
+        let mut cmd = Command::cargo_bin("printfiles")?;
+        cmd.current_dir(root).args(args);
+        let output = cmd.assert().success().get_output().stdout.clone();
+        Ok(String::from_utf8(output)?
+            .lines()
+            .filter(|l| l.starts_with("===") && !l.starts_with("===end of"))
+            .map(str::to_string)
+            .collect())
+    };
+
+    assert_eq!(
+        headers(&["--git-tracked"])?,
+        [
+            "===base.rs===",
+            "===feature.rs===",
+            "===notes.md===",
+            "===staged.rs==="
+        ]
+    );
+    assert_eq!(headers(&["--git-staged"])?, ["===staged.rs==="]);
+    assert_eq!(headers(&["--git-modified"])?, ["===base.rs==="]);
+    assert_eq!(
+        headers(&["--git-changed-since", "main"])?,
+        ["===base.rs===", "===feature.rs===", "===staged.rs==="]
+    );
+    // 与 --ext 和 items 取交集
+    assert_eq!(
+        headers(&["--git-tracked", "--ext", "md"])?,
+        ["===notes.md==="]
+    );
+    assert_eq!(
+        headers(&["--git-changed-since", "main", "*.rs,!staged.rs"])?,
+        ["===base.rs===", "===feature.rs==="]
+    );
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(root)
+        .args(["--git-changed-since", "no-such-ref"]);
+    cmd.assert().failure();
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn rev_reads_files_from_the_object_database() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    let root = temp.path();
+    git(root, &["init", "-q", "-b", "main"])?;
+    temp.child("src/a.rs").write_str("old a\n")?;
+    temp.child("src/gone.rs").write_str("gone\n")?;
+    temp.child("README.md").write_str("readme\n")?;
+    git(root, &["add", "."])?;
+    git(root, &["commit", "-q", "-m", "base"])?;
+
+    temp.child("src/a.rs").write_str("new a\n")?;
+    temp.child("src/new.rs").write_str("new file\n")?;
+    std::fs::remove_file(temp.child("src/gone.rs").path())?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(root).args(["src/*.rs", "--rev", "main"]);
+    cmd.assert().success().stdout(
+        "===src/a.rs [rev main]===\nold a\n===end of 'src/a.rs'===\n\
+         ===src/gone.rs [rev main]===\ngone\n===end of 'src/gone.rs'===\n",
+    );
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.child("src").path()).args([
+        ".",
+        "--rev",
+        "HEAD",
+        "--divider",
+        "xml-tag",
+        "--exclude",
+        "gone.rs",
+    ]);
+    let dump = cmd.assert().success().get_output().stdout.clone();
+    assert_eq!(
+        String::from_utf8(dump.clone())?,
+        "<file path=\"a.rs\" rev=\"HEAD\">\nold a\n</file>\n"
+    );
+
+    // 版本内容可以直接 unpack 回工作区
+    temp.child("dump.txt").write_binary(&dump)?;
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(root)
+        .args(["unpack", "dump.txt", "--to", "src"]);
+    cmd.assert().success().stdout("update a.rs\n");
+    temp.child("src/a.rs").assert("old a\n");
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(root).args(["src", "--rev", "nope"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown git revision 'nope'"));
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn diff_compares_directories_with_change_marks() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("old/same.txt").write_str("same\n")?;
+    temp.child("old/edit.txt").write_str("one\ntwo\nthree\n")?;
+    temp.child("old/gone.txt").write_str("bye\n")?;
+    temp.child("old/moved.txt").write_str("moving content\n")?;
+    temp.child("old/skip.md").write_str("not compared\n")?;
+    temp.child("new/same.txt").write_str("same\n")?;
+    temp.child("new/edit.txt").write_str("one\n2\nthree\n")?;
+    temp.child("new/fresh.txt").write_str("hello\n")?;
+    temp.child("new/sub/moved.txt")
+        .write_str("moving content\n")?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["diff", "old", "new", "--ext", "txt"]);
+    cmd.assert().success().stdout(
+        "===edit.txt [modified]===\n\
+         --- a/edit.txt\n+++ b/edit.txt\n@@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n\
+         ===end of 'edit.txt'===\n\
+         ===fresh.txt [added]===\n\
+         --- /dev/null\n+++ b/fresh.txt\n@@ -0,0 +1 @@\n+hello\n\
+         ===end of 'fresh.txt'===\n\
+         ===gone.txt [deleted]===\n\
+         --- a/gone.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-bye\n\
+         ===end of 'gone.txt'===\n\
+         ===sub/moved.txt [renamed from moved.txt]===\n\
+         (no content changes)\n\
+         ===end of 'sub/moved.txt'===\n",
+    );
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn diff_accepts_git_revisions_and_binary_strategies() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    let root = temp.path();
+    git(root, &["init", "-q", "-b", "main"])?;
+    temp.child("a.txt").write_str("v1\n")?;
+    temp.child("img.bin").write_binary(&[0, 1, 2])?;
+    git(root, &["add", "."])?;
+    git(root, &["commit", "-q", "-m", "one"])?;
+    temp.child("a.txt").write_str("v2\n")?;
+    temp.child("img.bin").write_binary(&[0, 1, 3])?;
+    git(root, &["commit", "-q", "-am", "two"])?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(root)
+        .args(["diff", "HEAD~1", "HEAD", "--divider", "xml-tag"]);
+    cmd.assert().success().stdout(
+        "<file path=\"a.txt\" change=\"modified\">\n\
+         --- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-v1\n+v2\n</file>\n\
+         <file path=\"img.bin\" change=\"modified\">\n(binary files differ)\n</file>\n",
+    );
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(root)
+        .args(["diff", "HEAD~1", ".", "--binary", "hex", "--ext", "bin"]);
+    cmd.assert().success().stdout(
+        "===img.bin [modified] [hex]===\n\
+         --- a/img.bin\n+++ b/img.bin\n@@ -1 +1 @@\n-000102\n+000103\n\
+         ===end of 'img.bin'===\n",
+    );
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(root).args(["diff", "HEAD", "nowhere"]);
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "'nowhere' is neither a directory nor a git revision",
+    ));
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn tree_and_toc_precede_blocks_with_matching_offsets() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("src/lib.rs")
+        .write_str("pub fn a() {}\npub fn b() {}\n")?;
+    temp.child("README.md").write_str("readme\n")?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["README.md,src", "--tree", "--toc"]);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let text = String::from_utf8(output)?;
+
+    assert!(text.starts_with(
+        "===tree===\n.\n├── README.md (7 bytes, 1 line)\n└── src/\n    └── lib.rs (28 bytes, 2 lines)\n===end of tree===\n===toc===\n"
+    ));
+    // 目录中的字节偏移与行号都指向对应的 Header
+    let toc: Vec<&str> = text
+        .lines()
+        .skip_while(|l| *l != "===toc===")
+        .skip(1)
+        .take_while(|l| *l != "===end of toc===")
+        .collect();
+    assert_eq!(toc.len(), 2);
+    for row in toc {
+        let (path, position) = row.split_once(": byte ").unwrap();
+        let (byte, line) = position.split_once(", line ").unwrap();
+        let header = format!("==={}===", path);
+        assert!(text[byte.parse::<usize>()?..].starts_with(&header), "{row}");
+        assert_eq!(
+            text.lines().nth(line.parse::<usize>()? - 1),
+            Some(header.as_str())
+        );
+    }
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["README.md", "--tree", "--divider", "xml-tag"]);
+    cmd.assert().success().stdout(
+        "<tree>\n.\n└── README.md (7 bytes, 1 line)\n</tree>\n<file path=\"README.md\">\nreadme\n</file>\n",
+    );
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn summary_reports_counts_and_skipped_files() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("a.txt").write_str("1\n2\n3\n4\n")?;
+    temp.child("big.txt").write_str(&"x".repeat(100))?;
+    temp.child("blob.bin").write_binary(&[0, 1, 2])?;
+    temp.child("gbk.txt")
+        .write_binary(&[0xD6, 0xD0, 0xCE, 0xC4, b'\n'])?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).args([
+        "a.txt,big.txt,blob.bin,gbk.txt",
+        "--max-size",
+        "50",
+        "--clip",
+        "1:1",
+        "--tokenizer",
+        "chars",
+        "--summary",
+        "--quiet",
+    ]);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let text = String::from_utf8(output)?;
+    let footer = &text[text.find("===summary===").expect("summary")..];
+    let blocks = &text[..text.len() - footer.len()];
+
+    assert_eq!(
+        footer,
+        format!(
+            "===summary===\n\
+             printed: 2\n\
+             clipped: 1\n\
+             skipped (max size): 1\n\
+             skipped (binary): 1\n\
+             failed: 0\n\
+             non-UTF-8: 1\n\
+             total: {} bytes, {} lines, 62 tokens (chars/4)\n\
+             skipped files:\n  big.txt: exceeds max size\n  blob.bin: binary\n\
+             ===end of summary===\n",
+            blocks.len(),
+            blocks.lines().count()
+        )
+    );
+
+    // 发往 stderr 时标准输出保持不变
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["a.txt", "--summary", "stderr", "--format", "jsonl"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("summary").not())
+        .stderr(predicate::str::contains("===summary===\nprinted: 1\n"));
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn large_files_are_streamed_with_clip_ranges_and_numbers() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    // 超过 1 MiB 的流式阈值
+    let content: String = (1..=200_000).map(|i| format!("row {i}\n")).collect();
+    temp.child("big.log").write_str(&content)?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).arg("big.log");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    assert_eq!(
+        String::from_utf8(output)?,
+        format!("===big.log===\n{}===end of 'big.log'===\n", content)
+    );
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["big.log", "--clip", "2:2", "-n"]);
+    cmd.assert().success().stdout(
+        "===big.log [numbered]===\n     1: row 1\n     2: row 2\n... (snipped 199996 lines) ...\n199999: row 199999\n200000: row 200000\n===end of 'big.log'===\n",
+    );
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["big.log:3-4", "big.log:150000", "--format", "jsonl"]);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let record: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(
+        record["content"],
+        "row 3\nrow 4\n... (snipped 149995 lines) ...\nrow 150000\n"
+    );
+
+    // 开头含 NUL 的大文件按二进制处理
+    let mut blob = vec![0u8; 16];
+    blob.extend(content.as_bytes());
+    temp.child("big.bin").write_binary(&blob)?;
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).args(["big.bin", "--quiet"]);
+    cmd.assert()
+        .success()
+        .stdout("===big.bin===\n(skipped binary file)\n===end of 'big.bin'===\n");
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn large_files_detect_the_encoding_beyond_an_ascii_prefix() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    // 超过流式阈值；开头 1.1 MiB 是 ASCII，之后才出现 GBK 文本
+    let ascii: String = (1..=110_000).map(|i| format!("row {i:05}\n")).collect();
+    let chinese = "中文内容，编码探测\n".repeat(100);
+    let mut bytes = ascii.clone().into_bytes();
+    bytes.extend_from_slice(&encoding_rs::GBK.encode(&chinese).0);
+    temp.child("big.txt").write_binary(&bytes)?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["big.txt", "--summary=stderr"]);
+    let output = cmd.assert().success().get_output().clone();
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        format!("===big.txt [GBK]===\n{ascii}{chinese}===end of 'big.txt'===\n")
+    );
+    assert!(String::from_utf8(output.stderr)?.contains("non-UTF-8: 1\n"));
+
+    temp.close()?;
+    Ok(())
+}
+
+// 流式输出时内存占用与文件大小无关：输出开始后子进程的内存峰值远小于文件本身
+#[cfg(target_os = "linux")]
+#[test]
+fn large_files_are_written_without_buffering_the_block() -> anyhow::Result<()> {
+    use std::io::Read;
+    use std::process::Stdio;
+    let temp = assert_fs::TempDir::new()?;
+    let line = format!("{}\n", "x".repeat(63));
+    let size = 64 << 20;
+    temp.child("big.log")
+        .write_str(&line.repeat(size / line.len()))?;
+
+    // --verbose 与 --summary 需要计数 token 与统计输出大小
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("printfiles"))
+        .current_dir(temp.path())
+        .args([
+            "big.log",
+            "--verbose",
+            "--tokenizer",
+            "chars",
+            "--summary=stderr",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdout = child.stdout.take().unwrap();
+    let mut head = vec![0u8; 1 << 20];
+    stdout.read_exact(&mut head)?;
+    // 管道写满后子进程阻塞在输出上
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    let status = std::fs::read_to_string(format!("/proc/{}/status", child.id()))?;
+    let peak_kib: usize = status
+        .lines()
+        .find_map(|l| l.strip_prefix("VmHWM:"))
+        .and_then(|v| v.trim().trim_end_matches("kB").trim().parse().ok())
+        .unwrap();
+    let mut rest = Vec::new();
+    stdout.read_to_end(&mut rest)?;
+    let output = child.wait_with_output()?;
+    assert!(output.status.success());
+    assert!(peak_kib < 32 * 1024, "peak RSS {} KiB", peak_kib);
+
+    assert!(head.starts_with(b"===big.log===\nxxxx"));
+    assert_eq!(
+        head.len() + rest.len(),
+        size + "===big.log===\n===end of 'big.log'===\n".len()
+    );
+    let stderr = String::from_utf8(output.stderr)?;
+    // Header 与 Footer 也计入；按块计数时只有含 Header 或 Footer 的块可能向上取整
+    let tokens: usize = stderr
+        .split("tokens: big.log = ")
+        .nth(1)
+        .and_then(|rest| rest.lines().next())
+        .and_then(|n| n.parse().ok())
+        .unwrap();
+    let whole = (head.len() + rest.len()).div_ceil(4);
+    assert!(
+        tokens >= whole && tokens <= whole + 2,
+        "{tokens} vs {whole}"
+    );
+    assert!(stderr.contains(&format!(
+        "total: {} bytes, {} lines",
+        head.len() + rest.len(),
+        size / line.len() + 2
+    )));
+
+    temp.close()?;
+    Ok(())
+}
+
+// 没有换行的大文件（压缩过的脚本、单行日志）按段处理：裁剪、行号、计数与预算截断都不整行读入内存
+#[cfg(target_os = "linux")]
+#[test]
+fn large_files_without_newlines_are_processed_in_pieces() -> anyhow::Result<()> {
+    use std::io::Read;
+    use std::process::Stdio;
+    let temp = assert_fs::TempDir::new()?;
+    let size = 64 << 20;
+    temp.child("min.js").write_str(&"x".repeat(size))?;
+
+    // 运行到结束，期间轮询内存峰值；返回 (峰值 KiB, 标准输出长度, 标准输出开头, 标准错误)
+    let run = |args: &[&str]| -> anyhow::Result<(usize, usize, Vec<u8>, String)> {
+        let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("printfiles"))
+            .current_dir(temp.path())
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut stdout = child.stdout.take().unwrap();
+        let reader = std::thread::spawn(move || {
+            let mut head = Vec::new();
+            let mut buf = vec![0u8; 1 << 16];
+            let mut len = 0;
+            while let Ok(n @ 1..) = stdout.read(&mut buf) {
+                if head.len() < 64 {
+                    head.extend_from_slice(&buf[..n.min(64)]);
+                }
+                len += n;
+            }
+            (len, head)
+        });
+        let status_path = format!("/proc/{}/status", child.id());
+        let mut peak_kib = 0;
+        while child.try_wait()?.is_none() {
+            let peak = std::fs::read_to_string(&status_path)
+                .ok()
+                .and_then(|status| {
+                    status
+                        .lines()
+                        .find_map(|l| l.strip_prefix("VmHWM:"))
+                        .and_then(|v| v.trim().trim_end_matches("kB").trim().parse().ok())
+                });
+            peak_kib = peak_kib.max(peak.unwrap_or(0));
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        let (len, head) = reader.join().unwrap();
+        let output = child.wait_with_output()?;
+        assert!(output.status.success());
+        Ok((peak_kib, len, head, String::from_utf8(output.stderr)?))
+    };
+
+    let (peak_kib, len, head, stderr) = run(&[
+        "min.js",
+        "--clip",
+        "1:1",
+        "-n",
+        "--verbose",
+        "--tokenizer",
+        "chars",
+    ])?;
+    assert!(peak_kib < 32 * 1024, "peak RSS {} KiB", peak_kib);
+    assert!(head.starts_with(b"===min.js [numbered]===\n1: xxxx"));
+    assert_eq!(
+        len,
+        size + "===min.js [numbered]===\n1: \n===end of 'min.js'===\n".len()
+    );
+    // 强制切开的每一块各自取整，与整体计数只差几百
+    let tokens: usize = stderr
+        .lines()
+        .find_map(|l| l.strip_prefix("tokens: min.js = "))
+        .unwrap()
+        .parse()?;
+    assert!(tokens.abs_diff(size / 4) < 1000, "{tokens}");
+
+    let (peak_kib, len, head, _) = run(&[
+        "min.js",
+        "--tokenizer",
+        "chars",
+        "--max-tokens",
+        "1000",
+        "--budget-mode",
+        "clip",
+        "--quiet",
+    ])?;
+    assert!(peak_kib < 32 * 1024, "peak RSS {} KiB", peak_kib);
+    let expected = "===min.js===\n... (snipped 1 lines) ...\n===end of 'min.js'===\n";
+    assert_eq!(
+        (len, head.as_slice()),
+        (expected.len(), expected.as_bytes())
+    );
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn jobs_output_is_identical_to_serial() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    for i in 0..60 {
+        let content: String = (0..(i % 9) + 1).map(|j| format!("f{i} l{j}\n")).collect();
+        temp.child(format!("src/m{:02}.rs", i))
+            .write_str(&content)?;
+    }
+    temp.child("src/blob.bin").write_binary(b"\0\x01\x02")?;
+
+    let runs: [&[&str]; 3] = [
+        &["src", "--clip", "2:1", "-n", "--toc", "--summary"],
+        &["src", "--format", "jsonl", "--binary", "hex"],
+        &[
+            "src",
+            "--max-tokens",
+            "200",
+            "--budget-mode",
+            "clip",
+            "--tokenizer",
+            "chars",
+        ],
+    ];
+    for args in runs {
+        let mut cmd = Command::cargo_bin("printfiles")?;
+        cmd.current_dir(temp.path()).args(args).arg("--quiet");
+        let serial = cmd.assert().success().get_output().stdout.clone();
+        for jobs in ["4", "0"] {
+            let mut cmd = Command::cargo_bin("printfiles")?;
+            cmd.current_dir(temp.path())
+                .args(args)
+                .args(["--quiet", "--jobs", jobs]);
+            cmd.assert().success().stdout(serial.clone());
+        }
+    }
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn files_from_reads_literal_paths_from_a_file_or_stdin() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("a, b.txt").write_str("comma\n")?;
+    temp.child("dir/c d.txt").write_str("space\n")?;
+    temp.child("skip.txt").write_str("no\n")?;
+    temp.child("list.txt")
+        .write_str("a, b.txt\r\ndir/c d.txt\n\n")?;
+
+    let expected = "===a, b.txt===\ncomma\n===end of 'a, b.txt'===\n\
+                    ===dir/c d.txt===\nspace\n===end of 'dir/c d.txt'===\n";
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["--files-from", "list.txt"]);
+    cmd.assert().success().stdout(expected);
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["-0", "-@"])
+        .write_stdin("dir/c d.txt\0a, b.txt\0");
+    cmd.assert().success().stdout(expected);
+
+    // 与 items 同时使用时取并集；不存在的路径只给出提示
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["skip.txt", "--files-from", "-"])
+        .write_stdin("missing.txt\n");
+    cmd.assert()
+        .success()
+        .stdout("===skip.txt===\nno\n===end of 'skip.txt'===\n")
+        .stderr(predicate::str::contains("missing.txt"));
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn dash_reads_stdin_content_under_stdin_name() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("a.txt").write_str("file\n")?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["a.txt,-", "--stdin-name", "cargo-tree.txt", "--clip", "1:1"])
+        .write_stdin("root\n├── a\n├── b\n└── c\n");
+    cmd.assert().success().stdout(
+        "===a.txt===\nfile\n===end of 'a.txt'===\n\
+         ===cargo-tree.txt===\nroot\n... (snipped 2 lines) ...\n└── c\n===end of 'cargo-tree.txt'===\n",
+    );
+
+    // 同样经过编码探测与二进制策略；"中文" 的 GBK 编码
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .arg("-")
+        .write_stdin(vec![0xD6, 0xD0, 0xCE, 0xC4, b'\n']);
+    cmd.assert()
+        .success()
+        .stdout("===stdin [GBK]===\n中文\n===end of 'stdin'===\n");
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["-", "--binary", "hex", "--quiet"])
+        .write_stdin(b"\0\x01".to_vec());
+    cmd.assert()
+        .success()
+        .stdout("===stdin [hex]===\n0001\n===end of 'stdin'===\n");
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn content_filters_and_grep_select_files_and_hunks() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    let lib: String = (1..=12)
+        .map(|i| match i {
+            3 | 10 => format!("// TODO {i}\n"),
+            _ => format!("line {i}\n"),
+        })
+        .collect();
+    temp.child("lib.rs").write_str(&lib)?;
+    temp.child("main.rs").write_str("fn main() {}\n")?;
+    temp.child("gen.rs")
+        .write_str("// @generated\n// TODO later\n")?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).args([
+        ".",
+        "--contains",
+        "TODO",
+        "--not-contains",
+        "@generated",
+        "--divider",
+        "xml-tag",
+        "--clip",
+        "1:0",
+    ]);
+    cmd.assert()
+        .success()
+        .stdout("<file path=\"lib.rs\">\nline 1\n... (snipped 11 lines) ...\n</file>\n");
+
+    // 被过滤掉的文件列在汇总的跳过列表中
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).args([
+        ".",
+        "--contains",
+        "TODO",
+        "--not-contains",
+        "@generated",
+        "--summary",
+        "stderr",
+    ]);
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("skipped (filtered): 2\n"))
+        .stderr(predicate::str::contains(
+            "skipped files:\n  gen.rs: filtered\n  main.rs: filtered\n",
+        ));
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args([".", "--grep", "^// TODO", "-C", "1", "-n"]);
+    cmd.assert().success().stdout(
+        "===gen.rs [lines 1-2] [numbered]===\n1: // @generated\n2: // TODO later\n===end of 'gen.rs'===\n\
+         ===lib.rs [lines 2-4,9-11] [numbered]===\n 2: line 2\n 3: // TODO 3\n 4: line 4\n... (snipped 4 lines) ...\n 9: line 9\n10: // TODO 10\n11: line 11\n===end of 'lib.rs'===\n",
+    );
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args([".", "--grep", "nothing-here", "--quiet"]);
+    cmd.assert().code(2).stdout("");
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).args([".", "--contains", "("]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid --contains regex"));
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn config_files_and_profiles_supply_defaults_below_the_cli() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("xdg/printfiles/config.toml")
+        .write_str("divider = \"xml-tag\"\n")?;
+    temp.child("repo/.printfiles.toml").write_str(
+        "binary = \"skip\"\nexclude = [\"*.lock\"]\n\n\
+         [profile.review]\nline-numbers = true\nclip = \"1:0\"\n",
+    )?;
+    temp.child("repo/src/a.txt").write_str("one\ntwo\n")?;
+    temp.child("repo/src/Cargo.lock").write_str("lock\n")?;
+    let src = temp.child("repo/src");
+    let printfiles = || -> anyhow::Result<Command> {
+        let mut cmd = Command::cargo_bin("printfiles")?;
+        cmd.current_dir(src.path())
+            .env("XDG_CONFIG_HOME", temp.child("xdg").path());
+        Ok(cmd)
+    };
+
+    // 项目配置从当前目录向上查找，与用户配置合并
+    printfiles()?
+        .arg(".")
+        .assert()
+        .success()
+        .stdout("<file path=\"a.txt\">\none\ntwo\n</file>\n");
+
+    printfiles()?
+        .args([".", "--profile", "review", "--divider", "equals"])
+        .assert()
+        .success()
+        .stdout(
+            "===a.txt [numbered]===\n1: one\n... (snipped 1 lines) ...\n===end of 'a.txt'===\n",
+        );
+
+    printfiles()?
+        .args([".", "--no-config"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("===Cargo.lock==="));
+
+    printfiles()?
+        .args([".", "--profile", "llm"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "unknown profile 'llm' (available: review)",
+        ));
+
+    let output = printfiles()?
+        .args(["config", "show", "--profile", "review"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let shown = String::from_utf8(output)?;
+    assert!(shown.starts_with("# config files:\n#   "));
+    assert!(shown.contains("# profile: review\n"));
+    assert!(shown.contains("binary = \"skip\"  # "));
+    assert!(shown.contains("exclude = [\"*.lock\"]  # "));
+    assert!(shown.contains("line-numbers = true  # "));
+    assert!(shown.contains(".printfiles.toml [profile.review]\n"));
+    assert!(shown.contains("sort = \"name\"  # default\n"));
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn config_switches_can_be_turned_off_and_yield_to_conflicting_flags() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("xdg/printfiles/config.toml")
+        .write_str("tree = true\nline-numbers = true\nno-ignore = true\n")?;
+    temp.child("repo/.gitignore")
+        .write_str("*.log\n.gitignore\n")?;
+    temp.child("repo/a.txt").write_str("a\n")?;
+    temp.child("repo/b.log").write_str("b\n")?;
+    let printfiles = || -> anyhow::Result<Command> {
+        let mut cmd = Command::cargo_bin("printfiles")?;
+        cmd.current_dir(temp.child("repo").path())
+            .env("XDG_CONFIG_HOME", temp.child("xdg").path());
+        Ok(cmd)
+    };
+
+    printfiles()?
+        .arg(".")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("===tree===\n"))
+        .stdout(predicate::str::contains("===b.log [numbered]===\n"));
+
+    printfiles()?
+        .args([".", "--no-tree", "--no-line-numbers", "--ignore"])
+        .assert()
+        .success()
+        .stdout("===a.txt===\na\n===end of 'a.txt'===\n");
+
+    // 与命令行选项冲突的配置（tree 与 --split-bytes）让位于命令行
+    printfiles()?
+        .args([
+            ".",
+            "--ignore",
+            "--split-bytes",
+            "1000",
+            "--output-dir",
+            "out",
+        ])
+        .assert()
+        .success();
+    temp.child("repo/out/part-001.txt")
+        .assert("===a.txt [numbered]===\n1: a\n===end of 'a.txt'===\n");
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn config_settings_without_their_required_options_are_ignored() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child(".printfiles.toml")
+        .write_str("context = 1\nline-number-width = 3\n")?;
+    temp.child("a.rs").write_str("a\nfn b\nc\nd\n")?;
+    let printfiles = || -> anyhow::Result<Command> {
+        let mut cmd = Command::cargo_bin("printfiles")?;
+        cmd.current_dir(temp.path())
+            .env("XDG_CONFIG_HOME", temp.child("xdg").path());
+        Ok(cmd)
+    };
+
+    printfiles()?
+        .arg("a.rs")
+        .assert()
+        .success()
+        .stdout("===a.rs===\na\nfn b\nc\nd\n===end of 'a.rs'===\n");
+
+    printfiles()?
+        .args(["a.rs", "--grep", "fn", "-n"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("  1: a\n  2: fn b\n  3: c\n"))
+        .stdout(predicate::str::contains("  4: d").not());
+
+    temp.close()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn reader_commands_convert_by_extension_and_fall_back_on_failure() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("xdg/printfiles/config.toml")
+        .write_str("reader = \"auto\"\n\n[reader-cmd]\nup = \"tr a-z A-Z\"\n")?;
+    temp.child("a.up").write_str("hello\n")?;
+    temp.child("b.cat").write_str("via path\n")?;
+    temp.child("c.bad").write_str("raw\n")?;
+    temp.child("d.slow").write_str("slow\n")?;
+    let printfiles = || -> anyhow::Result<Command> {
+        let mut cmd = Command::cargo_bin("printfiles")?;
+        cmd.current_dir(temp.path())
+            .env("XDG_CONFIG_HOME", temp.child("xdg").path());
+        Ok(cmd)
+    };
+
+    // 没有 `{}` 时内容从标准输入传入；配置文件中可以写成表
+    printfiles()?
+        .arg("a.up")
+        .assert()
+        .success()
+        .stdout("===a.up [extracted]===\nHELLO\n===end of 'a.up'===\n");
+
+    printfiles()?
+        .args(["b.cat", "--reader-cmd", "cat=sed s/path/{}/ {}"])
+        .assert()
+        .success()
+        .stdout("===b.cat [extracted]===\nvia b.cat\n===end of 'b.cat'===\n");
+
+    printfiles()?
+        .args(["c.bad", "--reader-cmd", "bad=sh -c 'echo boom >&2; exit 3'"])
+        .assert()
+        .success()
+        .stdout("===c.bad===\nraw\n===end of 'c.bad'===\n")
+        .stderr(predicate::str::contains(
+            "外部命令 sh 处理失败 (exit status: 3: boom)",
+        ));
+
+    printfiles()?
+        .args([
+            "d.slow",
+            "--reader-cmd",
+            "slow=sleep 5",
+            "--reader-timeout",
+            "1",
+        ])
+        .assert()
+        .success()
+        .stdout("===d.slow===\nslow\n===end of 'd.slow'===\n")
+        .stderr(predicate::str::contains("超时 1s"));
+
+    // 只在 --reader auto 时生效
+    printfiles()?
+        .args(["a.up", "--reader", "text"])
+        .assert()
+        .success()
+        .stdout("===a.up===\nhello\n===end of 'a.up'===\n");
+
+    temp.close()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn project_config_cannot_enable_external_commands() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("xdg").create_dir_all()?;
+    temp.child("repo/.printfiles.toml").write_str(
+        "reader = \"auto\"\n\n[reader-cmd]\nrs = \"sh -c 'touch ../pwned; echo ran'\"\n\n\
+         [profile.p]\nreader-cmd = [\"rs=sh -c 'touch ../pwned; echo ran'\"]\n",
+    )?;
+    temp.child("repo/a.rs").write_str("fn main() {}\n")?;
+    let printfiles = || -> anyhow::Result<Command> {
+        let mut cmd = Command::cargo_bin("printfiles")?;
+        cmd.current_dir(temp.child("repo").path())
+            .env("XDG_CONFIG_HOME", temp.child("xdg").path());
+        Ok(cmd)
+    };
+
+    for args in [
+        &["a.rs"][..],
+        &["a.rs", "--profile", "p", "--reader", "auto"],
+    ] {
+        printfiles()?
+            .args(args)
+            .assert()
+            .success()
+            .stdout("===a.rs===\nfn main() {}\n===end of 'a.rs'===\n")
+            .stderr(predicate::str::contains(
+                "忽略项目配置中不受信任的 reader-cmd",
+            ));
+        temp.child("pwned").assert(predicate::path::missing());
+    }
+
+    printfiles()?
+        .args(["config", "show"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "# ignored without --trust-config: reader (",
+        ));
+
+    printfiles()?
+        .args(["a.rs", "--trust-config"])
+        .assert()
+        .success()
+        .stdout("===a.rs [extracted]===\nran\n===end of 'a.rs'===\n");
+    temp.child("pwned").assert(predicate::path::exists());
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn project_config_cannot_write_or_delete_files() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("xdg").create_dir_all()?;
+    temp.child("victim/part-001.txt").write_str("keep\n")?;
+    temp.child("victim/part-007.txt").write_str("keep\n")?;
+    temp.child("repo/.printfiles.toml").write_str(
+        "output-dir = \"../victim\"\nsplit-bytes = 1\n\n\
+         [profile.p]\noutput-dir = \"../victim\"\nfiles-from = \"../secret.txt\"\n",
+    )?;
+    temp.child("secret.txt")
+        .write_str("../victim/part-001.txt\n")?;
+    temp.child("repo/a.txt").write_str("a\n")?;
+
+    for args in [&["a.txt"][..], &["a.txt", "--profile", "p"]] {
+        Command::cargo_bin("printfiles")?
+            .current_dir(temp.child("repo").path())
+            .env("XDG_CONFIG_HOME", temp.child("xdg").path())
+            .args(args)
+            .assert()
+            .success()
+            .stdout("===a.txt===\na\n===end of 'a.txt'===\n")
+            .stderr(predicate::str::contains(
+                "忽略项目配置中不受信任的 output-dir",
+            ));
+        temp.child("victim/part-001.txt").assert("keep\n");
+        temp.child("victim/part-007.txt").assert("keep\n");
+        temp.child("victim/index.txt")
+            .assert(predicate::path::missing());
+    }
+
+    temp.close()?;
+    Ok(())
+}
+
+#[cfg(feature = "archives")]
+fn tar_bytes(entries: &[(&str, &str)]) -> anyhow::Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, body) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(body.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(1_700_000_000);
+        header.set_cksum();
+        builder.append_data(&mut header, name, body.as_bytes())?;
+    }
+    Ok(builder.into_inner()?)
+}
+
+#[cfg(feature = "archives")]
+#[test]
+fn archives_are_read_as_virtual_directories() -> anyhow::Result<()> {
+    use std::io::Write;
+    let temp = assert_fs::TempDir::new()?;
+    let zip = temp.child("bundle.zip");
+    zip.touch()?;
+    write_zip(
+        zip.path(),
+        &[
+            ("src/lib.rs", "pub fn lib() {}\n"),
+            ("src/gen/x.rs", "// generated\n"),
+            ("README.md", "# Bundle\n"),
+        ],
+    )?;
+    let tar = tar_bytes(&[("./pkg/a.txt", "one\ntwo\nthree\n"), ("pkg/b.md", "b\n")])?;
+    let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gz.write_all(&tar)?;
+    temp.child("release.tar.gz").write_binary(&gz.finish()?)?;
+    temp.child("release.tar.zst")
+        .write_binary(&zstd::encode_all(tar.as_slice(), 0)?)?;
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["bundle.zip/src/**/*.rs", "bundle.zip!/README.md"]);
+    cmd.assert().success().stdout(
+        "===bundle.zip!/README.md===\n# Bundle\n===end of 'bundle.zip!/README.md'===\n\
+         ===bundle.zip!/src/gen/x.rs===\n// generated\n===end of 'bundle.zip!/src/gen/x.rs'===\n\
+         ===bundle.zip!/src/lib.rs===\npub fn lib() {}\n===end of 'bundle.zip!/src/lib.rs'===\n",
+    );
+
+    // 整个归档按目录展开：应用 --ext 与排除模式
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["bundle.zip", "--ext", "rs", "--exclude", "gen/"]);
+    cmd.assert().success().stdout(
+        "===bundle.zip!/src/lib.rs===\npub fn lib() {}\n===end of 'bundle.zip!/src/lib.rs'===\n",
+    );
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["release.tar.gz", "release.tar.zst!/pkg/a.txt:2"]);
+    cmd.assert().success().stdout(
+        "===release.tar.gz!/pkg/a.txt===\none\ntwo\nthree\n===end of 'release.tar.gz!/pkg/a.txt'===\n\
+         ===release.tar.gz!/pkg/b.md===\nb\n===end of 'release.tar.gz!/pkg/b.md'===\n\
+         ===release.tar.zst!/pkg/a.txt [lines 2]===\ntwo\n===end of 'release.tar.zst!/pkg/a.txt'===\n",
+    );
+
+    // --max-size 按归档目录中的大小判断，超过的成员不解压
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path())
+        .args(["release.tar.gz", "--max-size", "5"]);
+    cmd.assert()
+        .success()
+        .stdout(
+            "===release.tar.gz!/pkg/a.txt===\n(skipped: file exceeds max size)\n\
+             ===end of 'release.tar.gz!/pkg/a.txt'===\n\
+             ===release.tar.gz!/pkg/b.md===\nb\n===end of 'release.tar.gz!/pkg/b.md'===\n",
+        )
+        .stderr(predicate::str::contains("size=14 > max_size=5"));
+
+    let mut cmd = Command::cargo_bin("printfiles")?;
+    cmd.current_dir(temp.path()).args(["bundle.zip/missing.rs"]);
+    cmd.assert().code(2).stderr(predicate::str::contains(
+        "'missing.rs' not found in bundle.zip",
+    ));
+
+    temp.close()?;
+    Ok(())
+}
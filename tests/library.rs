@@ -0,0 +1,122 @@
+use assert_fs::prelude::*;
+use printfiles::print::{self, PrintOptions, Status};
+use printfiles::unpack::{self, Action, Outcome};
+use printfiles::{
+    load_file, write_block, BinaryStrategy, BodyStyle, Collector, Divider, FileOutcome, HeaderMeta,
+    LoadedFile, Logger, ReadOptions, Reader, Renderer,
+};
+use std::path::Path;
+
+struct Upper;
+
+impl Reader for Upper {
+    fn read(&self, _path: &Path, bytes: &[u8], _opts: ReadOptions) -> anyhow::Result<LoadedFile> {
+        let text = String::from_utf8_lossy(bytes).to_uppercase();
+        Ok(LoadedFile::text(text, None))
+    }
+}
+
+struct Markdown;
+
+impl Renderer for Markdown {
+    fn header(&self, path: &str, _meta: HeaderMeta<'_>) -> String {
+        format!("## {}", path)
+    }
+
+    fn footer(&self, _path: &str) -> String {
+        String::new()
+    }
+
+    fn section(&self, name: &str, body: &str) -> String {
+        format!("## {}\n{}", name, body)
+    }
+}
+
+#[test]
+fn collector_reader_and_renderer_are_pluggable() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("src/a.rs").write_str("fn a() {}\n")?;
+    temp.child("src/b.txt").write_str("b\n")?;
+    temp.child("src/gen/c.rs").write_str("fn c() {}\n")?;
+
+    let mut collector = Collector::new().ext(Some("rs"));
+    collector.add(&temp.child("src").path().to_string_lossy())?;
+    let entries = collector.finish();
+
+    let logger = Logger::new(false, true);
+    let opts = ReadOptions {
+        binary: BinaryStrategy::Skip,
+        rev: None,
+        notebook_outputs: None,
+        logger: &logger,
+    };
+    let mut out = Vec::new();
+    for entry in &entries {
+        let outcome = FileOutcome::Loaded(load_file(&Upper, &entry.path, opts)?);
+        let rel = entry.display(Some(temp.path()));
+        write_block(
+            &mut out,
+            &Markdown,
+            &rel,
+            &outcome,
+            BodyStyle::default(),
+            None,
+        )?;
+    }
+
+    assert_eq!(
+        String::from_utf8(out)?,
+        "## src/a.rs\nFN A() {}\n\n## src/gen/c.rs\nFN C() {}\n\n"
+    );
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn print_and_unpack_run_without_the_cli() -> anyhow::Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("src/a.rs").write_str("fn a() {}\n")?;
+    temp.child("src/b.txt").write_str("b\n")?;
+    let src = temp.child("src").path().to_string_lossy().into_owned();
+
+    let logger = Logger::new(false, true);
+    let options = PrintOptions {
+        items: vec![src.clone()],
+        ext: Some("rs".into()),
+        relative_from: Some(temp.path().to_path_buf()),
+        divider: Divider::XmlTag,
+        ..PrintOptions::default()
+    };
+    let mut out = Vec::new();
+    assert_eq!(print::run(&options, &mut out, &logger)?, Status::Done);
+    assert_eq!(
+        String::from_utf8(out.clone())?,
+        "<file path=\"src/a.rs\">\nfn a() {}\n</file>\n"
+    );
+
+    let missing = PrintOptions {
+        items: vec![format!("{}/*.md", src)],
+        ..PrintOptions::default()
+    };
+    assert_eq!(
+        print::run(&missing, &mut Vec::new(), &logger)?,
+        Status::NoFiles
+    );
+
+    // unpack 把每个文件块的结果交给调用方，自身不输出
+    temp.child("dump.txt").write_binary(&out)?;
+    let results = unpack::run(
+        Some(temp.child("dump.txt").path()),
+        temp.child("out").path(),
+        false,
+        &logger,
+    )?;
+    assert!(matches!(
+        results.as_slice(),
+        [(path, Outcome::Written(Action::Create))] if path == "src/a.rs"
+    ));
+    temp.child("out/src/a.rs").assert("fn a() {}\n");
+
+    temp.close()?;
+    Ok(())
+}